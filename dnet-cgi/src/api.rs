@@ -0,0 +1,63 @@
+use dnet_types::response::Response;
+use management_client::DaemonRpcClient;
+
+/// CGI使用的management IPC接口
+pub trait DaemonApi {
+    fn status(&mut self) -> Option<Response>;
+    fn group_list(&mut self) -> Option<Response>;
+    fn group_users(&mut self, team_id: String) -> Option<Response>;
+    fn traffic(&mut self) -> Option<Response>;
+    fn login(&mut self, user: String) -> Option<Response>;
+    fn logout(&mut self) -> Option<Response>;
+    fn group_join(&mut self, team_id: String) -> Option<Response>;
+    fn group_leave(&mut self, team_id: String) -> Option<Response>;
+    fn tunnel_connect(&mut self) -> Option<Response>;
+    fn tunnel_disconnect(&mut self, team_id: String) -> Option<Response>;
+    fn settings_update(&mut self, update: String) -> Option<Response>;
+}
+
+impl DaemonApi for DaemonRpcClient {
+    fn status(&mut self) -> Option<Response> {
+        DaemonRpcClient::status(self).ok()
+    }
+
+    fn group_list(&mut self) -> Option<Response> {
+        DaemonRpcClient::group_list(self).ok()
+    }
+
+    fn group_users(&mut self, team_id: String) -> Option<Response> {
+        DaemonRpcClient::group_users(self, team_id).ok()
+    }
+
+    fn traffic(&mut self) -> Option<Response> {
+        DaemonRpcClient::traffic(self).ok()
+    }
+
+    fn login(&mut self, user: String) -> Option<Response> {
+        DaemonRpcClient::login(self, user).ok()
+    }
+
+    fn logout(&mut self) -> Option<Response> {
+        DaemonRpcClient::logout(self).ok()
+    }
+
+    fn group_join(&mut self, team_id: String) -> Option<Response> {
+        DaemonRpcClient::group_join(self, team_id).ok()
+    }
+
+    fn group_leave(&mut self, team_id: String) -> Option<Response> {
+        DaemonRpcClient::group_leave(self, team_id).ok()
+    }
+
+    fn tunnel_connect(&mut self) -> Option<Response> {
+        DaemonRpcClient::tunnel_connect(self).ok()
+    }
+
+    fn tunnel_disconnect(&mut self, team_id: String) -> Option<Response> {
+        DaemonRpcClient::tunnel_disconnect(self, team_id).ok()
+    }
+
+    fn settings_update(&mut self, update: String) -> Option<Response> {
+        DaemonRpcClient::settings_update(self, update).ok()
+    }
+}
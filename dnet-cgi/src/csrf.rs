@@ -0,0 +1,86 @@
+//! CSRF token. GET时通过cookie下发, POST必须在 X-CSRF-Token 头中带回.
+//! token保存在daemon home下的文件中, 各次CGI调用共享; login/logout后更换, 每个会话使用不同的token.
+
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+pub const CSRF_COOKIE: &str = "dnet_csrf";
+const CSRF_FILENAME: &str = "cgi.csrf";
+
+pub fn default_csrf_file() -> PathBuf {
+    #[cfg(target_os = "linux")]
+        let home = dnet_path::home_dir(None);
+    #[cfg(not(target_os = "linux"))]
+        let home = dnet_path::home_dir();
+    home.unwrap_or(std::env::temp_dir()).join(CSRF_FILENAME)
+}
+
+/// 已有token时返回原token, 否则生成新token
+pub fn issue(path: &Path) -> Option<String> {
+    if let Some(token) = load(path) {
+        return Some(token);
+    }
+    let token = new_token()?;
+    match create(path, &token) {
+        Ok(_) => Some(token),
+        // 同时有其他CGI进程创建
+        Err(ref e) if e.kind() == ErrorKind::AlreadyExists => load(path),
+        Err(_) => None,
+    }
+}
+
+/// 丢弃旧token, 生成新token
+pub fn rotate(path: &Path) -> Option<String> {
+    let token = new_token()?;
+    let tmp = path.with_extension("tmp");
+    let _ = fs::remove_file(&tmp);
+    create(&tmp, &token).ok()?;
+    // rename替换的是路径本身, path为符号链接时不会写到链接目标
+    fs::rename(&tmp, path).ok()?;
+    Some(token)
+}
+
+pub fn validate(path: &Path, header: Option<&String>) -> bool {
+    match (load(path), header) {
+        (Some(token), Some(header)) => constant_time_eq(token.as_bytes(), header.trim().as_bytes()),
+        _ => false,
+    }
+}
+
+/// O_EXCL: 已存在(包括符号链接)时失败, 不会覆盖其他文件
+fn create(path: &Path, token: &str) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+        options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    options.open(path)?.write_all(token.as_bytes())
+}
+
+fn load(path: &Path) -> Option<String> {
+    let mut options = fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+        options.custom_flags(libc::O_NOFOLLOW);
+    let mut token = String::new();
+    options.open(path).ok()?
+        .read_to_string(&mut token).ok()?;
+    Some(token.trim().to_string())
+        .filter(|token|!token.is_empty())
+}
+
+fn new_token() -> Option<String> {
+    let mut buf = [0u8; 16];
+    fs::File::open("/dev/urandom").ok()?
+        .read_exact(&mut buf).ok()?;
+    Some(buf.iter().map(|byte|format!("{:02x}", byte)).collect())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,38 @@
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(err_derive::Error, Debug)]
+#[allow(non_camel_case_types)]
+pub enum Error {
+    #[error(display = "Method not allowed")]
+    method_not_allowed,
+
+    #[error(display = "Unknown action {}", _0)]
+    unknown_action(String),
+
+    #[error(display = "Missing parameter {}", _0)]
+    missing_param(&'static str),
+
+    #[error(display = "Invalid request body")]
+    invalid_body,
+
+    #[error(display = "Read request body failed")]
+    read_body(#[error(cause)] std::io::Error),
+
+    #[error(display = "CSRF token invalid")]
+    csrf_failed,
+
+    #[error(display = "Failed to connect to daemon")]
+    ipc_connect_failed,
+}
+
+impl Error {
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Error::method_not_allowed => 405,
+            Error::unknown_action(_) => 404,
+            Error::missing_param(_) | Error::invalid_body | Error::read_body(_) => 400,
+            Error::csrf_failed => 403,
+            Error::ipc_connect_failed => 502,
+        }
+    }
+}
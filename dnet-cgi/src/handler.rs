@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use dnet_types::response::Response;
+use dnet_types::settings::SettingsUpdate;
+use dnet_types::team::Team;
+use dnet_types::user::User;
+use router_plugin::team_status_response::TeamStatusResponse;
+
+use crate::api::DaemonApi;
+use crate::csrf::{self, CSRF_COOKIE};
+use crate::error::{Error, Result};
+use crate::request::{CgiRequest, Method};
+
+pub struct CgiResponse {
+    pub status:         u16,
+    pub set_cookie:     Option<String>,
+    pub body:           String,
+    /// 旧接口只输出body
+    pub legacy:         bool,
+}
+
+impl CgiResponse {
+    fn json(status: u16, response: &Response) -> Self {
+        Self {
+            status,
+            set_cookie: None,
+            body: serde_json::to_string(response).unwrap_or(String::new()),
+            legacy: false,
+        }
+    }
+
+    fn from_error(e: Error) -> Self {
+        let status = e.http_status();
+        Self::json(status, &Response::new(status as u32, e.to_string(), None))
+    }
+
+    pub fn to_http(&self) -> String {
+        if self.legacy {
+            return self.body.clone() + "\n";
+        }
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Bad Gateway",
+        };
+        let mut buf = format!("Status: {} {}\r\nContent-Type: application/json\r\nCache-Control: no-store\r\n",
+                              self.status, reason);
+        if let Some(cookie) = &self.set_cookie {
+            buf += &format!("Set-Cookie: {}\r\n", cookie);
+        }
+        buf + "\r\n" + &self.body
+    }
+}
+
+/// 处理一次CGI请求. api为None表示连接daemon失败.
+pub fn handle<R: Read>(
+    env:        &HashMap<String, String>,
+    stdin:      &mut R,
+    api:        Option<&mut dyn DaemonApi>,
+    csrf_file:  &Path,
+) -> CgiResponse {
+    let request = match CgiRequest::parse(env, stdin) {
+        Ok(request) => request,
+        Err(e) => return CgiResponse::from_error(e),
+    };
+
+    let mut token = None;
+    if request.method == Method::Get {
+        token = csrf::issue(csrf_file);
+    }
+    else if !csrf::validate(csrf_file, request.csrf_header.as_ref()) {
+        return CgiResponse::from_error(Error::csrf_failed);
+    }
+
+    let mut response = match api {
+        Some(api) => match dispatch(&request, api, token.as_ref()) {
+            Ok(response) => response,
+            Err(e) => CgiResponse::from_error(e),
+        },
+        None => CgiResponse::from_error(Error::ipc_connect_failed),
+    };
+    // 会话变化后旧token失效
+    let session_changed = match request.action.as_ref().map(|action|action.as_str()) {
+        Some("login") | Some("logout") => request.method == Method::Post && response.status == 200,
+        _ => false,
+    };
+    if session_changed {
+        token = csrf::rotate(csrf_file);
+    }
+    response.set_cookie = token
+        .map(|token|format!("{}={}; Path=/; SameSite=Strict", CSRF_COOKIE, token));
+    response
+}
+
+fn dispatch(
+    request:    &CgiRequest,
+    api:        &mut dyn DaemonApi,
+    csrf_token: Option<&String>,
+) -> Result<CgiResponse> {
+    let action = match &request.action {
+        Some(action) => action.as_str(),
+        // 兼容旧的web页面, 不带action时输出team列表
+        None if request.method == Method::Get => return Ok(legacy_team_list(api)),
+        None => return Err(Error::missing_param("action")),
+    };
+
+    let response = match (&request.method, action) {
+        (Method::Get, "csrf") => {
+            Some(Response::success().set_data(Some(json!({ "csrf_token": csrf_token }))))
+        }
+        (Method::Get, "status") => api.status(),
+        (Method::Get, "teams") => api.group_list(),
+        (Method::Get, "members") => api.group_users(request.param("team_id")?),
+        (Method::Get, "traffic") => api.traffic(),
+        (Method::Post, "login") => {
+            let user = User::new(&request.param("name")?, &request.param("password")?);
+            api.login(user.to_json_str())
+        }
+        (Method::Post, "logout") => api.logout(),
+        (Method::Post, "join") => api.group_join(request.param("team_id")?),
+        (Method::Post, "leave") => api.group_leave(request.param("team_id")?),
+        (Method::Post, "connect") => api.tunnel_connect(),
+        (Method::Post, "disconnect") => api.tunnel_disconnect(request.param("team_id")?),
+        (Method::Post, "settings") => {
+            let update = serde_json::from_value::<SettingsUpdate>(request.body.clone())
+                .map_err(|_|Error::invalid_body)?;
+            api.settings_update(serde_json::to_string(&update).map_err(|_|Error::invalid_body)?)
+        }
+        (Method::Get, "login") | (Method::Get, "logout") | (Method::Get, "join")
+        | (Method::Get, "leave") | (Method::Get, "connect") | (Method::Get, "disconnect")
+        | (Method::Get, "settings") | (Method::Post, "csrf") | (Method::Post, "status")
+        | (Method::Post, "teams") | (Method::Post, "members") | (Method::Post, "traffic") => {
+            return Err(Error::method_not_allowed);
+        }
+        _ => return Err(Error::unknown_action(action.to_string())),
+    };
+
+    response
+        .map(|response|CgiResponse::json(200, &response))
+        .ok_or(Error::ipc_connect_failed)
+}
+
+fn legacy_team_list(api: &mut dyn DaemonApi) -> CgiResponse {
+    let team_status_response = api.group_list()
+        .and_then(|res|res.data)
+        .and_then(|teams_json|serde_json::from_value::<Vec<Team>>(teams_json).ok())
+        .map(TeamStatusResponse::from)
+        .unwrap_or(TeamStatusResponse {
+            code: 500,
+            teams: vec![],
+        });
+    CgiResponse {
+        status: 200,
+        set_cookie: None,
+        body: team_status_response.to_json_str(),
+        legacy: true,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use dnet_types::response::Response;
+    use crate::api::DaemonApi;
+    use super::handle;
+
+    #[derive(Default)]
+    struct FakeApi {
+        calls: Vec<String>,
+    }
+
+    impl FakeApi {
+        fn call(&mut self, call: String) -> Option<Response> {
+            self.calls.push(call);
+            Some(Response::success())
+        }
+    }
+
+    impl DaemonApi for FakeApi {
+        fn status(&mut self) -> Option<Response> { self.call("status".to_string()) }
+        fn group_list(&mut self) -> Option<Response> { self.call("group_list".to_string()) }
+        fn group_users(&mut self, team_id: String) -> Option<Response> { self.call("group_users ".to_string() + &team_id) }
+        fn traffic(&mut self) -> Option<Response> { self.call("traffic".to_string()) }
+        fn login(&mut self, user: String) -> Option<Response> { self.call("login ".to_string() + &user) }
+        fn logout(&mut self) -> Option<Response> { self.call("logout".to_string()) }
+        fn group_join(&mut self, team_id: String) -> Option<Response> { self.call("group_join ".to_string() + &team_id) }
+        fn group_leave(&mut self, team_id: String) -> Option<Response> { self.call("group_leave ".to_string() + &team_id) }
+        fn tunnel_connect(&mut self) -> Option<Response> { self.call("tunnel_connect".to_string()) }
+        fn tunnel_disconnect(&mut self, team_id: String) -> Option<Response> { self.call("tunnel_disconnect ".to_string() + &team_id) }
+        fn settings_update(&mut self, update: String) -> Option<Response> { self.call("settings_update ".to_string() + &update) }
+    }
+
+    fn csrf_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(k, v)|(k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_get_sets_csrf_cookie() {
+        let csrf_file = csrf_file("dnet_cgi_test_get.csrf");
+        let mut api = FakeApi::default();
+        let res = handle(&env(&[("REQUEST_METHOD", "GET"), ("QUERY_STRING", "action=members&team_id=t%201")]),
+                         &mut "".as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 200);
+        assert!(res.set_cookie.unwrap().starts_with("dnet_csrf="));
+        assert_eq!(api.calls, vec!["group_users t 1".to_string()]);
+        let _ = std::fs::remove_file(&csrf_file);
+    }
+
+    #[test]
+    fn test_post_requires_csrf_token() {
+        let csrf_file = csrf_file("dnet_cgi_test_post.csrf");
+        let mut api = FakeApi::default();
+        let body = r#"{"team_id":"123"}"#;
+        let len = body.len().to_string();
+        let res = handle(&env(&[("REQUEST_METHOD", "POST"), ("QUERY_STRING", "action=join"),
+                                ("CONTENT_LENGTH", &len)]),
+                         &mut body.as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 403);
+        assert!(api.calls.is_empty());
+
+        let token = crate::csrf::issue(&csrf_file).unwrap();
+        let res = handle(&env(&[("REQUEST_METHOD", "POST"), ("QUERY_STRING", "action=join"),
+                                ("CONTENT_LENGTH", &len), ("HTTP_X_CSRF_TOKEN", &token)]),
+                         &mut body.as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 200);
+        assert_eq!(api.calls, vec!["group_join 123".to_string()]);
+        assert!(res.to_http().starts_with("Status: 200 OK\r\nContent-Type: application/json\r\n"));
+        let _ = std::fs::remove_file(&csrf_file);
+    }
+
+    #[test]
+    fn test_errors() {
+        let csrf_file = csrf_file("dnet_cgi_test_errors.csrf");
+        let mut api = FakeApi::default();
+        let res = handle(&env(&[("REQUEST_METHOD", "GET"), ("QUERY_STRING", "action=nothing")]),
+                         &mut "".as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 404);
+
+        let res = handle(&env(&[("REQUEST_METHOD", "GET"), ("QUERY_STRING", "action=members")]),
+                         &mut "".as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 400);
+
+        let res = handle(&env(&[("REQUEST_METHOD", "GET"), ("QUERY_STRING", "action=login")]),
+                         &mut "".as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 405);
+
+        let res = handle(&env(&[("REQUEST_METHOD", "GET"), ("QUERY_STRING", "action=status")]),
+                         &mut "".as_bytes(), None, &csrf_file);
+        assert_eq!(res.status, 502);
+        assert!(api.calls.is_empty());
+        let _ = std::fs::remove_file(&csrf_file);
+    }
+
+    #[test]
+    fn test_login_rotates_csrf_token() {
+        let csrf_file = csrf_file("dnet_cgi_test_rotate.csrf");
+        let mut api = FakeApi::default();
+        let token = crate::csrf::issue(&csrf_file).unwrap();
+        let body = r#"{"name":"user","password":"pass"}"#;
+        let len = body.len().to_string();
+        let res = handle(&env(&[("REQUEST_METHOD", "POST"), ("QUERY_STRING", "action=login"),
+                                ("CONTENT_LENGTH", &len), ("HTTP_X_CSRF_TOKEN", &token)]),
+                         &mut body.as_bytes(), Some(&mut api), &csrf_file);
+        assert_eq!(res.status, 200);
+        let cookie = res.set_cookie.unwrap();
+        assert!(!cookie.contains(&token));
+        assert!(!crate::csrf::validate(&csrf_file, Some(&token)));
+
+        let new_token = crate::csrf::issue(&csrf_file).unwrap();
+        assert!(cookie.starts_with(&format!("dnet_csrf={};", new_token)));
+        let _ = std::fs::remove_file(&csrf_file);
+    }
+}
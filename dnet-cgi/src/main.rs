@@ -1,14 +1,20 @@
-//use std::env;
-//use std::fs;
-//use std::io::Read;
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 extern crate ipc_server;
-extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
 
 use management_client::{new_standalone_ipc_client, DaemonRpcClient};
-use dnet_types::team::Team;
-use router_plugin::team_status_response::TeamStatusResponse;
+
+mod api;
+mod csrf;
+mod error;
+mod handler;
+mod request;
+
+use api::DaemonApi;
 
 pub fn new_ipc_client() -> Option<DaemonRpcClient> {
     let path = dnet_path::ipc_path();
@@ -18,106 +24,20 @@ pub fn new_ipc_client() -> Option<DaemonRpcClient> {
     }
 }
 
-//enum RequestMethod {
-//    Get(GetAction),
-//    Post,
-//}
-//
-//#[allow(non_camel_case_types)]
-//enum GetAction {
-//    get_vpn_status,
-//}
-//
-//struct InputHandle;
-//
-//impl InputHandle {
-//    fn get_input() -> Option<RequestMethod> {
-//        if let Ok(method) = env::var("REQUEST_METHOD") {
-//            if &method == "GET" {
-//                if let Ok(env_input) = env::var("QUERY_STRING") {
-//                    if let Some(param) = Self::parse_get_args(&env_input) {
-//                        return Some(RequestMethod::Get(param));
-//                    }
-//                }
-//            } else {
-//                let input = Self::read_req_txt().unwrap_or("".to_owned());
-//                return Some(RequestMethod::Post);
-//            }
-//        }
-//        println!("REQUEST_METHOD env not found.");
-//        None
-//    }
-//
-//    fn read_req_txt() -> Option<String> {
-//        if let Ok(mut file) = fs::File::open("/tmp/req.txt") {
-//            let mut contents = String::new();
-//            if let Ok(_) = file.read_to_string(&mut contents) {
-//                return Some(contents);
-//            }
-//        }
-//        None
-//    }
-//
-//    fn parse_get_args(input: &str) -> Option<GetAction> {
-//        let mut action = None;
-//
-//        let fields: Vec<&str> = input.split("&").collect();
-//        for field in fields {
-//            let kv: Vec<&str> = field.split("=").collect();
-//            if kv.len() > 1 {
-//                let key = kv[0];
-//                let value = kv[1];
-//                action = match key {
-//                    "get_vpn_status" => Some(GetAction::get_vpn_status),
-//                    _ => None,
-//                };
-//            }
-//        }
-//        return action;
-//    }
-//}
-//
-//struct Exec;
-//
-//impl Exec {
-//    fn get_vpn_status() {
-//
-//    }
-//}
-
-//fn main() {
-//    if let Some(mut ipc) = new_ipc_client() {
-//        if let Some(request) = InputHandle::get_input() {
-//            match request {
-//                RequestMethod::Get(GetAction::get_vpn_status) => {
-//                    let teams = ipc.group_list().unwrap();
-//                    let team_status_response = TeamStatusResponse::from(teams);
-//                    let json = team_status_response.to_json_str();
-//                    println!("{}", json)
-//                },
-//                RequestMethod::Post => (),
-//            }
-//        }
-//    }
-//}
-
+/// GET  ?action=status|teams|members&team_id=|traffic|csrf
+/// POST ?action=login|logout|join|leave|connect|disconnect|settings, 需要 X-CSRF-Token
 fn main() {
-    if let Some(mut ipc) = new_ipc_client() {
-        let res = ipc.group_list().unwrap();
-        if let Some(teams_json) = res.data {
-            if let Ok(teams) = serde_json::from_value::<Vec<Team>>(teams_json) {
-                let team_status_response = TeamStatusResponse::from(teams);
-                let json = team_status_response.to_json_str();
-                println!("{}", json);
-                return;
-            }
-        }
-    }
-
-    let json = TeamStatusResponse {
-        code: 500,
-        teams: vec![],
-    }.to_json_str();
-    println!("{}", json);
+    let env = std::env::vars().collect::<HashMap<String, String>>();
+    let csrf_file = env.get("DNET_CGI_CSRF_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(csrf::default_csrf_file);
+
+    let mut ipc = new_ipc_client();
+    let api = ipc.as_mut().map(|ipc|ipc as &mut dyn DaemonApi);
+    let response = handler::handle(&env, &mut std::io::stdin(), api, &csrf_file);
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = stdout.write_all(response.to_http().as_bytes());
+    let _ = stdout.flush();
 }
-
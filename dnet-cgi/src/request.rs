@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use serde_json::Value;
+
+use crate::error::{Error, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+/// 由CGI环境变量和stdin解析的请求
+#[derive(Debug)]
+pub struct CgiRequest {
+    pub method:         Method,
+    pub action:         Option<String>,
+    pub query:          HashMap<String, String>,
+    pub body:           Value,
+    pub csrf_header:    Option<String>,
+}
+
+impl CgiRequest {
+    pub fn parse<R: Read>(env: &HashMap<String, String>, stdin: &mut R) -> Result<Self> {
+        let method = match env.get("REQUEST_METHOD").map(|method|method.to_uppercase()) {
+            Some(ref method) if method == "GET" => Method::Get,
+            Some(ref method) if method == "POST" => Method::Post,
+            _ => return Err(Error::method_not_allowed),
+        };
+
+        let query = env.get("QUERY_STRING")
+            .map(|query|parse_urlencoded(query))
+            .unwrap_or(HashMap::new());
+        let action = query.get("action").cloned();
+
+        let body = if method == Method::Post {
+            let content_length = env.get("CONTENT_LENGTH")
+                .and_then(|len|len.parse::<u64>().ok())
+                .unwrap_or(0);
+            let mut buf = String::new();
+            stdin.take(content_length).read_to_string(&mut buf)
+                .map_err(Error::read_body)?;
+            let is_form = env.get("CONTENT_TYPE")
+                .map(|content_type|content_type.starts_with("application/x-www-form-urlencoded"))
+                .unwrap_or(false);
+            if buf.trim().is_empty() {
+                Value::Object(serde_json::Map::new())
+            }
+            else if is_form {
+                serde_json::to_value(parse_urlencoded(&buf))
+                    .map_err(|_|Error::invalid_body)?
+            }
+            else {
+                serde_json::from_str(&buf).map_err(|_|Error::invalid_body)?
+            }
+        }
+        else {
+            Value::Null
+        };
+
+        Ok(Self {
+            method,
+            action,
+            query,
+            body,
+            csrf_header: env.get("HTTP_X_CSRF_TOKEN").cloned(),
+        })
+    }
+
+    /// 先查body再查query string
+    pub fn param(&self, key: &'static str) -> Result<String> {
+        self.body.get(key)
+            .and_then(|value|value.as_str())
+            .map(|value|value.to_string())
+            .or(self.query.get(key).cloned())
+            .filter(|value|!value.is_empty())
+            .ok_or(Error::missing_param(key))
+    }
+}
+
+fn parse_urlencoded(input: &str) -> HashMap<String, String> {
+    input.split('&')
+        .filter_map(|field| {
+            let mut kv = field.splitn(2, '=');
+            let key = url_decode(kv.next()?);
+            let value = url_decode(kv.next().unwrap_or(""));
+            if key.is_empty() {
+                return None;
+            }
+            Some((key, value))
+        })
+        .collect()
+}
+
+fn url_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()
+                    .and_then(|hex|u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            byte => out.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
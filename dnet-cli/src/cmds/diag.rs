@@ -0,0 +1,65 @@
+use std::fs::File;
+
+use clap::{App, Arg};
+use flate2::{Compression, write::GzEncoder};
+
+use crate::{new_ipc_client, Command};
+use crate::error::{Error, Result};
+
+pub struct Diag;
+
+impl Command for Diag {
+    fn name(&self) -> &'static str {
+        "diag"
+    }
+
+    fn clap_subcommand(&self) -> App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .args(&[
+                Arg::with_name("out")
+                    .long("out")
+                    .takes_value(true)
+                    .default_value("dnet-diag.tar.gz")
+                    .help("Bundle output path."),
+            ])
+            .about("Collect a diagnostics bundle, passwords, tokens and private keys stripped.")
+    }
+
+    fn run(&self, matches: &clap::ArgMatches<'_>) -> Result<()> {
+        let out = matches.value_of("out").unwrap_or("dnet-diag.tar.gz");
+
+        let mut ipc = new_ipc_client()?;
+        let res = ipc.diagnostics()
+            .map_err(Error::ipc_connect_failed)?;
+        if res.code != 200 {
+            println!("Response {:?}", res);
+            return Ok(());
+        }
+        let files = match res.data.as_ref().and_then(|data| data.as_object()) {
+            Some(files) => files,
+            None => {
+                println!("Response {:?}", res);
+                return Ok(());
+            }
+        };
+
+        let file = File::create(out).map_err(Error::write_bundle)?;
+        let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for (name, content) in files {
+            let content = content.as_str().unwrap_or("");
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(0);
+            header.set_cksum();
+            tar.append_data(&mut header, "dnet-diag/".to_string() + name, content.as_bytes())
+                .map_err(Error::write_bundle)?;
+        }
+        tar.into_inner()
+            .and_then(|gz| gz.finish())
+            .map_err(Error::write_bundle)?;
+
+        println!("Diagnostics bundle written to {}", out);
+        Ok(())
+    }
+}
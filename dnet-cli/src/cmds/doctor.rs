@@ -0,0 +1,52 @@
+use clap::App;
+use prettytable::Table;
+
+use dnet_types::doctor::{DoctorCheck, Verdict};
+
+use crate::{new_ipc_client, Command};
+use crate::error::{Error, Result};
+
+pub struct Doctor;
+
+impl Command for Doctor {
+    fn name(&self) -> &'static str {
+        "doctor"
+    }
+
+    fn clap_subcommand(&self) -> App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Connectivity self-check.")
+    }
+
+    fn run(&self, _matches: &clap::ArgMatches<'_>) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+        let res = ipc.doctor()
+            .map_err(Error::ipc_connect_failed)?;
+        let checks = res.data.clone()
+            .and_then(|data| serde_json::from_value::<Vec<DoctorCheck>>(data).ok());
+        match checks {
+            Some(checks) => print(checks),
+            None => println!("Response {:?}", res),
+        }
+        Ok(())
+    }
+}
+
+fn print(checks: Vec<DoctorCheck>) {
+    let mut table = Table::new();
+    table.add_row(row!["Check", "Result", "Detail", "Hint"]);
+    for check in checks {
+        let verdict = match check.verdict {
+            Verdict::Pass => "pass",
+            Verdict::Warn => "warn",
+            Verdict::Fail => "fail",
+        };
+        table.add_row(row![
+            check.name,
+            verdict,
+            check.detail,
+            check.hint.unwrap_or("".to_string()),
+        ]);
+    }
+    table.printstd();
+}
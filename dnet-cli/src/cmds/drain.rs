@@ -0,0 +1,33 @@
+use clap::App;
+use crate::{new_ipc_client, Command};
+use crate::error::Result;
+
+pub struct Drain;
+
+impl Command for Drain {
+    fn name(&self) -> &'static str {
+        "drain"
+    }
+
+    fn clap_subcommand(&self) -> App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Proxy only. Let clients migrate to other proxies, then stop daemon.")
+    }
+
+    fn run(&self, _matches: &clap::ArgMatches<'_>) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+
+        match ipc.drain() {
+            Ok(res) => {
+                if res.code == 200 {
+                    println!("Daemon draining, see dnet status.")
+                }
+                else {
+                    println!("Daemon drain failed. {}", res.msg)
+                }
+            }
+            Err(e) => println!("{:?}", e),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use clap::App;
+use prettytable::Table;
+
+use crate::{new_ipc_client, Command};
+use crate::error::{Error, Result};
+use dnet_types::identity::IdentityStatus;
+use dnet_types::response::Response;
+
+pub struct Identity;
+
+impl Command for Identity {
+    fn name(&self) -> &'static str {
+        "identity"
+    }
+
+    fn clap_subcommand(&self) -> App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Device identity reported to the conductor.")
+            .subcommand(
+                clap::SubCommand::with_name("migrate")
+                    .about("Switch to a stable identity. The conductor sees a new device, \
+                            log in again with the new device name.")
+                    .arg(
+                        clap::Arg::with_name("provider")
+                            .help("machine_id, dmi, vendor_sn or generated. \
+                                   Default the order of identity_providers in settings.toml."),
+                    ),
+            )
+    }
+
+    fn run(&self, matches: &clap::ArgMatches<'_>) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+        if let Some(migrate_matches) = matches.subcommand_matches("migrate") {
+            let provider = migrate_matches.value_of("provider").unwrap_or("").to_string();
+            let res = ipc.identity_migrate(provider)
+                .map_err(Error::ipc_connect_failed)?;
+            if res.code != 200 {
+                println!("{:#?}", res);
+                return Ok(());
+            }
+        }
+        let res = ipc.identity()
+            .map_err(Error::ipc_connect_failed)?;
+        print_identity(res);
+        Ok(())
+    }
+}
+
+fn print_identity(res: Response) {
+    match res.data.clone().and_then(|data|serde_json::from_value::<IdentityStatus>(data).ok()) {
+        Some(status) => {
+            let mut table = Table::new();
+            table.set_titles(row!["Provider", "Id", "Device name", "Resolved at"]);
+            table.add_row(row![
+                status.identity.provider.name(),
+                status.identity.id,
+                status.device_name,
+                status.identity.resolved_at,
+            ]);
+            table.printstd();
+        }
+        None => println!("{:#?}", res),
+    }
+}
@@ -27,8 +27,6 @@ impl Command for Login {
 
         let user = User::new(&name, &password).to_json_str();
 
-        println!("{:?}", user);
-
         let mut ipc = new_ipc_client()?;
         match ipc.login(user) {
             Ok(res) => {
@@ -6,18 +6,33 @@ use std::collections::HashMap;
 mod connect;
 pub use self::connect::Connect;
 
+mod diag;
+pub use self::diag::Diag;
+
 mod disconnect;
 pub use self::disconnect::Disconnect;
 
+mod doctor;
+pub use self::doctor::Doctor;
+
+mod drain;
+pub use self::drain::Drain;
+
 mod group;
 pub use self::group::Group;
 
+mod identity;
+pub use self::identity::Identity;
+
 mod login;
 pub use self::login::Login;
 
 mod logout;
 pub use self::logout::Logout;
 
+mod profile;
+pub use self::profile::Profile;
+
 mod shutdown;
 pub use self::shutdown::Shutdown;
 
@@ -29,10 +44,15 @@ pub fn get_commands() -> HashMap<&'static str, Box<dyn Command>> {
     let mut map = HashMap::new();
     let commands: Vec<Box<dyn Command>> = vec![
         Box::new(Connect),
+        Box::new(Diag),
         Box::new(Disconnect),
+        Box::new(Doctor),
+        Box::new(Drain),
         Box::new(Group),
+        Box::new(Identity),
         Box::new(Login),
         Box::new(Logout),
+        Box::new(Profile),
         Box::new(Shutdown),
         Box::new(Status),
     ];
@@ -0,0 +1,140 @@
+use clap::{App, Arg};
+use clap::value_t_or_exit;
+use prettytable::Table;
+
+use crate::{new_ipc_client, Command};
+use crate::error::{Error, Result};
+use dnet_types::settings::{Profile as TypeProfile, ProfileList};
+
+pub struct Profile;
+
+impl Command for Profile {
+    fn name(&self) -> &'static str {
+        "profile"
+    }
+
+    fn clap_subcommand(&self) -> App<'static, 'static> {
+        clap::SubCommand::with_name(self.name())
+            .about("Conductor and account profiles.")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                clap::SubCommand::with_name("list")
+                    .about("List of all the profiles."),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("add")
+                    .about("Add or replace a profile.")
+                    .args(&[
+                        Arg::with_name("name")
+                            .help("Profile name, letters, digits, - and _.")
+                            .required(true),
+                        Arg::with_name("conductor_url")
+                            .help("Conductor url.")
+                            .required(true),
+                        Arg::with_name("user name")
+                            .required(true),
+                        Arg::with_name("password")
+                            .required(true),
+                        Arg::with_name("accept_invalid_certs")
+                            .long("accept-invalid-certs")
+                            .help("Accept invalid conductor certificates."),
+                    ]),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("use")
+                    .about("Log out, and log in with the profile.")
+                    .arg(
+                        clap::Arg::with_name("name")
+                            .required(true),
+                    ),
+            )
+            .subcommand(
+                clap::SubCommand::with_name("remove")
+                    .about("Remove a profile.")
+                    .arg(
+                        clap::Arg::with_name("name")
+                            .required(true),
+                    ),
+            )
+    }
+
+    fn run(&self, matches: &clap::ArgMatches<'_>) -> Result<()> {
+        if let Some(_matches) = matches.subcommand_matches("list") {
+            self.profile_list()?;
+        } else if let Some(set_matches) = matches.subcommand_matches("add") {
+            let profile = TypeProfile {
+                name:           value_t_or_exit!(set_matches.value_of("name"), String),
+                conductor_url:  value_t_or_exit!(set_matches.value_of("conductor_url"), String),
+                username:       value_t_or_exit!(set_matches.value_of("user name"), String),
+                password:       value_t_or_exit!(set_matches.value_of("password"), String),
+                accept_conductor_invalid_certs: set_matches.is_present("accept_invalid_certs"),
+                from_file:      false,
+            };
+            self.profile_add(profile)?;
+        } else if let Some(set_matches) = matches.subcommand_matches("use") {
+            let name = value_t_or_exit!(set_matches.value_of("name"), String);
+            self.profile_use(name)?;
+        } else if let Some(set_matches) = matches.subcommand_matches("remove") {
+            let name = value_t_or_exit!(set_matches.value_of("name"), String);
+            self.profile_remove(name)?;
+        } else {
+            unreachable!("No profile command given");
+        }
+        Ok(())
+    }
+}
+
+impl Profile {
+    fn profile_list(&self) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+        let res = ipc.profile_list()
+            .map_err(Error::ipc_connect_failed)?;
+        match res.data.clone().and_then(|data|serde_json::from_value::<ProfileList>(data).ok()) {
+            Some(list) => print_profile(list),
+            None => println!("{:#?}", res),
+        }
+        Ok(())
+    }
+
+    fn profile_add(&self, profile: TypeProfile) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+        let profile = serde_json::to_string(&profile).unwrap();
+        let res = ipc.profile_add(profile)
+            .map_err(Error::ipc_connect_failed)?;
+        println!("{:#?}", res);
+        Ok(())
+    }
+
+    fn profile_use(&self, name: String) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+        let res = ipc.profile_use(name)
+            .map_err(Error::ipc_connect_failed)?;
+        println!("{:#?}", res);
+        Ok(())
+    }
+
+    fn profile_remove(&self, name: String) -> Result<()> {
+        let mut ipc = new_ipc_client()?;
+        let res = ipc.profile_remove(name)
+            .map_err(Error::ipc_connect_failed)?;
+        println!("{:#?}", res);
+        Ok(())
+    }
+}
+
+fn print_profile(list: ProfileList) {
+    let mut table = Table::new();
+    table.set_titles(row!["", "Name", "Conductor", "User", "Invalid Certs", "Source"]);
+    for profile in list.profiles {
+        let active = if list.active.as_ref() == Some(&profile.name) { "*" } else { "" };
+        table.add_row(row![
+            active,
+            profile.name,
+            profile.conductor_url,
+            profile.username,
+            if profile.accept_conductor_invalid_certs { "accept" } else { "" },
+            if profile.from_file { "settings.toml" } else { "added" },
+        ]);
+    }
+    table.printstd();
+}
@@ -48,10 +48,67 @@ impl ResponseData {
         table.add_row(row!["Tunnel", "Cloud", "Daemon", "Vip"]);
         table.add_row(row![
              format!("{:?}", self.status.tunnel),
-             format!("{:?}", self.status.rpc),
+             match &self.status.cached {
+                 Some(saved_at) => format!("{:?} (cached {})", self.status.rpc, saved_at),
+                 None => format!("{:?}", self.status.rpc),
+             },
              format!("{:?}", self.status.daemon),
              self.vip.map(|vip|vip.to_string()).unwrap_or("".to_string()),
         ]);
         table.printstd();
+
+        if let Some(draining) = &self.status.draining {
+            let mut table = Table::new();
+            table.add_row(row!["Draining since", "Deadline", "Connections"]);
+            table.add_row(row![draining.started_at, draining.deadline, draining.connections]);
+            table.printstd();
+        }
+
+        if !self.status.security_events.is_empty() {
+            let mut table = Table::new();
+            table.add_row(row!["Security event", "Node", "Detail", "Kicked", "Time"]);
+            for event in &self.status.security_events {
+                table.add_row(row![
+                     format!("{:?}", event.kind),
+                     event.node,
+                     event.detail,
+                     event.kicked,
+                     event.time,
+                ]);
+            }
+            table.printstd();
+        }
+
+        let team_sync = self.status.team_sync;
+        if team_sync.reconcile_total > 0 {
+            let mut table = Table::new();
+            table.add_row(row!["Team sync", "Drift", "Drift total", "Failed teams"]);
+            table.add_row(row![
+                 team_sync.last_reconcile.unwrap_or("".to_string()),
+                 team_sync.last_drift,
+                 format!("{}/{}", team_sync.drift_total, team_sync.reconcile_total),
+                 team_sync.failed_teams.join(","),
+            ]);
+            table.printstd();
+        }
+
+        if !self.status.proxy_scores.is_empty() {
+            let mut table = Table::new();
+            table.add_row(row!["", "Proxy", "Vip", "Rtt(ms)", "Loss", "Load", "Region", "Score"]);
+            for proxy in self.status.proxy_scores {
+                let to_string = |value: Option<String>|value.unwrap_or("-".to_string());
+                table.add_row(row![
+                     if proxy.selected { "*" } else { "" },
+                     proxy.id,
+                     proxy.vip,
+                     to_string(proxy.rtt_ms.map(|rtt|rtt.to_string())),
+                     format!("{}%", proxy.loss),
+                     to_string(proxy.load.map(|load|format!("{}%", load))),
+                     to_string(proxy.region),
+                     to_string(proxy.score.map(|score|score.to_string())),
+                ]);
+            }
+            table.printstd();
+        }
     }
 }
\ No newline at end of file
@@ -9,4 +9,6 @@ pub enum Error {
     DaemonNotRunning(#[error(cause)] io::Error),
     #[error(display = "Failed to connect to daemon")]
     ipc_connect_failed(#[error(cause)] management_client::Error),
+    #[error(display = "Failed to write diagnostics bundle")]
+    write_bundle(#[error(cause)] io::Error),
 }
\ No newline at end of file
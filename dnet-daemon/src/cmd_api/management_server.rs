@@ -17,7 +17,7 @@ use std::{
 };
 use ipc_server;
 use dnet_types::status::TunnelState;
-use dnet_types::daemon_broadcast::DaemonBroadcast;
+use dnet_types::daemon_broadcast::{DaemonBroadcast, NetworkChange};
 use dnet_types::response::Response;
 
 use crate::cmd_api::types::EventListener;
@@ -25,6 +25,7 @@ use crate::mpsc::IntoSender;
 use crate::settings::Settings;
 use dnet_types::tinc_host_status_change::HostStatusChange;
 use dnet_types::user::User;
+use dnet_types::settings::{Profile, SettingsUpdate};
 
 /// FIXME(linus): This is here just because the futures crate has deprecated it and jsonrpc_core
 /// did not introduce their own yet (https://github.com/paritytech/jsonrpc/pull/196).
@@ -43,6 +44,9 @@ build_rpc_trait! {
         #[rpc(meta, name = "shutdown")]
         fn shutdown(&self, Self::Metadata) -> BoxFuture<Response, Error>;
 
+        #[rpc(meta, name = "drain")]
+        fn drain(&self, Self::Metadata) -> BoxFuture<Response, Error>;
+
         #[rpc(meta, name = "status")]
         fn status(&self, Self::Metadata) -> BoxFuture<Response, Error>;
 
@@ -69,6 +73,36 @@ build_rpc_trait! {
 
         #[rpc(meta, name = "host_status_change")]
         fn host_status_change(&self, Self::Metadata, String) -> BoxFuture<(), Error>;
+
+        #[rpc(meta, name = "diagnostics")]
+        fn diagnostics(&self, Self::Metadata) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "doctor")]
+        fn doctor(&self, Self::Metadata) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "traffic")]
+        fn traffic(&self, Self::Metadata) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "settings_update")]
+        fn settings_update(&self, Self::Metadata, String) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "profile_list")]
+        fn profile_list(&self, Self::Metadata) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "profile_add")]
+        fn profile_add(&self, Self::Metadata, String) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "profile_use")]
+        fn profile_use(&self, Self::Metadata, String) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "profile_remove")]
+        fn profile_remove(&self, Self::Metadata, String) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "identity")]
+        fn identity(&self, Self::Metadata) -> BoxFuture<Response, Error>;
+
+        #[rpc(meta, name = "identity_migrate")]
+        fn identity_migrate(&self, Self::Metadata, String) -> BoxFuture<Response, Error>;
     }
 }
 
@@ -102,7 +136,39 @@ pub enum ManagementCommand {
 
     Logout(OneshotSender<Response>),
 
+    /// Collect a support bundle, passwords, tokens and private keys stripped.
+    Diagnostics(OneshotSender<Response>),
+
+    /// Run connectivity self-checks.
+    Doctor(OneshotSender<Response>),
+
+    /// Per node traffic counters from tinc.
+    Traffic(OneshotSender<Response>),
+
+    /// Change settings of the running daemon.
+    SettingsUpdate(OneshotSender<Response>, SettingsUpdate),
+
+    /// List profiles, passwords stripped.
+    ProfileList(OneshotSender<Response>),
+
+    /// Add or replace a profile.
+    ProfileAdd(OneshotSender<Response>, Profile),
+
+    /// Log out and switch to another profile.
+    ProfileUse(OneshotSender<Response>, String),
+
+    ProfileRemove(OneshotSender<Response>, String),
+
+    /// Current device identity and device name.
+    Identity(OneshotSender<Response>),
+
+    /// Switch to a stable device identity, optionally from the given provider, and log in again.
+    IdentityMigrate(OneshotSender<Response>, Option<String>),
+
     Shutdown(OneshotSender<Response>),
+
+    /// Proxy reports draining, waits for clients to migrate, then stops.
+    Drain(OneshotSender<Response>),
 }
 
 pub struct ManagementInterfaceServer {
@@ -169,6 +235,12 @@ impl EventListener for ManagementInterfaceEventBroadcaster {
         log::info!("Broadcasting new settings");
         self.notify(DaemonBroadcast::Settings(settings.into()));
     }
+
+    /// Sends the new network to all subscribers of the management interface.
+    fn notify_network_changed(&self, change: NetworkChange) {
+        log::info!("Broadcasting network changed: {:?}", change);
+        self.notify(DaemonBroadcast::NetworkChanged(change));
+    }
 }
 
 impl ManagementInterfaceEventBroadcaster {
@@ -246,6 +318,15 @@ for ManagementInterface<T>
         Box::new(future)
     }
 
+    fn drain(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
+        log::info!("management interface drain command.");
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::Drain(tx))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
     fn status(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
         log::info!("management interface get status.");
         let (tx, rx) = sync::oneshot::channel();
@@ -331,6 +412,105 @@ for ManagementInterface<T>
             .and_then(|_| rx.map_err(|_| Error::internal_error()));
         Box::new(future)
     }
+
+    fn diagnostics(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
+        log::info!("management interface diagnostics");
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::Diagnostics(tx))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn doctor(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
+        log::info!("management interface doctor");
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::Doctor(tx))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn traffic(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
+        log::info!("management interface traffic");
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::Traffic(tx))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn settings_update(&self, _: Self::Metadata, update: String) -> BoxFuture<Response, Error> {
+        log::info!("management interface settings update");
+        let update = match serde_json::from_str(&update) {
+            Ok(update) => update,
+            Err(_) => return Box::new(future::err(Error::invalid_params("settings"))),
+        };
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::SettingsUpdate(tx, update))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn profile_list(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
+        log::info!("management interface profile list");
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::ProfileList(tx))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn profile_add(&self, _: Self::Metadata, profile: String) -> BoxFuture<Response, Error> {
+        log::info!("management interface profile add");
+        let profile = match serde_json::from_str(&profile) {
+            Ok(profile) => profile,
+            Err(_) => return Box::new(future::err(Error::invalid_params("profile"))),
+        };
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::ProfileAdd(tx, profile))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn profile_use(&self, _: Self::Metadata, name: String) -> BoxFuture<Response, Error> {
+        log::info!("management interface profile use {}", name);
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::ProfileUse(tx, name))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn profile_remove(&self, _: Self::Metadata, name: String) -> BoxFuture<Response, Error> {
+        log::info!("management interface profile remove {}", name);
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::ProfileRemove(tx, name))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn identity(&self, _: Self::Metadata) -> BoxFuture<Response, Error> {
+        log::info!("management interface identity");
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::Identity(tx))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
+
+    fn identity_migrate(&self, _: Self::Metadata, provider: String) -> BoxFuture<Response, Error> {
+        log::info!("management interface identity migrate {}", provider);
+        let provider = Some(provider).filter(|provider|!provider.is_empty());
+        let (tx, rx) = sync::oneshot::channel();
+        let future = self
+            .send_command_to_daemon(ManagementCommand::IdentityMigrate(tx, provider))
+            .and_then(|_| rx.map_err(|_| Error::internal_error()));
+        Box::new(future)
+    }
 }
 
 
@@ -1,4 +1,5 @@
 use dnet_types::status::TunnelState;
+use dnet_types::daemon_broadcast::NetworkChange;
 use crate::settings::Settings;
 
 /// Trait representing something that can broadcast daemon events.
@@ -8,4 +9,7 @@ pub trait EventListener {
 
     /// Notify that the settings changed.
     fn notify_settings(&self, settings: Settings);
+
+    /// Notify that the default route interface, gateway or address changed.
+    fn notify_network_changed(&self, change: NetworkChange);
 }
\ No newline at end of file
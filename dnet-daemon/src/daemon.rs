@@ -5,18 +5,20 @@ use std::time::Duration;
 use futures::sync::oneshot;
 
 use dnet_types::status::{TunnelState, RpcState};
+use dnet_types::daemon_broadcast::NetworkChange;
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
 use dnet_types::settings::RunMode;
 use dnet_types::response::Response;
 
 use crate::traits::TunnelTrait;
-use crate::info::{self, Info, get_mut_info};
+use crate::info::{self, Info, get_mut_info, set_tunnel_state};
 use crate::rpc::{self, RpcMonitor};
 use crate::tinc_manager::{TincMonitor, TincOperator};
 use crate::cmd_api::management_server::{ManagementInterfaceServer, ManagementCommand, ManagementInterfaceEventBroadcaster};
+use crate::cmd_api::types::EventListener;
 use crate::mpsc::IntoSender;
 use crate::settings::get_settings;
-use crate::rpc::rpc_cmd::RpcEvent;
+use crate::rpc::rpc_cmd::{RpcEvent, RpcClientCmd};
 use super::daemon_event_handle;
 #[cfg(windows)]
 use crate::settings::default_settings::TINC_INTERFACE;
@@ -74,6 +76,18 @@ pub enum DaemonEvent {
 
     // Ctrl + c && kill
     ShutDown,
+
+    // proxy开始drain, 结束后停止
+    Drain,
+
+    // drain结束 -> 停止
+    Drained,
+
+    // 默认路由网卡, 网关或地址变化
+    NetworkChanged(NetworkChange),
+
+    // 当前proxy drain, 重新选择proxy
+    SelectProxy,
 }
 
 impl From<ManagementCommand> for DaemonEvent {
@@ -89,6 +103,7 @@ pub struct Daemon {
     tunnel_command_tx:      mpsc::Sender<(TunnelCommand, mpsc::Sender<Response>)>,
     rpc_command_tx:         mpsc::Sender<RpcEvent>,
     daemon_monitor_cmd_tx:  mpsc::Sender<ManagementCommand>,
+    event_broadcaster:      ManagementInterfaceEventBroadcaster,
     shutdown_sign:          bool,
 }
 
@@ -98,14 +113,15 @@ impl Daemon {
             {
                 info!("start dnet firewall config.");
                 let tunnel_port = get_settings().tinc.port;
-                router_plugin::firewall::start_firewall(tunnel_port);
+                router_plugin::firewall::start_firewall(
+                    &get_settings().router.firewall_config(), tunnel_port);
             }
 
         let (daemon_event_tx, daemon_event_rx) = mpsc::channel();
 
         let _ = crate::set_shutdown_signal_handler(daemon_event_tx.clone());
 
-        let _event_broadcaster = Self::start_management_interface(daemon_event_tx.clone())?;
+        let event_broadcaster = Self::start_management_interface(daemon_event_tx.clone())?;
 
         TincOperator::new().init()
             .map_err(Error::TunnelInit)?;
@@ -113,6 +129,11 @@ impl Daemon {
         info!("Init local info.");
         Info::new().map_err(Error::InfoError)?;
 
+        crate::hooks::init();
+
+        // 在conductor连接前恢复上次保存的状态, 未过期的token可直接使用
+        let cached_tunnel = info::state::restore_state();
+
         let rpc_command_tx;
         #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
             {
@@ -137,6 +158,12 @@ impl Daemon {
         tinc.start_monitor()
             .ok_or(Error::InitTunnelMonitor)?;
 
+        // conductor连接前, 以上次保存的状态拉起隧道
+        if cached_tunnel {
+            info!("start tunnel with cached state.");
+            let _ = daemon_event_tx.send(DaemonEvent::DaemonInnerCmd(TunnelCommand::Connect));
+        }
+
         let daemon_monitor_cmd_tx =
             daemon_event_handle::daemon_event_monitor::DaemonEventMonitor::start(
                 rpc_command_tx.clone(),
@@ -145,12 +172,16 @@ impl Daemon {
             )
                 .ok_or(Error::InitDaemonEventMonitor)?;
 
+        crate::network_watcher::start(daemon_event_tx.clone());
+        crate::select_proxy_notify::start(daemon_event_tx.clone());
+
         Ok(Daemon {
             daemon_event_tx,
             daemon_event_rx,
             tunnel_command_tx,
             rpc_command_tx,
             daemon_monitor_cmd_tx,
+            event_broadcaster,
             shutdown_sign:      false,
         })
     }
@@ -177,7 +208,7 @@ impl Daemon {
                 }
             },
             DaemonEvent::TunnelInitFailed(err_str) => {
-                get_mut_info().lock().unwrap().status.tunnel  = TunnelState::TunnelInitFailed(err_str);
+                set_tunnel_state(TunnelState::TunnelInitFailed(err_str));
             },
             DaemonEvent::DaemonInnerCmd(cmd) => {
                 let (res_tx, res_rx) = mpsc::channel::<Response>();
@@ -197,8 +228,35 @@ impl Daemon {
             }
             // Ctrl + c && kill
             DaemonEvent::ShutDown => {
+                // proxy先drain, drain期间再次收到停止信号时立即停止
+                #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+                    {
+                        if daemon_event_handle::drain::need_drain() {
+                            if daemon_event_handle::drain::start_drain(self.daemon_event_tx.clone()) {
+                                return;
+                            }
+                            warn!("shutdown while draining, stop now.");
+                        }
+                    }
+                self.handle_shutdown();
+            },
+            DaemonEvent::Drain => {
+                #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+                    {
+                        let _ = daemon_event_handle::drain::start_drain(self.daemon_event_tx.clone());
+                    }
+            },
+            DaemonEvent::Drained => {
                 self.handle_shutdown();
             },
+            DaemonEvent::NetworkChanged(change) => {
+                daemon_event_handle::network::handle_network_changed(&change, &self.rpc_command_tx);
+                self.event_broadcaster.notify_network_changed(change);
+            },
+            DaemonEvent::SelectProxy => {
+                let (res_tx, _) = mpsc::channel();
+                let _ = self.rpc_command_tx.send(RpcEvent::Client(RpcClientCmd::ReportDeviceSelectProxy(res_tx)));
+            },
         };
     }
 
@@ -216,17 +274,16 @@ impl Daemon {
             {
                 info!("stop dnet firewall config.");
                 let tunnel_port = get_settings().tinc.port;
-                router_plugin::firewall::stop_firewall(tunnel_port);
-                let info = get_mut_info().lock().unwrap();
-                if let Some(local_vip) = info.tinc_info.vip.clone() {
-                    std::mem::drop(info);
-                    router_plugin::firewall::start_tunnel_firewall(&local_vip);
-                }
+                let firewall_config = get_settings().router.firewall_config();
+                router_plugin::firewall::stop_firewall(&firewall_config, tunnel_port);
             }
         #[cfg(windows)]
             {
                 sandbox::route::keep_route(None, vec![], TINC_INTERFACE.to_string());
             }
+        if get_settings().client.team_isolation_firewall {
+            crate::tinc_manager::team_firewall::clear();
+        }
         self.shutdown_sign = true;
     }
 
@@ -236,6 +293,7 @@ impl Daemon {
 
     fn handle_rpc_connected(&mut self) {
         get_mut_info().lock().unwrap().status.rpc = RpcState::Connected;
+        info::state::clear_cached();
         #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
             {
                 let _response = daemon_event_handle::tunnel::send_tunnel_connect(
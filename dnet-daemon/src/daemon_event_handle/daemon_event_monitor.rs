@@ -12,13 +12,16 @@ use crate::cmd_api::management_server::ManagementCommand;
 use crate::daemon_event_handle;
 use crate::rpc::rpc_cmd::{RpcEvent, RpcProxyCmd};
 use crate::daemon::{DaemonEvent, TunnelCommand};
-use crate::info::{get_info, get_mut_info};
+use crate::info::{get_info, get_mut_info, set_tunnel_state};
 use crate::settings::get_settings;
+use crate::tinc_manager::TincInterface;
+use crate::hooks::{self, HookEvent};
 
 pub struct DaemonEventMonitor {
     rpc_command_tx:         mpsc::Sender<RpcEvent>,
     daemon_event_tx:        mpsc::Sender<DaemonEvent>,
     tunnel_command_tx:      mpsc::Sender<(TunnelCommand, mpsc::Sender<Response>)>,
+    tinc_interface:         Option<TincInterface>,
 }
 impl DaemonEventMonitor {
     pub fn start(
@@ -31,6 +34,7 @@ impl DaemonEventMonitor {
             rpc_command_tx,
             daemon_event_tx,
             tunnel_command_tx,
+            tinc_interface: None,
         };
         thread::Builder::new()
             .name("DaemonEventMonitor".to_string())
@@ -125,6 +129,7 @@ impl DaemonEventMonitor {
                             self.handle_tunnel_disconnected()
                         },
                         dnet_types::tinc_host_status_change::HostStatusChange::HostUp(host) => {
+                            hooks::emit(HookEvent::HostUp { host: host.clone() });
                             if let Some(vip) = TincTools::get_vip_by_filename(host) {
                                 get_mut_info().lock().unwrap().tinc_info.current_connect.push(vip);
                                 if !host.contains("proxy") {
@@ -133,6 +138,7 @@ impl DaemonEventMonitor {
                             }
                         }
                         dnet_types::tinc_host_status_change::HostStatusChange::HostDown(host) => {
+                            hooks::emit(HookEvent::HostDown { host: host.clone() });
                             if let Some(vip) = TincTools::get_vip_by_filename(host) {
                                 get_mut_info().lock().unwrap().tinc_info.remove_current_connect(&vip);
                                 if !host.contains("proxy") {
@@ -156,6 +162,54 @@ impl DaemonEventMonitor {
                     }
                 }
 
+                ManagementCommand::Diagnostics(ipc_tx) => {
+                    thread::spawn(||
+                        daemon_event_handle::diagnostics::handle_diagnostics(ipc_tx));
+                }
+
+                ManagementCommand::Doctor(ipc_tx) => {
+                    thread::spawn(||
+                        daemon_event_handle::doctor::handle_doctor(ipc_tx));
+                }
+
+                ManagementCommand::Traffic(ipc_tx) => {
+                    daemon_event_handle::traffic::handle_traffic(ipc_tx);
+                }
+
+                ManagementCommand::SettingsUpdate(ipc_tx, update) => {
+                    daemon_event_handle::handle_settings::handle_settings_update(ipc_tx, update);
+                }
+
+                ManagementCommand::ProfileList(ipc_tx) => {
+                    daemon_event_handle::profile::handle_profile_list(ipc_tx);
+                }
+
+                ManagementCommand::ProfileAdd(ipc_tx, profile) => {
+                    daemon_event_handle::profile::handle_profile_add(ipc_tx, profile);
+                }
+
+                ManagementCommand::ProfileUse(ipc_tx, name) => {
+                    let rpc_command_tx = self.rpc_command_tx.clone();
+                    let tunnel_command_tx = self.tunnel_command_tx.clone();
+                    daemon_event_handle::profile::handle_profile_use(
+                        ipc_tx, name, rpc_command_tx, tunnel_command_tx);
+                }
+
+                ManagementCommand::ProfileRemove(ipc_tx, name) => {
+                    daemon_event_handle::profile::handle_profile_remove(ipc_tx, name);
+                }
+
+                ManagementCommand::Identity(ipc_tx) => {
+                    daemon_event_handle::identity::handle_identity(ipc_tx);
+                }
+
+                ManagementCommand::IdentityMigrate(ipc_tx, provider) => {
+                    let rpc_command_tx = self.rpc_command_tx.clone();
+                    let tunnel_command_tx = self.tunnel_command_tx.clone();
+                    daemon_event_handle::identity::handle_identity_migrate(
+                        ipc_tx, provider, rpc_command_tx, tunnel_command_tx);
+                }
+
                 ManagementCommand::Shutdown(ipc_tx) => {
                     let _ = self.daemon_event_tx.send(DaemonEvent::ShutDown);
 
@@ -165,6 +219,19 @@ impl DaemonEventMonitor {
 
                     let _ = Self::oneshot_send(ipc_tx, command_response, "");
                 }
+
+                ManagementCommand::Drain(ipc_tx) => {
+                    let mode = get_settings().common.mode.clone();
+                    let command_response = if mode == RunMode::Proxy || mode == RunMode::Center {
+                        info!("Drain by cli command.");
+                        let _ = self.daemon_event_tx.send(DaemonEvent::Drain);
+                        Response::success()
+                    }
+                    else {
+                        Response::internal_error().set_msg("Only proxy can drain.".to_owned())
+                    };
+                    let _ = Self::oneshot_send(ipc_tx, command_response, "");
+                }
             }
         }
     }
@@ -211,10 +278,16 @@ impl DaemonEventMonitor {
 
 
     fn handle_tunnel_connected(&mut self) {
-//        if let Err(e) = TincOperator::new().set_routing() {
-//            error!("host_status_change tinc-up {:?}", e);
-//        }
-        get_mut_info().lock().unwrap().status.tunnel = TunnelState::Connected;
+        if let Some(tinc_interface) = TincInterface::from_info() {
+            if let Err(e) = tinc_interface.configure() {
+                error!("host_status_change tinc-up {:?}", e);
+            }
+            self.tinc_interface = Some(tinc_interface);
+        }
+        else {
+            warn!("host_status_change tinc-up local vip not found.");
+        }
+        set_tunnel_state(TunnelState::Connected);
 
         let _ = self.rpc_command_tx.send(RpcEvent::TunnelConnected);
         let (res_tx, _res_rx) = mpsc::channel::<Response>();
@@ -222,7 +295,10 @@ impl DaemonEventMonitor {
     }
 
     fn handle_tunnel_disconnected(&mut self) {
-        get_mut_info().lock().unwrap().status.tunnel = TunnelState::Disconnected;
+        if let Some(tinc_interface) = self.tinc_interface.take() {
+            tinc_interface.undo();
+        }
+        set_tunnel_state(TunnelState::Disconnected);
     }
 
 
@@ -0,0 +1,258 @@
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, UdpSocket};
+use std::path::Path;
+use std::time::Duration;
+
+use futures::sync::oneshot;
+
+use dnet_types::doctor::DoctorCheck;
+use dnet_types::response::Response;
+use dnet_types::status::RpcState;
+use tinc_plugin::{PID_FILENAME, TINC_BIN_FILENAME};
+use tinc_plugin::tinc_tcp_stream::TincStream;
+
+use crate::daemon::Daemon;
+use crate::info::get_info;
+use crate::settings::get_settings;
+
+/// 依次执行连通性检查, 返回 Vec<DoctorCheck>
+pub fn handle_doctor(ipc_tx: oneshot::Sender<Response>) {
+    let checks = vec![
+        check_conductor(),
+        check_login(),
+        check_tincd_bin(),
+        check_tun_device(),
+        check_tinc_port(),
+        check_control_socket(),
+        check_default_route(),
+        check_firewall(),
+    ]
+        .into_iter()
+        .chain(check_connect_to())
+        .collect::<Vec<DoctorCheck>>();
+
+    let res = match serde_json::to_value(checks) {
+        Ok(data) => Response::success().set_data(Some(data)),
+        Err(e) => Response::internal_error().set_msg(e.to_string()),
+    };
+    let _ = Daemon::oneshot_send(ipc_tx, res, "");
+}
+
+fn tinc_home() -> String {
+    get_settings().common.home_path.join("tinc").to_str().unwrap_or("").to_string() + "/"
+}
+
+fn check_conductor() -> DoctorCheck {
+    let name = "conductor";
+    let settings = &get_settings().common;
+    if let Err(e) = url::Url::parse(&settings.conductor_url) {
+        return DoctorCheck::fail(name, &format!("conductor_url {:?} {}", settings.conductor_url, e),
+                                 "Set [common] conductor_url in settings.toml, e.g. https://host.");
+    }
+    let client = crate::rpc::with_egress_proxy(reqwest::ClientBuilder::new())
+        .timeout(Duration::from_secs(settings.http_timeout as u64))
+        .danger_accept_invalid_certs(settings.accept_conductor_invalid_certs)
+        .build();
+    let res = client
+        .map_err(|e| e.to_string())
+        .and_then(|client| client.get(&settings.conductor_url).send()
+            .map_err(|e| e.to_string()));
+    match res {
+        Ok(res) => DoctorCheck::pass(name, &format!("{} {}", settings.conductor_url, res.status())),
+        Err(e) => DoctorCheck::fail(name, &e,
+                                    "Check DNS, outbound https and the conductor certificate; \
+                                    set accept_conductor_invalid_certs for self-signed certificates."),
+    }
+}
+
+fn check_login() -> DoctorCheck {
+    let name = "login";
+    if get_settings().common.username.is_empty() {
+        return DoctorCheck::warn(name, "not logged in", "Run `dnet login <user> <password>`.");
+    }
+    let info = get_info().lock().unwrap();
+    let logged_in = !info.node.token.is_empty() && info.status.rpc == RpcState::Connected;
+    let rpc = info.status.rpc.clone();
+    std::mem::drop(info);
+    if logged_in {
+        DoctorCheck::pass(name, &get_settings().common.username)
+    }
+    else {
+        DoctorCheck::fail(name, &format!("conductor {:?}", rpc),
+                          "Check username/password, then run `dnet login` again.")
+    }
+}
+
+fn check_tincd_bin() -> DoctorCheck {
+    let name = "tincd";
+    #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+        let path = tinc_home() + TINC_BIN_FILENAME;
+    #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+        let path = match which(TINC_BIN_FILENAME) {
+            Some(path) => path,
+            None => return DoctorCheck::fail(name, "tincd not found in PATH", "Install tinc."),
+        };
+
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(e) => return DoctorCheck::fail(name, &format!("{} {}", path, e),
+                                           "Reinstall dnet, tincd is shipped in the tinc directory."),
+    };
+    #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.permissions().mode() & 0o111 == 0 {
+                return DoctorCheck::fail(name, &format!("{} not executable", path),
+                                         &format!("chmod 755 {}", path));
+            }
+        }
+    let _ = metadata;
+    DoctorCheck::pass(name, &path)
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+fn which(bin: &str) -> Option<String> {
+    std::env::var("PATH").ok()?
+        .split(':')
+        .map(|dir| dir.to_string() + "/" + bin)
+        .find(|path| Path::new(path).is_file())
+}
+
+fn check_tun_device() -> DoctorCheck {
+    let name = "tun";
+    #[cfg(target_os = "linux")]
+        {
+            let path = "/dev/net/tun";
+            return match std::fs::OpenOptions::new().read(true).write(true).open(path) {
+                Ok(_) => DoctorCheck::pass(name, path),
+                Err(e) => DoctorCheck::fail(name, &format!("{} {}", path, e),
+                                            "Load the tun module (modprobe tun) and run dnet-daemon as root."),
+            };
+        }
+    #[cfg(not(target_os = "linux"))]
+        DoctorCheck::pass(name, "not checked on this platform")
+}
+
+fn check_tinc_port() -> DoctorCheck {
+    let name = "tinc port";
+    let port = get_settings().tinc.port;
+    let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, port));
+    let res = TcpListener::bind(addr)
+        .and_then(|_| UdpSocket::bind(addr))
+        .map(|_| ());
+    match res {
+        Ok(_) => DoctorCheck::pass(name, &format!("{} bindable", port)),
+        Err(e) => {
+            // tincd运行时端口被自身占用
+            if Path::new(&(tinc_home() + PID_FILENAME)).is_file() {
+                DoctorCheck::pass(name, &format!("{} in use by tincd", port))
+            }
+            else {
+                DoctorCheck::fail(name, &format!("{} {}", port, e),
+                                  "Another process holds the port, stop it or change [tinc] port.")
+            }
+        }
+    }
+}
+
+fn check_control_socket() -> DoctorCheck {
+    let name = "tinc control";
+    let pid_path = tinc_home() + PID_FILENAME;
+    if !Path::new(&pid_path).is_file() {
+        return DoctorCheck::warn(name, "tincd not running", "Run `dnet connect`.");
+    }
+    match TincStream::new(&pid_path).and_then(|mut stream| stream.connect_test()) {
+        Ok(_) => DoctorCheck::pass(name, &pid_path),
+        Err(e) => DoctorCheck::fail(name, &e.to_string(),
+                                    "Stale pid file or tincd hung, run `dnet disconnect` then `dnet connect`."),
+    }
+}
+
+fn check_connect_to() -> Vec<DoctorCheck> {
+    let connect_to = get_info().lock().unwrap().tinc_info.connect_to.clone();
+    if connect_to.is_empty() {
+        return vec![DoctorCheck::warn("proxy", "no proxy selected",
+                                      "Join a team, or check that the conductor has online proxies.")];
+    }
+    connect_to.into_iter()
+        .map(|proxy| {
+            let name = format!("proxy {}", proxy.ip);
+            match pinger::ping(proxy.ip, Some(Duration::from_secs(1)), None, None, Some(1), None) {
+                Ok(rtt) => DoctorCheck::pass(&name, &format!("rtt {}us", rtt)),
+                Err(_) => DoctorCheck::fail(&name, "no icmp reply",
+                                          "Check the WAN link; icmp may be blocked by an upstream firewall."),
+            }
+        })
+        .collect()
+}
+
+fn check_default_route() -> DoctorCheck {
+    let name = "default route";
+    match sandbox::route::get_default_route() {
+        Ok(route) => DoctorCheck::pass(name, &format!("{} dev {}", route.gw, route.dev)),
+        Err(e) => DoctorCheck::fail(name, &e.to_string(), "Check the network connection."),
+    }
+}
+
+/// 使用与dnet规则相同的防火墙后端(iptables或nftables)检查入站规则
+fn check_firewall() -> DoctorCheck {
+    let name = "firewall";
+    #[cfg(target_os = "linux")]
+        {
+            let port = get_settings().tinc.port.to_string();
+            let firewall = match sandbox::firewall::firewall() {
+                Ok(firewall) => firewall,
+                Err(_) => return DoctorCheck::warn(
+                    name, "iptables/nft not available", "Check the tinc port manually."),
+            };
+            let rules = match firewall.input_rules() {
+                Ok(rules) => rules,
+                Err(e) => return DoctorCheck::warn(
+                    name, &format!("{} {}", firewall.name(), e), "Check the tinc port manually."),
+            };
+            return if input_may_accept(&rules, &port) {
+                DoctorCheck::pass(name, &format!("{} input accepts {}", firewall.name(), port))
+            }
+            else if firewall.name() == "nftables" {
+                DoctorCheck::warn(name, &format!("nftables input may drop {}", port),
+                                  &format!("nft insert rule <family> <table> <input chain> udp dport {0} accept; \
+                                  nft insert rule <family> <table> <input chain> tcp dport {0} accept", port))
+            }
+            else {
+                DoctorCheck::warn(name, &format!("iptables INPUT may drop {}", port),
+                                  &format!("iptables -I INPUT -p udp --dport {0} -j ACCEPT; \
+                                  iptables -I INPUT -p tcp --dport {0} -j ACCEPT", port))
+            };
+        }
+    #[cfg(not(target_os = "linux"))]
+        DoctorCheck::pass(name, "not checked on this platform")
+}
+
+/// iptables -S 或nft的规则中有放行port的规则, 或者没有drop/reject
+#[cfg(target_os = "linux")]
+fn input_may_accept(rules: &[String], port: &str) -> bool {
+    let tokens = |rule: &str| rule.split_ascii_whitespace()
+        .map(|token| token.trim_end_matches(';').to_string())
+        .collect::<Vec<String>>();
+    let accept_port = rules.iter().any(|rule| {
+        let tokens = tokens(rule);
+        tokens.windows(2).any(|pair| (pair[0] == "--dport" || pair[0] == "dport") && pair[1] == port)
+            && tokens.iter().any(|token| token == "ACCEPT" || token == "accept")
+    });
+    let blocking = rules.iter().any(|rule| tokens(rule).iter()
+        .any(|token| token == "DROP" || token == "REJECT" || token == "drop" || token == "reject"));
+    accept_port || !blocking
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_input_may_accept() {
+    let rules = |rules: &[&str]| rules.iter().map(|rule| rule.to_string()).collect::<Vec<String>>();
+    assert!(input_may_accept(&rules(&["-P INPUT ACCEPT"]), "50069"));
+    assert!(!input_may_accept(&rules(&["-P INPUT DROP"]), "50069"));
+    assert!(input_may_accept(&rules(&["-P INPUT DROP", "-A INPUT -p udp -m udp --dport 50069 -j ACCEPT"]), "50069"));
+    assert!(!input_may_accept(&rules(&["-P INPUT DROP", "-A INPUT -p udp -m udp --dport 500690 -j ACCEPT"]), "50069"));
+    assert!(!input_may_accept(&rules(&["type filter hook input priority filter; policy drop;"]), "50069"));
+    assert!(input_may_accept(&rules(&["type filter hook input priority filter; policy drop;",
+                                      "udp dport 50069 accept"]), "50069"));
+    assert!(input_may_accept(&rules(&["type filter hook input priority filter; policy accept;"]), "50069"));
+}
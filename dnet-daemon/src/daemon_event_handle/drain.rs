@@ -0,0 +1,151 @@
+//! proxy停止前的drain: 心跳上报draining, 客户端在下次获取在线proxy时迁移到其他proxy.
+//! drain期间定期通知直连的客户端重新选择proxy(见select_proxy_notify).
+//! 直连的客户端数(不包括其他proxy)降到drain_threshold或超过drain_timeout后发送DaemonEvent::Drained停止daemon.
+
+use std::net::IpAddr;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dnet_types::settings::RunMode;
+use dnet_types::status::DrainState;
+use tinc_plugin::{TincTools, PID_FILENAME};
+
+use crate::daemon::DaemonEvent;
+use crate::info::get_mut_info;
+use crate::rpc::proxy::RpcClient;
+use crate::select_proxy_notify;
+use crate::settings::get_settings;
+use crate::settings::default_settings::DRAIN_CHECK_FREQUENCY_SEC;
+use crate::tinc_manager::connection_nodes;
+
+pub fn can_drain() -> bool {
+    let mode = &get_settings().common.mode;
+    mode == &RunMode::Proxy || mode == &RunMode::Center
+}
+
+/// 停止daemon前是否先drain
+pub fn need_drain() -> bool {
+    can_drain() && get_settings().proxy.drain_on_shutdown
+}
+
+/// 开始drain, 已经在drain时返回false
+pub fn start_drain(daemon_event_tx: mpsc::Sender<DaemonEvent>) -> bool {
+    let timeout = Duration::from_secs(get_settings().proxy.drain_timeout as u64);
+    {
+        let mut info = get_mut_info().lock().unwrap();
+        if info.status.draining.is_some() {
+            return false;
+        }
+        let now = chrono::Utc::now();
+        let deadline = now + chrono::Duration::from_std(timeout)
+            .unwrap_or(chrono::Duration::zero());
+        info.status.draining = Some(DrainState {
+            started_at:     now.to_string(),
+            deadline:       deadline.to_string(),
+            connections:    0,
+        });
+    }
+
+    info!("start drain, timeout {:?}", timeout);
+    let _ = thread::Builder::new()
+        .name("drain".to_string())
+        .spawn(move || {
+            drain(timeout);
+            let _ = daemon_event_tx.send(DaemonEvent::Drained);
+        });
+    true
+}
+
+fn drain(timeout: Duration) {
+    let start = Instant::now();
+    // 立即上报draining, 不等下一次心跳
+    if let Err(e) = RpcClient::new().proxy_heartbeat() {
+        error!("drain heartbeat {:?}", e);
+    }
+
+    let threshold = get_settings().proxy.drain_threshold;
+    loop {
+        let clients = client_nodes();
+        let connections = clients.as_ref().map(|clients|clients.len() as u32);
+        if let Some(connections) = connections {
+            if let Some(draining) = get_mut_info().lock().unwrap().status.draining.as_mut() {
+                draining.connections = connections;
+            }
+        }
+        match check(connections, threshold, start.elapsed(), timeout) {
+            DrainCheck::Finished => {
+                info!("drain finished, {:?} connections left", connections);
+                return;
+            }
+            DrainCheck::Timeout => {
+                warn!("drain timeout, {:?} connections left", connections);
+                return;
+            }
+            DrainCheck::Waiting => (),
+        }
+        // 未收到通知或通知丢失的客户端在下一次获取在线proxy时迁移
+        if let Some(clients) = clients {
+            let vips: Vec<IpAddr> = clients.iter()
+                .filter_map(|node|TincTools::get_vip_by_filename(node))
+                .collect();
+            select_proxy_notify::notify(&vips);
+        }
+        thread::sleep(Duration::from_secs(DRAIN_CHECK_FREQUENCY_SEC));
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum DrainCheck {
+    Finished,
+    Timeout,
+    Waiting,
+}
+
+/// 获取连接数失败(None)时只检查超时
+fn check(connections: Option<u32>, threshold: u32, elapsed: Duration, timeout: Duration) -> DrainCheck {
+    match connections {
+        Some(connections) if connections <= threshold => DrainCheck::Finished,
+        _ if elapsed >= timeout => DrainCheck::Timeout,
+        _ => DrainCheck::Waiting,
+    }
+}
+
+/// 直连的客户端节点, 不包括其他proxy和控制连接
+fn client_nodes() -> Option<Vec<String>> {
+    let pid_path = get_settings().common.home_path
+        .join("tinc").join(PID_FILENAME)
+        .to_string_lossy().to_string();
+    let nodes = connection_nodes(&pid_path)
+        .map_err(|e|warn!("drain dump connections {:?}", e))
+        .ok()?;
+    Some(nodes.into_iter().filter(|node|is_client_node(node)).collect())
+}
+
+fn is_client_node(node: &str) -> bool {
+    node != "<control>" && !node.contains("proxy")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let timeout = Duration::from_secs(60);
+        assert_eq!(check(Some(0), 0, Duration::from_secs(1), timeout), DrainCheck::Finished);
+        assert_eq!(check(Some(3), 5, Duration::from_secs(1), timeout), DrainCheck::Finished);
+        assert_eq!(check(Some(6), 5, Duration::from_secs(1), timeout), DrainCheck::Waiting);
+        assert_eq!(check(Some(6), 5, timeout, timeout), DrainCheck::Timeout);
+        assert_eq!(check(Some(0), 0, timeout, timeout), DrainCheck::Finished);
+        assert_eq!(check(None, 5, Duration::from_secs(1), timeout), DrainCheck::Waiting);
+        assert_eq!(check(None, 5, timeout, timeout), DrainCheck::Timeout);
+    }
+
+    #[test]
+    fn test_is_client_node() {
+        let nodes = vec!["<control>", "proxy_10_253_0_1", "0_1_2", "0_1_3"];
+        let clients: Vec<&str> = nodes.into_iter().filter(|node|is_client_node(node)).collect();
+        assert_eq!(clients, vec!["0_1_2", "0_1_3"]);
+    }
+}
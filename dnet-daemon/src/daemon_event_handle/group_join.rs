@@ -11,6 +11,7 @@ use super::handle_settings;
 use super::common::is_not_proxy;
 use crate::daemon_event_handle::common::{is_rpc_connected, send_rpc_group_fresh, daemon_event_handle_fresh_running_from_all};
 use crate::settings::get_settings;
+use crate::hooks::{self, HookEvent};
 
 pub fn group_join(
     ipc_tx:                 oneshot::Sender<Response>,
@@ -46,6 +47,7 @@ pub fn group_join(
             let response = Response::success();
             let _ = Daemon::oneshot_send(ipc_tx, response, "");
             info!("success");
+            hooks::emit(HookEvent::TeamJoin { team_id: team_id.clone() });
             Some(())
         });
 }
@@ -10,6 +10,7 @@ use crate::info::get_info;
 use super::common::is_not_proxy;
 use crate::daemon_event_handle::common::{is_rpc_connected, send_rpc_group_fresh, daemon_event_handle_fresh_running_from_all};
 use crate::settings::get_settings;
+use crate::hooks::{self, HookEvent};
 
 pub fn group_leave(
     ipc_tx:                 oneshot::Sender<Response>,
@@ -52,6 +53,7 @@ pub fn group_leave(
             info!("success");
             let response = Response::success();
             let _ = Daemon::oneshot_send(ipc_tx, response, "");
+            hooks::emit(HookEvent::TeamLeave { team_id: team_id.clone() });
             Some(())
         });
 }
@@ -2,7 +2,8 @@ use url;
 use futures::sync::oneshot;
 
 use dnet_types::response::Response;
-use crate::settings::get_settings;
+use dnet_types::settings::SettingsUpdate;
+use crate::settings::{get_settings, get_mut_settings};
 use crate::daemon::Daemon;
 
 pub fn check_conductor_url(
@@ -18,4 +19,48 @@ pub fn check_conductor_url(
         let _ = Daemon::oneshot_send(ipc_tx, response, "");
         None
     }
-}
\ No newline at end of file
+}
+/// 修改运行中daemon的配置, 不写回settings.toml
+pub fn handle_settings_update(
+    ipc_tx: oneshot::Sender<Response>,
+    update: SettingsUpdate,
+) {
+    if let Some(conductor_url) = &update.conductor_url {
+        if url::Url::parse(conductor_url).is_err() {
+            let response = Response::internal_error().set_msg("Conductor url failed.".to_owned());
+            let _ = Daemon::oneshot_send(ipc_tx, response, "");
+            return;
+        }
+    }
+    let log_level = match update.log_level.as_ref().map(|level|level.to_ascii_lowercase()) {
+        Some(level) => match level.parse::<log::LevelFilter>() {
+            Ok(filter) => Some((level, filter)),
+            Err(_) => {
+                let response = Response::internal_error().set_msg("Invalid log level.".to_owned());
+                let _ = Daemon::oneshot_send(ipc_tx, response, "");
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let settings = get_mut_settings();
+    if let Some(conductor_url) = update.conductor_url {
+        settings.common.conductor_url = conductor_url;
+    }
+    if let Some((level, filter)) = log_level {
+        // 日志输出在启动时按配置级别初始化, 只能在此级别以内调整
+        log::set_max_level(filter);
+        settings.common.log_level = level;
+    }
+    if let Some(auto_connect) = update.auto_connect {
+        settings.client.auto_connect = auto_connect;
+    }
+
+    let data = serde_json::to_value(SettingsUpdate {
+        conductor_url:  Some(settings.common.conductor_url.clone()),
+        log_level:      Some(settings.common.log_level.clone()),
+        auto_connect:   Some(settings.client.auto_connect),
+    }).ok();
+    let _ = Daemon::oneshot_send(ipc_tx, Response::success().set_data(data), "");
+}
@@ -0,0 +1,94 @@
+use std::sync::mpsc;
+
+use futures::sync::oneshot;
+
+use dnet_types::identity::{IdentityProvider, IdentityStatus};
+use dnet_types::response::Response;
+use dnet_types::settings::RunMode;
+use dnet_types::user::User;
+
+use crate::rpc::rpc_cmd::RpcEvent;
+use crate::settings::get_settings;
+use crate::daemon::{Daemon, TunnelCommand};
+use crate::info::get_mut_info;
+use crate::info::identity;
+use super::{login, logout};
+
+pub fn handle_identity(ipc_tx: oneshot::Sender<Response>) {
+    let response = match identity_status() {
+        Some(status) => Response::success().set_data(serde_json::to_value(status).ok()),
+        None => Response::internal_error().set_msg("Device identity not resolved.".to_owned()),
+    };
+    let _ = Daemon::oneshot_send(ipc_tx, response, "");
+}
+
+/// 切换到稳定的设备身份. conductor中视为新设备, 已登录时登出后以新设备名重新登录
+pub fn handle_identity_migrate(
+    ipc_tx:             oneshot::Sender<Response>,
+    provider:           Option<String>,
+    rpc_command_tx:     mpsc::Sender<RpcEvent>,
+    tunnel_command_tx:  mpsc::Sender<(TunnelCommand, mpsc::Sender<Response>)>,
+) {
+    if get_settings().common.mode != RunMode::Client {
+        let response = Response::internal_error().set_msg("Only client can migrate identity.".to_owned());
+        let _ = Daemon::oneshot_send(ipc_tx, response, "");
+        return;
+    }
+    let provider = match provider.map(|provider|provider.parse::<IdentityProvider>()).transpose() {
+        Ok(provider) => provider,
+        Err(provider) => {
+            let response = Response::internal_error()
+                .set_msg(format!("Unknown identity provider {}.", provider));
+            let _ = Daemon::oneshot_send(ipc_tx, response, "");
+            return;
+        }
+    };
+
+    let settings = get_settings();
+    let identity = match identity::migrate(
+        &settings.common.home_path, &settings.common.identity_providers, provider) {
+        Ok(identity) => identity,
+        Err(e) => {
+            let response = Response::internal_error().set_msg(format!("{}", e));
+            let _ = Daemon::oneshot_send(ipc_tx, response, "");
+            return;
+        }
+    };
+
+    let mut info = get_mut_info().lock().unwrap();
+    let device_name = identity::device_name(&info.client_info.devicetype, &identity);
+    if device_name == info.client_info.device_name {
+        std::mem::drop(info);
+        handle_identity(ipc_tx);
+        return;
+    }
+    info!("migrate device name {} -> {}", info.client_info.device_name, device_name);
+    std::mem::drop(info);
+
+    let logged_in = !settings.common.username.is_empty();
+    if logged_in {
+        let response = logout::logout_for_switch(rpc_command_tx.clone(), tunnel_command_tx);
+        if response.code != 200 {
+            let _ = Daemon::oneshot_send(ipc_tx, response, "");
+            return;
+        }
+    }
+    get_mut_info().lock().unwrap().client_info.set_device_name(device_name);
+
+    if logged_in {
+        let user = User::new(&settings.common.username, &settings.common.password);
+        login::handle_login(ipc_tx, user, rpc_command_tx);
+    }
+    else {
+        handle_identity(ipc_tx);
+    }
+}
+
+fn identity_status() -> Option<IdentityStatus> {
+    let identity = identity::load(&get_settings().common.home_path)?;
+    let device_name = get_mut_info().lock().unwrap().client_info.device_name.clone();
+    Some(IdentityStatus {
+        identity,
+        device_name,
+    })
+}
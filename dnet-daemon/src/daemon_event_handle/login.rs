@@ -8,7 +8,8 @@ use dnet_types::response::Response;
 use crate::rpc::rpc_cmd::{RpcEvent, RpcClientCmd};
 use crate::settings::{get_mut_settings, get_settings};
 use crate::daemon::Daemon;
-use crate::info::get_info;
+use crate::info::{get_info, get_mut_info};
+use crate::hooks::{self, HookEvent};
 use super::handle_settings;
 
 pub fn handle_login(ipc_tx: oneshot::Sender<Response>,
@@ -18,6 +19,10 @@ pub fn handle_login(ipc_tx: oneshot::Sender<Response>,
     if let Some(ipc_tx) = handle_settings::check_conductor_url(ipc_tx) {
         {
             let settings = get_mut_settings();
+            // 账号变化后不能复用旧token; 切换profile时保留restore_state恢复的token
+            if settings.common.username != user.name || settings.common.password != user.password {
+                get_mut_info().lock().unwrap().node.token = String::new();
+            }
             settings.common.username = user.name;
             settings.common.password = user.password;
         }
@@ -58,6 +63,11 @@ pub fn handle_login(ipc_tx: oneshot::Sender<Response>,
                     get_settings().common.http_timeout as u64
                 )
             );
+            // 只保存加密后的密码, daemon重启后使用
+            if let Err(e) = get_mut_settings().save_login() {
+                error!("save login {:?}", e);
+            }
+            hooks::emit(HookEvent::Login { username: get_settings().common.username.clone() });
         }
 
         let _ = Daemon::oneshot_send(ipc_tx, response, "");
@@ -10,9 +10,10 @@ use sandbox::route::keep_route;
 use crate::rpc::rpc_cmd::{RpcEvent, RpcClientCmd};
 use crate::settings::{get_mut_settings, get_settings};
 use crate::daemon::{Daemon, TunnelCommand};
-use crate::info::{get_mut_info, UserInfo, TincInfo};
+use crate::info::{get_mut_info, state, UserInfo, TincInfo};
 use crate::daemon_event_handle::tunnel::send_tunnel_disconnect;
 use crate::settings::default_settings::TINC_INTERFACE;
+use crate::hooks::{self, HookEvent};
 
 pub fn handle_logout(
     ipc_tx:             oneshot::Sender<Response>,
@@ -29,10 +30,37 @@ pub fn handle_logout(
             clean_all_running_teams();
             clean_route_table();
             let _ = Daemon::oneshot_send(ipc_tx, response, "");
+            hooks::emit(HookEvent::Logout);
             Some(())
         });
 }
 
+/// 切换profile前断开conductor和隧道, 保留profile的缓存状态
+pub fn logout_for_switch(
+    rpc_command_tx:     mpsc::Sender<RpcEvent>,
+    tunnel_command_tx:  mpsc::Sender<(TunnelCommand, mpsc::Sender<Response>)>,
+) -> Response {
+    let (rpc_stop_tx, rpc_stop_rx) = mpsc::channel::<bool>();
+    if rpc_command_tx.send(RpcEvent::Client(RpcClientCmd::Stop(rpc_stop_tx))).is_err() {
+        return Response::internal_error();
+    }
+    if rpc_stop_rx.recv_timeout(
+        Duration::from_secs(get_settings().common.http_timeout as u64)).is_err() {
+        return Response::exec_timeout();
+    }
+    let _ = send_tunnel_disconnect(tunnel_command_tx);
+    let mut info = get_mut_info().lock().unwrap();
+    info.status.rpc = RpcState::Disconnected;
+    info.status.cached = None;
+    info.teams.all_teams.clear();
+    std::mem::drop(info);
+    clean_info_user();
+    clean_all_running_teams();
+    clean_route_table();
+    hooks::emit(HookEvent::Logout);
+    Response::success()
+}
+
 fn need_logout(
     ipc_tx:             oneshot::Sender<Response>,
 ) -> Option<oneshot::Sender<Response>> {
@@ -47,12 +75,14 @@ fn need_logout(
         clean_settings_user();
         info!("clean_info_user.");
         clean_info_user();
+        state::remove_state();
         Some(ipc_tx)
     }
 }
 
 fn clean_settings_user() {
     let settings = get_mut_settings();
+    settings.remove_login();
     settings.common.username = "".to_owned();
     settings.common.password = "".to_owned();
 }
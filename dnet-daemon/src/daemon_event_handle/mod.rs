@@ -1,12 +1,20 @@
 pub mod common;
 pub mod connect;
+pub mod diagnostics;
+pub mod doctor;
 pub mod daemon_event_monitor;
 pub mod disconnect_team;
+#[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+pub mod drain;
 pub mod group_info;
 pub mod group_join;
 pub mod group_leave;
 pub mod group_users;
 pub mod handle_settings;
+pub mod identity;
 pub mod login;
 pub mod logout;
+pub mod network;
+pub mod profile;
+pub mod traffic;
 pub mod tunnel;
\ No newline at end of file
@@ -0,0 +1,48 @@
+//! 网络变化: 更新wan, tinc立即重连; 客户端重新探测proxy, 选择的proxy变化时重启隧道.
+
+use std::sync::mpsc;
+
+use dnet_types::daemon_broadcast::NetworkChange;
+#[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+use dnet_types::settings::RunMode;
+use dnet_types::status::RpcState;
+use tinc_plugin::PID_FILENAME;
+
+use crate::info::get_mut_info;
+use crate::rpc::rpc_cmd::{RpcEvent, RpcClientCmd};
+use crate::settings::get_settings;
+use crate::tinc_manager::retry_connections;
+
+pub fn handle_network_changed(change: &NetworkChange, rpc_command_tx: &mpsc::Sender<RpcEvent>) {
+    info!("network changed, dev {:?} -> {} wan {:?}", change.old_dev, change.dev, change.wan);
+    let logged_in = {
+        let mut info = get_mut_info().lock().unwrap();
+        info.client_info.wan = change.wan.clone();
+        info.status.rpc == RpcState::Connected
+    };
+
+    // tinc未运行时没有pid文件
+    let pid_path = get_settings().common.home_path
+        .join("tinc").join(PID_FILENAME)
+        .to_string_lossy().to_string();
+    if let Err(e) = retry_connections(&pid_path) {
+        debug!("tinc retry {:?}", e);
+    }
+
+    if is_client() && logged_in {
+        // 不等待结果, proxy探测可能需要较长时间
+        let (res_tx, _) = mpsc::channel();
+        let _ = rpc_command_tx.send(RpcEvent::Client(RpcClientCmd::ReportDeviceSelectProxy(res_tx)));
+    }
+}
+
+fn is_client() -> bool {
+    #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+        {
+            get_settings().common.mode == RunMode::Client
+        }
+    #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+        {
+            true
+        }
+}
@@ -0,0 +1,103 @@
+use std::sync::mpsc;
+
+use futures::sync::oneshot;
+
+use dnet_types::response::Response;
+use dnet_types::settings::Profile;
+use dnet_types::user::User;
+
+use tinc_plugin::TincOperatorError;
+
+use crate::rpc::rpc_cmd::RpcEvent;
+use crate::settings::{get_settings, get_mut_settings, Settings};
+use crate::daemon::{Daemon, TunnelCommand};
+use crate::info::state;
+use crate::tinc_manager::TincOperator;
+use super::{login, logout, tunnel};
+
+pub fn handle_profile_list(ipc_tx: oneshot::Sender<Response>) {
+    let data = serde_json::to_value(get_settings().profile_list()).ok();
+    let _ = Daemon::oneshot_send(ipc_tx, Response::success().set_data(data), "");
+}
+
+pub fn handle_profile_add(ipc_tx: oneshot::Sender<Response>, profile: Profile) {
+    let response = match get_mut_settings().add_profile(profile) {
+        Ok(_) => Response::success(),
+        Err(e) => Response::internal_error().set_msg(format!("{:?}", e)),
+    };
+    let _ = Daemon::oneshot_send(ipc_tx, response, "");
+}
+
+pub fn handle_profile_remove(ipc_tx: oneshot::Sender<Response>, name: String) {
+    let response = match get_mut_settings().remove_profile(&name) {
+        Ok(_) => Response::success(),
+        Err(e) => Response::internal_error().set_msg(format!("{:?}", e)),
+    };
+    let _ = Daemon::oneshot_send(ipc_tx, response, "");
+}
+
+/// 登出当前conductor并断开隧道, 清除旧conductor的host文件,
+/// 恢复新profile的缓存状态后登录新profile.
+/// 切换失败时恢复之前的profile并重新登录, 返回切换失败
+pub fn handle_profile_use(
+    ipc_tx:             oneshot::Sender<Response>,
+    name:               String,
+    rpc_command_tx:     mpsc::Sender<RpcEvent>,
+    tunnel_command_tx:  mpsc::Sender<(TunnelCommand, mpsc::Sender<Response>)>,
+) {
+    if !get_settings().profiles.contains_key(&name) {
+        let response = Response::internal_error().set_msg("Profile not found.".to_owned());
+        let _ = Daemon::oneshot_send(ipc_tx, response, "");
+        return;
+    }
+
+    let previous = get_settings().clone();
+    info!("switch to profile {}", name);
+    let response = logout::logout_for_switch(rpc_command_tx.clone(), tunnel_command_tx.clone());
+    if response.code != 200 {
+        let _ = Daemon::oneshot_send(ipc_tx, response, "");
+        return;
+    }
+
+    let failed = switch_profile(&name).err();
+    if let Some(e) = &failed {
+        error!("switch to profile {} {}, restore previous profile", name, e);
+        if let Err(e) = get_mut_settings().restore_profile(previous) {
+            error!("restore previous profile {:?}", e);
+        }
+        TincOperator::new().clear_hosts();
+        if let Err(e) = write_tinc_config() {
+            error!("{}", e);
+        }
+    }
+
+    if state::restore_state() {
+        info!("start tunnel with cached state of profile {:?}.", get_settings().common.profile);
+        let _ = tunnel::send_tunnel_connect(tunnel_command_tx);
+    }
+
+    let settings = get_settings();
+    let user = User::new(&settings.common.username, &settings.common.password);
+    match failed {
+        None => login::handle_login(ipc_tx, user, rpc_command_tx),
+        Some(e) => {
+            let (login_tx, _) = oneshot::channel();
+            login::handle_login(login_tx, user, rpc_command_tx);
+            let _ = Daemon::oneshot_send(ipc_tx, Response::internal_error().set_msg(e), "");
+        }
+    }
+}
+
+fn switch_profile(name: &str) -> Result<(), String> {
+    get_mut_settings().use_profile(name).map_err(|e|format!("{:?}", e))?;
+    TincOperator::new().clear_hosts();
+    write_tinc_config()
+}
+
+/// 没有缓存的vip时不写入, 登录后启动tinc时写入
+fn write_tinc_config() -> Result<(), String> {
+    match TincOperator::new().set_info_to_local() {
+        Ok(()) | Err(TincOperatorError::TincInfoError(_)) => Ok(()),
+        Err(e) => Err(format!("rewrite tinc config {:?}", e)),
+    }
+}
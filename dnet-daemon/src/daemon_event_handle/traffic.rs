@@ -0,0 +1,23 @@
+use futures::sync::oneshot;
+
+use tinc_plugin::{control, PID_FILENAME};
+use dnet_types::response::Response;
+
+use crate::daemon::Daemon;
+use crate::settings::get_settings;
+
+/// 各tinc节点的收发统计
+pub fn handle_traffic(ipc_tx: oneshot::Sender<Response>) {
+    let tinc_pid = get_settings().common.home_path
+        .join("tinc").join(PID_FILENAME)
+        .to_str().unwrap().to_string();
+
+    let response = match control::dump_traffic(&tinc_pid) {
+        Ok(traffic) => {
+            let data = serde_json::to_value(traffic).ok();
+            Response::success().set_data(data)
+        }
+        Err(e) => Response::internal_error().set_msg(e.to_string()),
+    };
+    let _ = Daemon::oneshot_send(ipc_tx, response, "");
+}
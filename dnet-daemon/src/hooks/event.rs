@@ -0,0 +1,87 @@
+use dnet_types::status::{TunnelState, SecurityEvent};
+use dnet_types::team::TeamEvent;
+
+use crate::settings::get_settings;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HookEvent {
+    TunnelState {
+        old:        TunnelState,
+        new:        TunnelState,
+    },
+    HostUp {
+        host:       String,
+    },
+    HostDown {
+        host:       String,
+    },
+    TeamJoin {
+        team_id:    String,
+    },
+    TeamLeave {
+        team_id:    String,
+    },
+    Login {
+        username:   String,
+    },
+    Logout,
+    ProxySwitch {
+        /// proxy vip
+        old:        Vec<String>,
+        new:        Vec<String>,
+    },
+    /// 一次同步中的所有变化
+    TeamChange {
+        changes:    Vec<TeamEvent>,
+    },
+    SecurityViolation {
+        violation:  SecurityEvent,
+    },
+}
+
+impl HookEvent {
+    /// hooks.d下的目录名, 与payload中的event字段相同
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::TunnelState { .. } => "tunnel_state",
+            HookEvent::HostUp { .. }      => "host_up",
+            HookEvent::HostDown { .. }    => "host_down",
+            HookEvent::TeamJoin { .. }    => "team_join",
+            HookEvent::TeamLeave { .. }   => "team_leave",
+            HookEvent::Login { .. }       => "login",
+            HookEvent::Logout             => "logout",
+            HookEvent::ProxySwitch { .. } => "proxy_switch",
+            HookEvent::TeamChange { .. }  => "team_change",
+            HookEvent::SecurityViolation { .. } => "security_violation",
+        }
+    }
+
+    pub fn payload(&self) -> String {
+        let mut value = serde_json::to_value(self)
+            .unwrap_or(serde_json::Value::Null);
+        if let Some(object) = value.as_object_mut() {
+            object.insert("timestamp".to_string(),
+                          serde_json::Value::from(chrono::Utc::now().to_rfc3339()));
+            object.insert("mode".to_string(),
+                          serde_json::to_value(&get_settings().common.mode)
+                              .unwrap_or(serde_json::Value::Null));
+        }
+        value.to_string()
+    }
+}
+
+#[test]
+fn test_hook_event_to_json() {
+    let event = HookEvent::TunnelState {
+        old: TunnelState::Connecting,
+        new: TunnelState::Connected,
+    };
+    assert_eq!(event.name(), "tunnel_state");
+    let value = serde_json::to_value(&event).unwrap();
+    assert_eq!(value["event"], "tunnel_state");
+    assert_eq!(value["new"], "Connected");
+
+    let value = serde_json::to_value(&HookEvent::Logout).unwrap();
+    assert_eq!(value["event"], HookEvent::Logout.name());
+}
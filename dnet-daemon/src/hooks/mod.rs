@@ -0,0 +1,9 @@
+//! 用户自定义事件hook.
+//! 事件发生时, 以JSON payload调用 <home_path>/hooks.d/<event>/ 下的可执行文件(stdin)
+//! 和settings.toml [hooks] webhooks 中的地址(POST).
+
+mod event;
+mod runner;
+
+pub use self::event::HookEvent;
+pub use self::runner::{init, emit};
@@ -0,0 +1,290 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::settings::get_settings;
+use crate::settings::default_settings::{HOOKS_DIR, HOOK_QUEUE_SIZE};
+use super::HookEvent;
+
+static mut EL: *mut mpsc::SyncSender<Job> = 0 as *mut _;
+
+enum Job {
+    Script {
+        path:       PathBuf,
+        event:      &'static str,
+        payload:    String,
+    },
+    Webhook {
+        url:        String,
+        event:      &'static str,
+        payload:    String,
+    },
+}
+
+/// 启动 [hooks] max_concurrency 个执行线程, 未init时emit不做任何操作
+pub fn init() {
+    let settings = &get_settings().hooks;
+    let job_tx = start_runners(
+        settings.max_concurrency,
+        HOOK_QUEUE_SIZE,
+        Duration::from_secs(settings.timeout as u64));
+
+    unsafe {
+        EL = Box::into_raw(Box::new(job_tx));
+    }
+}
+
+/// 同时最多执行max_concurrency个job, 其余在容量为queue_size的队列中等待
+fn start_runners(max_concurrency: u32, queue_size: usize, timeout: Duration) -> mpsc::SyncSender<Job> {
+    let (job_tx, job_rx) = mpsc::sync_channel::<Job>(queue_size);
+    let job_rx = Arc::new(Mutex::new(job_rx));
+
+    for i in 0..max_concurrency.max(1) {
+        let job_rx = job_rx.clone();
+        let _ = thread::Builder::new()
+            .name(format!("hook_runner_{}", i))
+            .spawn(move || {
+                loop {
+                    let job = match job_rx.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    run_job(job, timeout);
+                }
+            });
+    }
+    job_tx
+}
+
+/// 非阻塞, 队列已满时丢弃并记录日志
+pub fn emit(event: HookEvent) {
+    let job_tx = unsafe {
+        if EL == 0 as *mut _ {
+            return;
+        }
+        &*EL
+    };
+
+    let name = event.name();
+    let payload = event.payload();
+    let mut jobs = hook_scripts(name)
+        .into_iter()
+        .map(|path| Job::Script { path, event: name, payload: payload.clone() })
+        .collect::<Vec<Job>>();
+    for url in &get_settings().hooks.webhooks {
+        jobs.push(Job::Webhook { url: url.clone(), event: name, payload: payload.clone() });
+    }
+
+    for job in jobs {
+        if !enqueue(job_tx, job) {
+            warn!("hook {} queue full, dropped.", name);
+        }
+    }
+}
+
+/// 队列已满时返回false
+fn enqueue(job_tx: &mpsc::SyncSender<Job>, job: Job) -> bool {
+    match job_tx.try_send(job) {
+        Err(mpsc::TrySendError::Full(_)) => false,
+        _ => true,
+    }
+}
+
+/// hooks.d/<event>/ 下的可执行文件, 按文件名排序
+fn hook_scripts(event: &str) -> Vec<PathBuf> {
+    let dir = get_settings().common.home_path.join(HOOKS_DIR).join(event);
+    let mut scripts = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_executable(path))
+            .filter(|path| !path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with('.'))
+                .unwrap_or(true))
+            .collect::<Vec<PathBuf>>(),
+        Err(_) => vec![],
+    };
+    scripts.sort();
+    scripts
+}
+
+fn is_executable(path: &Path) -> bool {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+    if !metadata.is_file() {
+        return false;
+    }
+    #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.permissions().mode() & 0o111 != 0
+        }
+    #[cfg(windows)]
+        {
+            true
+        }
+}
+
+fn run_job(job: Job, timeout: Duration) {
+    let start = Instant::now();
+    match job {
+        Job::Script { path, event, payload } => {
+            match run_script(&path, event, &payload, timeout) {
+                Ok(status) => info!("hook {} {:?} {} {:?}", event, path, status, start.elapsed()),
+                Err(e) => warn!("hook {} {:?} {} {:?}", event, path, e, start.elapsed()),
+            }
+        }
+        Job::Webhook { url, event, payload } => {
+            match post_webhook(&url, &payload, timeout) {
+                Ok(status) => info!("hook {} {} {} {:?}", event, url, status, start.elapsed()),
+                Err(e) => warn!("hook {} {} {} {:?}", event, url, e, start.elapsed()),
+            }
+        }
+    }
+}
+
+fn run_script(path: &Path, event: &str, payload: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = Command::new(path)
+        .env("DNET_HOOK_EVENT", event)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // 脚本不读stdin时管道写满会阻塞, 在单独线程中写入, 不影响超时处理
+    if let Some(mut stdin) = child.stdin.take() {
+        let payload = payload.to_string();
+        let _ = thread::Builder::new()
+            .name("hook_stdin".to_string())
+            .spawn(move || {
+                let _ = stdin.write_all(payload.as_bytes());
+            });
+    }
+
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                return if status.success() {
+                    Ok(status.to_string())
+                }
+                else {
+                    Err(status.to_string())
+                };
+            }
+            Ok(None) => {
+                if start.elapsed() > timeout {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err("timeout, killed".to_string());
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+fn post_webhook(url: &str, payload: &str, timeout: Duration) -> Result<String, String> {
+    let client = reqwest::ClientBuilder::new()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let res = client.post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .send()
+        .map_err(|e| e.to_string())?;
+    if res.status().is_success() {
+        Ok(res.status().to_string())
+    }
+    else {
+        Err(res.status().to_string())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn script(dir: &Path, body: &str) -> PathBuf {
+        let path = dir.join("hook.sh");
+        fs::write(&path, "#!/bin/sh\n".to_string() + body + "\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn job(path: &Path, payload: &str) -> Job {
+        Job::Script { path: path.to_path_buf(), event: "tunnel_state", payload: payload.to_string() }
+    }
+
+    #[test]
+    fn test_run_script_payload() {
+        let dir = test_dir("dnet_hook_payload_test");
+        let path = script(&dir, "cat > \"$0.out\"; echo \"$DNET_HOOK_EVENT\" >> \"$0.out\"");
+        assert!(run_script(&path, "tunnel_state", "{\"event\":\"tunnel_state\"}", Duration::from_secs(5)).is_ok());
+        assert_eq!(fs::read_to_string(dir.join("hook.sh.out")).unwrap(),
+                   "{\"event\":\"tunnel_state\"}tunnel_state\n");
+        assert!(run_script(&script(&dir, "exit 3"), "tunnel_state", "", Duration::from_secs(5)).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_run_script_timeout() {
+        let dir = test_dir("dnet_hook_timeout_test");
+        // 脚本不读stdin也不退出, 超时后被kill
+        let path = script(&dir, "echo $$ > \"$0.pid\"; exec sleep 10");
+        let start = Instant::now();
+        let payload = "x".repeat(1024 * 1024);
+        assert_eq!(run_script(&path, "tunnel_state", &payload, Duration::from_millis(500)),
+                   Err("timeout, killed".to_string()));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        if cfg!(target_os = "linux") {
+            let pid = fs::read_to_string(dir.join("hook.sh.pid")).unwrap();
+            assert!(!Path::new(&format!("/proc/{}", pid.trim())).exists());
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_concurrency_and_queue() {
+        let dir = test_dir("dnet_hook_concurrency_test");
+        let path = script(&dir, "echo start >> \"$0.log\"; sleep 1");
+        let started = || fs::read_to_string(dir.join("hook.sh.log"))
+            .map(|log| log.lines().count())
+            .unwrap_or(0);
+
+        // 2个线程, 队列容量1: 2个job执行, 1个等待, 第4个丢弃
+        let job_tx = start_runners(2, 1, Duration::from_secs(5));
+        for _ in 0..2 {
+            thread::sleep(Duration::from_millis(100));
+            assert!(enqueue(&job_tx, job(&path, "")));
+        }
+        thread::sleep(Duration::from_millis(200));
+        assert!(enqueue(&job_tx, job(&path, "")));
+        assert!(!enqueue(&job_tx, job(&path, "")));
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(started(), 2);
+
+        // 前两个job结束后执行排队的job
+        thread::sleep(Duration::from_millis(1000));
+        assert_eq!(started(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
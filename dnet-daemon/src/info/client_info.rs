@@ -4,18 +4,16 @@ use super::error::{Error, Result};
 
 #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
 use router_plugin::device_info::DeviceInfo;
-#[cfg(all(target_os = "linux", feature = "router_debug"))]
-use crate::settings::get_settings;
-#[cfg(target_arch = "arm")]
-use router_plugin::get_sn;
 #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
 extern crate base64;
 
 use dnet_types::device_type::DeviceType;
-use sandbox::route::{get_default_route, get_mac};
 use dnet_types::team::NetSegment;
 use sandbox::interface::get_default_interface;
 
+use crate::settings::get_settings;
+use super::identity;
+
 #[derive(Debug, Clone)]
 pub struct ClientInfo {
     pub devicetype:             DeviceType,
@@ -57,35 +55,26 @@ impl ClientInfo {
     }
 
     fn get_uid(device_type: &DeviceType) -> Result<String> {
-        let mac = get_default_route()
-            .and_then(|route_info| {
-                get_mac(&route_info.dev)
-            })
-            .map_err(Error::ParseDeviceMac)?
-            .replace(":", "");
+        #[cfg(feature = "router_debug")]
+            {
+                if let DeviceType::Linux = device_type {
+                    return Ok(get_settings().common.username.clone());
+                }
+            }
 
-        let uid;
-        match device_type {
-            DeviceType::Linux => {
-                #[cfg(feature = "router_debug")]
-                    {
-                        uid = get_settings().common.username.clone();
-                    }
+        let settings = get_settings();
+        let identity = identity::load_or_resolve(
+            &settings.common.home_path, &settings.common.identity_providers)?;
+        Ok(identity::device_name(device_type, &identity))
+    }
 
-                #[cfg(not(feature = "router_debug"))]
-                    {
-                        uid = "linux/".to_owned() + &mac;
-                    }
+    /// 身份迁移后更新设备名, 路由器的设备密码随之变化
+    pub fn set_device_name(&mut self, device_name: String) {
+        #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+            {
+                self.device_password = base64::encode(&device_name);
             }
-            #[cfg(target_arch = "arm")]
-            DeviceType::Router => uid = get_sn().ok_or(Error::GetMac)?,
-            DeviceType::MAC => uid = "macos/".to_owned() + &mac,
-            DeviceType::IOS => uid = "ios/".to_owned() + &mac,
-            DeviceType::Windows => uid = "windows/".to_owned() + &mac,
-            DeviceType::Cloud => uid = "cloud/".to_owned() + &mac,
-            _ => uid = "unknown/".to_owned() + &mac,
-        };
-        Ok(uid)
+        self.device_name = device_name;
     }
 
     pub fn get_lan_str(&self) -> String {
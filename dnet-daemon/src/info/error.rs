@@ -34,4 +34,7 @@ pub enum Error {
 
     #[error(display = "Get DeviceInfo.")]
     GetDeviceInfo,
+
+    #[error(display = "Can not resolve device identity.")]
+    Identity,
 }
\ No newline at end of file
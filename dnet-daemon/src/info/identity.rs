@@ -0,0 +1,293 @@
+//! 设备身份. 按 [common] identity_providers 的顺序解析, 第一次解析到的身份保存在
+//! home_path/identity.json, 之后不再随默认路由的网卡变化.
+//! 升级前已经注册过的设备首次启动时保存旧的身份(默认路由网卡MAC, 路由器为SN),
+//! 使用 dnet identity migrate 切换到稳定的身份.
+//! machine_id不直接上报/etc/machine-id(systemd要求保密, 也是credential密钥的输入),
+//! 而是与sd_id128_get_machine_app_specific相同, 以machine-id为密钥计算HMAC-SHA256.
+//! 从同一镜像克隆且没有重新生成machine-id的虚拟机会得到相同的身份, 此类设备应使用generated.
+
+use std::fs;
+use std::path::Path;
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+use dnet_types::device_type::DeviceType;
+use dnet_types::identity::{Identity, IdentityProvider};
+use sandbox::route::{get_default_route, get_mac};
+use tinc_plugin::PUB_KEY_FILENAME;
+
+use crate::settings::credential;
+use super::error::{Error, Result};
+
+const IDENTITY_FILENAME: &str = "identity.json";
+const IDENTITY_APP_ID: &[u8] = b"dnet-identity";
+/// DMI中厂商未填写时的占位值
+const DMI_PLACEHOLDERS: &[&str] = &[
+    "to be filled by o.e.m.", "default string", "not specified", "not applicable",
+    "system serial number", "none", "0", "123456789",
+];
+
+/// 旧版本使用的身份
+fn legacy_provider() -> IdentityProvider {
+    #[cfg(target_arch = "arm")]
+        {
+            IdentityProvider::VendorSn
+        }
+    #[cfg(not(target_arch = "arm"))]
+        {
+            IdentityProvider::Mac
+        }
+}
+
+pub fn load(home_path: &Path) -> Option<Identity> {
+    let buf = fs::read_to_string(home_path.join(IDENTITY_FILENAME)).ok()?;
+    serde_json::from_str(&buf)
+        .map_err(|e|error!("parse {} {:?}", IDENTITY_FILENAME, e))
+        .ok()
+}
+
+/// 已保存的身份, 没有时解析并保存
+pub fn load_or_resolve(home_path: &Path, providers: &[IdentityProvider]) -> Result<Identity> {
+    load_or_resolve_with(home_path, providers, &resolve)
+}
+
+fn load_or_resolve_with(
+    home_path:  &Path,
+    providers:  &[IdentityProvider],
+    resolve:    &dyn Fn(&Path, IdentityProvider) -> Option<Identity>,
+) -> Result<Identity> {
+    if let Some(identity) = load(home_path) {
+        return Ok(identity);
+    }
+
+    // 已经注册过的设备保持原有身份, 避免升级后在conductor中变成新设备
+    let registered = home_path.join("tinc").join(PUB_KEY_FILENAME).is_file();
+    let legacy = Some(legacy_provider()).filter(|_|registered);
+    let identity = legacy.iter()
+        .chain(providers)
+        .filter_map(|provider|resolve(home_path, *provider))
+        .next()
+        .ok_or(Error::Identity)?;
+    info!("device identity {} {}", identity.provider.name(), identity.id);
+    save(home_path, &identity)?;
+    Ok(identity)
+}
+
+/// 重新解析身份并保存, provider为None时按providers顺序, 跳过旧的MAC身份
+pub fn migrate(
+    home_path:  &Path,
+    providers:  &[IdentityProvider],
+    provider:   Option<IdentityProvider>,
+) -> Result<Identity> {
+    migrate_with(home_path, providers, provider, &resolve)
+}
+
+fn migrate_with(
+    home_path:  &Path,
+    providers:  &[IdentityProvider],
+    provider:   Option<IdentityProvider>,
+    resolve:    &dyn Fn(&Path, IdentityProvider) -> Option<Identity>,
+) -> Result<Identity> {
+    let providers = match provider {
+        Some(provider) => vec![provider],
+        None => providers.iter()
+            .filter(|provider|**provider != IdentityProvider::Mac)
+            .cloned()
+            .collect(),
+    };
+    let identity = providers.iter()
+        .filter_map(|provider|resolve(home_path, *provider))
+        .next()
+        .ok_or(Error::Identity)?;
+    info!("migrate device identity to {} {}", identity.provider.name(), identity.id);
+    save(home_path, &identity)?;
+    Ok(identity)
+}
+
+/// 上报给conductor的设备名
+pub fn device_name(device_type: &DeviceType, identity: &Identity) -> String {
+    let prefix = match device_type {
+        DeviceType::Router => return identity.id.clone(),
+        DeviceType::Linux => "linux/",
+        DeviceType::MAC => "macos/",
+        DeviceType::IOS => "ios/",
+        DeviceType::Windows => "windows/",
+        DeviceType::Cloud => "cloud/",
+        _ => "unknown/",
+    };
+    prefix.to_owned() + &identity.id
+}
+
+fn resolve(home_path: &Path, provider: IdentityProvider) -> Option<Identity> {
+    let id = match provider {
+        IdentityProvider::Generated => Some(uuid::Uuid::new_v4().to_simple().to_string()),
+        IdentityProvider::MachineId => credential::machine_id()
+            .and_then(|machine_id|app_specific_id(&machine_id)),
+        IdentityProvider::Dmi => dmi_serial(),
+        IdentityProvider::VendorSn => vendor_sn(),
+        IdentityProvider::Mac => get_default_route()
+            .and_then(|route_info|get_mac(&route_info.dev))
+            .map_err(|e|warn!("identity mac {:?}", e))
+            .ok()
+            .map(|mac|mac.replace(":", "")),
+    };
+    let id = sanitize(&id?)?;
+    debug!("resolved identity {} {} in {:?}", provider.name(), id, home_path);
+    Some(Identity {
+        provider,
+        id,
+        resolved_at: chrono::Utc::now().to_string(),
+    })
+}
+
+fn save(home_path: &Path, identity: &Identity) -> Result<()> {
+    let buf = serde_json::to_string_pretty(identity)
+        .map_err(|_|Error::Identity)?;
+    credential::write_private(&home_path.join(IDENTITY_FILENAME), buf.as_bytes())
+        .map_err(|e| {
+            error!("save identity {:?}", e);
+            Error::Identity
+        })
+}
+
+/// HMAC-SHA256(machine-id, IDENTITY_APP_ID)的前16字节, 不能反推machine-id
+fn app_specific_id(machine_id: &str) -> Option<String> {
+    let key = PKey::hmac(machine_id.as_bytes())
+        .map_err(|e|error!("identity hmac {:?}", e))
+        .ok()?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)
+        .map_err(|e|error!("identity hmac {:?}", e))
+        .ok()?;
+    signer.update(IDENTITY_APP_ID).ok()?;
+    let hmac = signer.sign_to_vec().ok()?;
+    Some(hmac[..16].iter().map(|byte|format!("{:02x}", byte)).collect())
+}
+
+#[cfg(target_os = "linux")]
+fn dmi_serial() -> Option<String> {
+    ["product_serial", "board_serial", "product_uuid"].iter()
+        .filter_map(|name|fs::read_to_string(Path::new("/sys/class/dmi/id").join(name)).ok())
+        .map(|serial|serial.trim().to_string())
+        .find(|serial|!DMI_PLACEHOLDERS.contains(&serial.to_lowercase().as_str()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn dmi_serial() -> Option<String> {
+    None
+}
+
+#[cfg(target_arch = "arm")]
+fn vendor_sn() -> Option<String> {
+    router_plugin::get_sn()
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn vendor_sn() -> Option<String> {
+    None
+}
+
+/// 只保留字母, 数字和 - _ . , 全0等无效值返回None
+fn sanitize(id: &str) -> Option<String> {
+    let id: String = id.trim().chars()
+        .filter(|c|c.is_ascii_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
+        .collect();
+    if id.chars().all(|c|c == '0' || c == '-' || c == '.' || c == '_') {
+        return None;
+    }
+    Some(id)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize(" 4c4c4544-0051 \n"), Some("4c4c4544-0051".to_string()));
+        assert_eq!(sanitize("VMware-56 4d/b2"), Some("VMware-564db2".to_string()));
+        assert_eq!(sanitize("00000000-0000-0000-0000-000000000000"), None);
+        assert_eq!(sanitize(""), None);
+    }
+
+    #[test]
+    fn test_device_name() {
+        let identity = Identity {
+            provider:       IdentityProvider::MachineId,
+            id:             "0123abcd".to_string(),
+            resolved_at:    String::new(),
+        };
+        assert_eq!(device_name(&DeviceType::Linux, &identity), "linux/0123abcd");
+        assert_eq!(device_name(&DeviceType::Router, &identity), "0123abcd");
+    }
+
+    #[test]
+    fn test_app_specific_id() {
+        let id = app_specific_id("0123456789abcdef0123456789abcdef").unwrap();
+        assert_eq!(id, "f6b1061d03c65fe1913c2583c6fae457");
+        assert!(!id.contains("0123456789abcdef"));
+    }
+
+    fn fake_resolve(_home_path: &Path, provider: IdentityProvider) -> Option<Identity> {
+        let id = match provider {
+            IdentityProvider::Mac => "0242ac110002",
+            IdentityProvider::VendorSn => "sn",
+            IdentityProvider::MachineId => "machine",
+            IdentityProvider::Generated => "generated",
+            _ => return None,
+        };
+        Some(Identity {
+            provider,
+            id:             id.to_string(),
+            resolved_at:    String::new(),
+        })
+    }
+
+    fn test_home(name: &str) -> std::path::PathBuf {
+        let home_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&home_path);
+        fs::create_dir_all(home_path.join("tinc")).unwrap();
+        home_path
+    }
+
+    #[test]
+    fn test_load_or_resolve() {
+        let providers = [IdentityProvider::Dmi, IdentityProvider::MachineId, IdentityProvider::Generated];
+
+        // 新安装按providers顺序解析, 之后使用保存的身份
+        let home_path = test_home("dnet_identity_new_test");
+        let identity = load_or_resolve_with(&home_path, &providers, &fake_resolve).unwrap();
+        assert_eq!(identity.provider, IdentityProvider::MachineId);
+        let saved = load_or_resolve_with(&home_path, &[IdentityProvider::Generated], &fake_resolve).unwrap();
+        assert_eq!(saved, identity);
+        let _ = fs::remove_dir_all(&home_path);
+
+        // 已注册的设备升级后保持旧的身份
+        let home_path = test_home("dnet_identity_legacy_test");
+        fs::write(home_path.join("tinc").join(PUB_KEY_FILENAME), "pubkey").unwrap();
+        let identity = load_or_resolve_with(&home_path, &providers, &fake_resolve).unwrap();
+        assert_eq!(identity.provider, legacy_provider());
+        assert_eq!(load(&home_path), Some(identity));
+        let _ = fs::remove_dir_all(&home_path);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let providers = [IdentityProvider::Mac, IdentityProvider::MachineId, IdentityProvider::Generated];
+        let home_path = test_home("dnet_identity_migrate_test");
+        fs::write(home_path.join("tinc").join(PUB_KEY_FILENAME), "pubkey").unwrap();
+        load_or_resolve_with(&home_path, &providers, &fake_resolve).unwrap();
+
+        // 跳过旧的MAC身份
+        let identity = migrate_with(&home_path, &providers, None, &fake_resolve).unwrap();
+        assert_eq!(identity.provider, IdentityProvider::MachineId);
+        assert_eq!(load(&home_path), Some(identity));
+
+        let identity = migrate_with(&home_path, &providers, Some(IdentityProvider::Generated), &fake_resolve).unwrap();
+        assert_eq!(identity.id, "generated");
+        assert!(migrate_with(&home_path, &providers, Some(IdentityProvider::Dmi), &fake_resolve).is_err());
+        assert_eq!(load(&home_path), Some(identity));
+        let _ = fs::remove_dir_all(&home_path);
+    }
+}
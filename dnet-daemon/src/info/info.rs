@@ -7,6 +7,7 @@ use dnet_types::team::Team;
 use dnet_types::status::{Status, TunnelState};
 
 use crate::settings::get_settings;
+use crate::hooks::{self, HookEvent};
 use super::error::{Error, Result};
 use super::{TeamInfo, NodeInfo, UserInfo, ClientInfo, TincInfo};
 
@@ -69,6 +70,7 @@ impl Info {
                 vip:            vip.clone(),
                 port:           settings.tinc.port,
                 pub_key:        self.tinc_info.pub_key.clone(),
+                ed25519_pub_key: self.tinc_info.ed25519_pub_key.clone(),
                 mode:           tinc_run_model,
                 connect_to:     self.tinc_info.connect_to.clone(),
             })
@@ -81,6 +83,7 @@ impl Info {
         if old_info != *new_info {
             self.tinc_info.vip = Some(new_info.vip.clone());
             self.tinc_info.pub_key = new_info.pub_key.clone();
+            self.tinc_info.ed25519_pub_key = new_info.ed25519_pub_key.clone();
             self.tinc_info.connect_to = new_info.connect_to.clone();
             self.proxy_info.ip = new_info.ip.clone();
             return Ok(true)
@@ -90,6 +93,23 @@ impl Info {
         }
     }
 
+    /// 状态变化时触发tunnel_state hook
+    /// 状态变化时返回hook事件, 由调用者释放info锁后再emit
+    #[must_use]
+    pub fn set_tunnel_state(&mut self, state: TunnelState) -> Option<HookEvent> {
+        let event = if self.status.tunnel != state {
+            Some(HookEvent::TunnelState {
+                old: self.status.tunnel.clone(),
+                new: state.clone(),
+            })
+        }
+        else {
+            None
+        };
+        self.status.tunnel = state;
+        event
+    }
+
     pub fn fresh_running_from_all(&mut self) {
         let self_name = &self.client_info.device_name;
         let mut running_teams = vec![];
@@ -145,6 +165,14 @@ pub fn get_info() -> &'static Mutex<Info> {
     }
 }
 
+/// 修改隧道状态, 释放info锁后emit hook事件
+pub fn set_tunnel_state(state: TunnelState) {
+    let event = get_mut_info().lock().unwrap().set_tunnel_state(state);
+    if let Some(event) = event {
+        hooks::emit(event);
+    }
+}
+
 pub fn get_mut_info() ->  &'static mut Mutex<Info> {
     unsafe {
         if EL == 0 as *mut _ {
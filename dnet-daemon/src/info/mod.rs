@@ -1,7 +1,9 @@
 mod client_info;
 mod error;
+pub mod identity;
 mod info;
 mod node;
+pub mod state;
 mod team_info;
 mod tinc;
 mod user;
@@ -13,4 +15,4 @@ pub use self::node::NodeInfo;
 pub use self::team_info::TeamInfo;
 pub use self::tinc::TincInfo;
 pub use self::user::UserInfo;
-pub use self::info::{get_info, get_mut_info};
\ No newline at end of file
+pub use self::info::{get_info, get_mut_info, set_tunnel_state};
\ No newline at end of file
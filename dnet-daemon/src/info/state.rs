@@ -0,0 +1,258 @@
+//! 最近一次成功同步的状态(team列表, 成员host公钥, 选择的proxy, 加密的token),
+//! 保存为 home_path/state.json(使用profile时为 home_path/profiles/<name>/state.json).
+//! 启动时恢复, conductor不可达时以缓存状态拉起隧道, conductor重新连接后由正常的同步流程刷新.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, Once};
+
+use sandbox::route;
+use tinc_plugin::ConnectTo;
+use dnet_types::settings::RunMode;
+use dnet_types::team::Team;
+
+use crate::settings::get_settings;
+use crate::settings::credential;
+use crate::settings::default_settings::TINC_INTERFACE;
+use crate::tinc_manager::TincOperator;
+#[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+use crate::tinc_manager::host_store;
+use super::{get_mut_info, Info};
+
+/// 状态文件格式版本, 格式不兼容时递增, 旧版本文件将被忽略
+pub const STATE_VERSION: u32 = 1;
+const STATE_FILENAME: &str = "state.json";
+
+static STATE_LOCK_INIT: Once = Once::new();
+static mut STATE_LOCK: *const Mutex<()> = 0 as *const _;
+
+/// state.json的所有读-改-写都在此锁内完成. 与info锁同时持有时先取此锁.
+fn lock_state() -> MutexGuard<'static, ()> {
+    unsafe {
+        STATE_LOCK_INIT.call_once(|| STATE_LOCK = Box::into_raw(Box::new(Mutex::new(()))));
+        (*STATE_LOCK).lock().unwrap_or_else(|e|e.into_inner())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostKey {
+    pub vip:                IpAddr,
+    pub pubkey:             String,
+    pub ed25519_pubkey:     Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedState {
+    pub version:            u32,
+    pub saved_at:           String,
+    pub token:              String,
+    pub teams:              HashMap<String, Team>,
+    pub running_teams:      Vec<String>,
+    pub connect_to:         Vec<ConnectTo>,
+    pub hosts:              Vec<HostKey>,
+}
+
+impl PersistedState {
+    fn from_info(info: &Info, hosts: Vec<HostKey>) -> Self {
+        let token = credential::encrypt(&get_settings().common.home_path, &info.node.token)
+            .map_err(|e|error!("encrypt token {:?}", e))
+            .unwrap_or(String::new());
+        Self {
+            version:        STATE_VERSION,
+            saved_at:       chrono::Utc::now().to_string(),
+            token,
+            teams:          info.teams.all_teams.clone(),
+            running_teams:  info.teams.running_teams.clone(),
+            connect_to:     info.tinc_info.connect_to.clone(),
+            hosts,
+        }
+    }
+
+    /// 版本不一致或内容损坏时返回None
+    pub fn parse(buf: &str) -> Option<Self> {
+        let value: serde_json::Value = serde_json::from_str(buf)
+            .map_err(|e|error!("parse state file {:?}", e))
+            .ok()?;
+        let version = value.get("version").and_then(|version|version.as_u64());
+        if version != Some(STATE_VERSION as u64) {
+            warn!("ignore state file version {:?}, expect {}", version, STATE_VERSION);
+            return None;
+        }
+        serde_json::from_value(value)
+            .map_err(|e|error!("parse state file {:?}", e))
+            .ok()
+    }
+}
+
+pub fn load_state() -> Option<PersistedState> {
+    let _guard = lock_state();
+    read_state()
+}
+
+/// 同步成功后保存当前info, 保留已保存的host公钥
+pub fn save_state() {
+    let _guard = lock_state();
+    let hosts = not_revoked(read_state().map(|state|state.hosts).unwrap_or(vec![]));
+    let info = get_mut_info().lock().unwrap();
+    write_state(&PersistedState::from_info(&info, hosts));
+}
+
+/// 保存proxy下发的全部设备公钥
+pub fn save_hosts(hosts: Vec<HostKey>) {
+    let _guard = lock_state();
+    let hosts = not_revoked(hosts);
+    let info = get_mut_info().lock().unwrap();
+    write_state(&PersistedState::from_info(&info, hosts));
+}
+
+/// 登出后不再使用缓存的token和team
+pub fn remove_state() {
+    let _guard = lock_state();
+    let _ = fs::remove_file(state_path());
+}
+
+/// 恢复状态文件到info并重写host文件, 标记为缓存状态.
+/// 返回是否应以缓存状态拉起隧道.
+pub fn restore_state() -> bool {
+    let state = match load_state() {
+        Some(state) => state,
+        None => return false,
+    };
+    info!("restore cached state saved at {}", state.saved_at);
+
+    let tinc = TincOperator::new();
+    for host in &not_revoked(state.hosts) {
+        if let Err(e) = tinc.set_hosts(
+            None,
+            host.vip,
+            &host.pubkey,
+            host.ed25519_pubkey.as_ref().map(String::as_str))
+        {
+            error!("restore host vip:{} err:{:?}", host.vip, e.to_string());
+        }
+    }
+
+    let token = credential::decrypt(&get_settings().common.home_path, &state.token)
+        .map_err(|e|error!("decrypt token {:?}", e))
+        .unwrap_or(String::new());
+
+    let mut info = get_mut_info().lock().unwrap();
+    info.node.token = token;
+    info.teams.all_teams = state.teams;
+    info.teams.running_teams = state.running_teams;
+    info.tinc_info.connect_to = state.connect_to;
+    info.status.cached = Some(state.saved_at);
+    let hosts = info.teams.get_connect_hosts(info.client_info.wan.clone(), &info.tinc_info.vip);
+    let local_vip = info.tinc_info.vip.clone();
+    let tunnel_up = match get_settings().common.mode {
+        RunMode::Proxy | RunMode::Center => true,
+        RunMode::Client => {
+            let has_proxy = !info.tinc_info.connect_to.is_empty();
+            #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+                {
+                    has_proxy
+                }
+            #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+                {
+                    has_proxy && !info.teams.running_teams.is_empty()
+                }
+        }
+    };
+    std::mem::drop(info);
+
+    if tunnel_up && !hosts.is_empty() {
+        let _ = std::thread::Builder::new()
+            .name("keep_route".to_string())
+            .spawn(move ||
+                route::keep_route(local_vip, hosts, TINC_INTERFACE.to_string())
+            );
+    }
+    tunnel_up
+}
+
+/// conductor重新连接, 退出缓存状态
+pub fn clear_cached() {
+    let mut info = get_mut_info().lock().unwrap();
+    if let Some(saved_at) = info.status.cached.take() {
+        info!("conductor connected, revalidate cached state saved at {}", saved_at);
+    }
+}
+
+/// proxy上已吊销的公钥不保存, 也不恢复host文件
+fn not_revoked(hosts: Vec<HostKey>) -> Vec<HostKey> {
+    #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+        {
+            let revoked = host_store::revoked_keys();
+            hosts.into_iter()
+                .filter(|host|!revoked.iter().any(|key|key.matches(&host.vip, &host.pubkey)))
+                .collect()
+        }
+    #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+        {
+            hosts
+        }
+}
+
+fn read_state() -> Option<PersistedState> {
+    let buf = fs::read_to_string(state_path()).ok()?;
+    PersistedState::parse(&buf)
+}
+
+fn write_state(state: &PersistedState) {
+    let path = state_path();
+    let res = serde_json::to_string(state)
+        .map_err(|e|e.to_string())
+        .and_then(|buf|credential::write_private(&path, buf.as_bytes())
+            .map_err(|e|format!("{:?}", e)));
+    if let Err(e) = res {
+        error!("save state {:?} {}", path, e);
+    }
+}
+
+/// 使用profile时每个profile有独立的状态文件
+fn state_path() -> PathBuf {
+    get_settings().profile_dir().join(STATE_FILENAME)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn state() -> PersistedState {
+        let mut team = Team::new();
+        team.team_id = "team".to_owned();
+        let mut teams = HashMap::new();
+        teams.insert(team.team_id.clone(), team);
+        PersistedState {
+            version:        STATE_VERSION,
+            saved_at:       "2020-01-01 00:00:00 UTC".to_owned(),
+            token:          "token".to_owned(),
+            teams,
+            running_teams:  vec!["team".to_owned()],
+            connect_to:     vec![],
+            hosts:          vec![HostKey {
+                vip:            "10.253.0.2".parse().unwrap(),
+                pubkey:         "pubkey".to_owned(),
+                ed25519_pubkey: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let state = state();
+        let buf = serde_json::to_string(&state).unwrap();
+        assert_eq!(PersistedState::parse(&buf), Some(state));
+    }
+
+    #[test]
+    fn test_state_version_mismatch() {
+        let mut state = state();
+        state.version = STATE_VERSION + 1;
+        let buf = serde_json::to_string(&state).unwrap();
+        assert_eq!(PersistedState::parse(&buf), None);
+        assert_eq!(PersistedState::parse("{}"), None);
+    }
+}
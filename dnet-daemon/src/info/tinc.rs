@@ -13,6 +13,7 @@ use super::error::{Error, Result};
 pub struct TincInfo {
     pub vip:                    Option<IpAddr>,
     pub pub_key:                String,
+    pub ed25519_pub_key:        Option<String>,
     pub port:                   u16,
     pub connections:            u32,
     pub edges:                  u32,
@@ -30,6 +31,7 @@ impl TincInfo {
         TincInfo {
             vip:                    None,
             pub_key,
+            ed25519_pub_key:        None,
             port:                   DEFAULT_TINC_PORT,
             connections:            0,
             edges:                  0,
@@ -50,6 +52,9 @@ impl TincInfo {
         self.pub_key = self.load_local_pubkey()
             .map_err(|e|error!("Must create tinc key pair before Info init. {:?}", e))
             .expect("Must create tinc key pair before Info init.");
+        self.ed25519_pub_key = TincOperator::new().get_ed25519_pub_key()
+            .map_err(|e|error!("load local ed25519 pubkey {:?}", e))
+            .unwrap_or(None);
     }
 
     fn load_local_vip(&self) -> Result<IpAddr> {
@@ -12,8 +12,11 @@ pub mod info;
 pub mod tinc_manager;
 pub mod daemon;
 mod daemon_event_handle;
+pub mod hooks;
 mod logging;
+mod network_watcher;
 pub mod rpc;
+mod select_proxy_notify;
 pub mod settings;
 pub mod traits;
 mod shutdown;
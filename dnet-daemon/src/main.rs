@@ -7,15 +7,13 @@ use clap::{App, Arg, ArgMatches};
 use sysinfo::{System, SystemExt, ProcessExt};
 
 extern crate dnet_daemon;
-use dnet_daemon::settings::{Settings, get_settings, Error as SettingsError};
+use dnet_daemon::settings::{Settings, get_settings, Error as SettingsError, LOG_FILENAME};
 use dnet_daemon::init_logger;
 
 pub const COMMIT_ID: &str = include_str!(concat!(env!("OUT_DIR"), "/git-commit-id.txt"));
 
 pub const COMMIT_DATE: &str = include_str!(concat!(env!("OUT_DIR"), "/git-commit-date.txt"));
 
-pub const LOG_FILENAME: &str = "dnet.log";
-
 pub const DEFAULT_CONFIG_DIR: &str = "/opt/dnet";
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,265 @@
+//! 网络变化监控. linux上订阅netlink的link, address, route(IPv4和IPv6)事件, 合并一段时间内的事件后检查一次;
+//! 其他系统定期检查. 默认路由网卡, 网关或网卡地址变化时发送DaemonEvent::NetworkChanged.
+//! tinc自己增删的路由不影响默认路由, 不会触发.
+
+use std::net::IpAddr;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use dnet_types::daemon_broadcast::NetworkChange;
+use sandbox::interface::get_default_interface;
+use sandbox::route::get_default_route;
+
+use crate::daemon::DaemonEvent;
+#[cfg(target_os = "linux")]
+use crate::settings::default_settings::NETWORK_CHANGE_DEBOUNCE_MILLIS;
+use crate::settings::default_settings::NETWORK_POLL_FREQUENCY_SEC;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Snapshot {
+    dev:    String,
+    gw:     String,
+    addrs:  Vec<IpAddr>,
+}
+
+pub fn start(daemon_event_tx: Sender<DaemonEvent>) {
+    let _ = thread::Builder::new()
+        .name("network_watcher".to_string())
+        .spawn(move || watch(daemon_event_tx))
+        .map_err(|e|error!("start network watcher {:?}", e));
+}
+
+fn watch(daemon_event_tx: Sender<DaemonEvent>) {
+    #[cfg(target_os = "linux")]
+    let mut socket = netlink::Socket::open()
+        .map_err(|e|warn!("netlink unavailable, poll default route. {:?}", e))
+        .ok();
+
+    let mut last = snapshot();
+    loop {
+        #[cfg(target_os = "linux")]
+        let waited = match &socket {
+            Some(netlink) => match netlink.wait(NETWORK_CHANGE_DEBOUNCE_MILLIS) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!("netlink recv {:?}, poll default route.", e);
+                    socket = None;
+                    false
+                }
+            },
+            None => false,
+        };
+        #[cfg(not(target_os = "linux"))]
+        let waited = false;
+        if !waited {
+            thread::sleep(Duration::from_secs(NETWORK_POLL_FREQUENCY_SEC));
+        }
+
+        let current = snapshot();
+        if current == last {
+            continue;
+        }
+        info!("network changed {:?} -> {:?}", last, current);
+        if let Some((old_dev, dev)) = change(&last, &current) {
+            match get_default_interface() {
+                Ok(wan) => {
+                    let change = NetworkChange {
+                        old_dev,
+                        dev,
+                        wan,
+                    };
+                    if daemon_event_tx.send(DaemonEvent::NetworkChanged(change)).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => warn!("network changed, get wan {:?}", e),
+            }
+        }
+        last = current;
+    }
+}
+
+/// 有默认路由且与上次不同时返回(原默认路由网卡, 当前默认路由网卡).
+/// 没有默认路由时只记录, 恢复后再处理
+fn change(last: &Option<Snapshot>, current: &Option<Snapshot>) -> Option<(Option<String>, String)> {
+    let current = current.as_ref()?;
+    if last.as_ref() == Some(current) {
+        return None;
+    }
+    Some((last.as_ref().map(|last|last.dev.clone()), current.dev.clone()))
+}
+
+fn snapshot() -> Option<Snapshot> {
+    let route = get_default_route().ok()?;
+    let addrs = dev_addrs(&route.dev);
+    Some(Snapshot {
+        dev:    route.dev,
+        gw:     route.gw,
+        addrs,
+    })
+}
+
+#[cfg(unix)]
+fn dev_addrs(dev: &str) -> Vec<IpAddr> {
+    use std::ffi::CStr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let mut addrs = vec![];
+    let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    unsafe {
+        if libc::getifaddrs(&mut ifaddrs) != 0 {
+            return addrs;
+        }
+        let mut cursor = ifaddrs;
+        while !cursor.is_null() {
+            let ifaddr = &*cursor;
+            cursor = ifaddr.ifa_next;
+            if ifaddr.ifa_addr.is_null() || CStr::from_ptr(ifaddr.ifa_name).to_bytes() != dev.as_bytes() {
+                continue;
+            }
+            match (*ifaddr.ifa_addr).sa_family as i32 {
+                libc::AF_INET => {
+                    let addr = &*(ifaddr.ifa_addr as *const libc::sockaddr_in);
+                    addrs.push(IpAddr::from(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))));
+                }
+                libc::AF_INET6 => {
+                    let addr = &*(ifaddr.ifa_addr as *const libc::sockaddr_in6);
+                    addrs.push(IpAddr::from(Ipv6Addr::from(addr.sin6_addr.s6_addr)));
+                }
+                _ => (),
+            }
+        }
+        libc::freeifaddrs(ifaddrs);
+    }
+    addrs.sort();
+    addrs
+}
+
+#[cfg(not(unix))]
+fn dev_addrs(_dev: &str) -> Vec<IpAddr> {
+    vec![]
+}
+
+#[cfg(target_os = "linux")]
+mod netlink {
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::RawFd;
+
+    const RTMGRP_LINK: u32          = 0x1;
+    const RTMGRP_IPV4_IFADDR: u32   = 0x10;
+    const RTMGRP_IPV4_ROUTE: u32    = 0x40;
+    const RTMGRP_IPV6_IFADDR: u32   = 0x100;
+    const RTMGRP_IPV6_ROUTE: u32    = 0x400;
+
+    pub struct Socket(RawFd);
+
+    impl Socket {
+        pub fn open() -> io::Result<Self> {
+            unsafe {
+                let fd = libc::socket(
+                    libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let socket = Socket(fd);
+
+                let mut addr: libc::sockaddr_nl = mem::zeroed();
+                addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+                addr.nl_groups = RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV4_ROUTE
+                    | RTMGRP_IPV6_IFADDR | RTMGRP_IPV6_ROUTE;
+                let res = libc::bind(
+                    fd,
+                    &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                );
+                if res < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(socket)
+            }
+        }
+
+        /// 等待事件, 之后直到debounce_millis内没有新事件才返回. 只关心是否有事件, 不解析内容.
+        pub fn wait(&self, debounce_millis: u64) -> io::Result<()> {
+            self.poll_and_drain(-1)?;
+            while self.poll_and_drain(debounce_millis as i32)? {}
+            Ok(())
+        }
+
+        fn poll_and_drain(&self, timeout_millis: i32) -> io::Result<bool> {
+            let mut fds = libc::pollfd {
+                fd:         self.0,
+                events:     libc::POLLIN,
+                revents:    0,
+            };
+            let res = unsafe { libc::poll(&mut fds, 1, timeout_millis) };
+            if res < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    return Ok(true);
+                }
+                return Err(e);
+            }
+            if res == 0 {
+                return Ok(false);
+            }
+
+            let mut buf = [0u8; 8192];
+            loop {
+                let len = unsafe {
+                    libc::recv(self.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), libc::MSG_DONTWAIT)
+                };
+                if len < 0 {
+                    let e = io::Error::last_os_error();
+                    match e.kind() {
+                        io::ErrorKind::WouldBlock => return Ok(true),
+                        io::ErrorKind::Interrupted => continue,
+                        // 事件太多溢出, 之后仍然会检查一次
+                        _ if e.raw_os_error() == Some(libc::ENOBUFS) => return Ok(true),
+                        _ => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for Socket {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot(dev: &str, gw: &str, addrs: &[&str]) -> Option<Snapshot> {
+        Some(Snapshot {
+            dev:    dev.to_string(),
+            gw:     gw.to_string(),
+            addrs:  addrs.iter().map(|addr|addr.parse().unwrap()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_change() {
+        let wired = snapshot("eth0", "192.168.1.1", &["192.168.1.2"]);
+        assert_eq!(change(&wired, &wired), None);
+        assert_eq!(change(&wired, &snapshot("wlan0", "192.168.2.1", &["192.168.2.2"])),
+                   Some((Some("eth0".to_string()), "wlan0".to_string())));
+        // 同一网卡换了网关或地址
+        assert_eq!(change(&wired, &snapshot("eth0", "192.168.1.254", &["192.168.1.2"])),
+                   Some((Some("eth0".to_string()), "eth0".to_string())));
+        assert_eq!(change(&wired, &snapshot("eth0", "192.168.1.1", &["192.168.1.2", "fd00::2"])),
+                   Some((Some("eth0".to_string()), "eth0".to_string())));
+        // 默认路由消失时不处理, 恢复后处理
+        assert_eq!(change(&wired, &None), None);
+        assert_eq!(change(&None, &wired), Some((None, "eth0".to_string())));
+        assert_eq!(change(&None, &None), None);
+    }
+}
@@ -8,6 +8,7 @@ use crate::daemon::{DaemonEvent, TunnelCommand};
 use crate::traits::RpcTrait;
 use crate::rpc::rpc_cmd::{RpcEvent, RpcClientCmd, ExecutorEvent};
 use crate::settings::default_settings::HEARTBEAT_FREQUENCY_SEC;
+use crate::tinc_manager::key_rotation;
 use super::RpcClient;
 use super::rpc_client;
 use super::error::{Error as ClientError, Result};
@@ -404,6 +405,8 @@ impl Executor {
                         error!("exec_online_proxy failed.");
                         self.status = ExecutorStatus::Failed;
                     }
+
+                    self.exec_key_rotation();
                 }
 
                 if start - fresh_team > Duration::from_secs(5) {
@@ -487,6 +490,17 @@ impl Executor {
         return Err(ClientError::RpcTimeout);
     }
 
+    fn exec_key_rotation(&self) {
+        match key_rotation::rotate_key_if_expired(|| self.client.device_add()) {
+            Ok(true) => {
+                let _ = self.rpc_tx.send(RpcEvent::Executor(
+                    ExecutorEvent::NeedRestartTunnel));
+            }
+            Ok(false) => (),
+            Err(e) => error!("exec_key_rotation {:?}", e),
+        }
+    }
+
     #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
     fn init(&self) -> std::result::Result<(), Error> {
         info!("client_login");
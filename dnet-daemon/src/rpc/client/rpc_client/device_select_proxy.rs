@@ -21,6 +21,7 @@ pub(super) fn device_select_proxy() -> Result<()> {
         proxy_ids.push(connect_to.id.to_owned());
     }
     let pubkey = info.tinc_info.pub_key.clone();
+    let ed25519_pubkey = info.tinc_info.ed25519_pub_key.clone();
 
     std::mem::drop(info);
 
@@ -28,6 +29,7 @@ pub(super) fn device_select_proxy() -> Result<()> {
         "deviceSerial":         device_name,
         "proxyIds":              proxy_ids,
         "pubKey":               pubkey,
+        "ed25519PubKey":        ed25519_pubkey,
     }).to_string();
 
     info!("request {}", data);
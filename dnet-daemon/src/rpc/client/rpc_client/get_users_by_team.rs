@@ -1,29 +1,15 @@
 use crate::info::UserInfo;
 use crate::settings::get_settings;
 use crate::rpc::Result;
-use crate::rpc::http_request::{get, MAX_PAGE, PAGESIZE, get_records};
+use crate::rpc::http_request::get_mutipage;
 use serde_json::Value;
 
 // if return true restart tunnel.
 pub(super) fn get_users_by_team(teamid: &str) -> Result<Vec<UserInfo>> {
-    let mut url = get_settings().common.conductor_url.clone()
+    let url = get_settings().common.conductor_url.clone()
         + "/vlan/team/user/queryAll?teamId=" + teamid;
 
-    let mut user_infos: Vec<UserInfo> = vec![];
-
-    for i in 0..MAX_PAGE {
-        url = url + &format!("&pageNum={}&pageSize={}", i, PAGESIZE);
-        let recv = get(&url)?;
-        let recv = get_records(&url, recv)?;
-
-        if recv.len() < PAGESIZE {
-            user_infos.append(&mut parse_to_user(recv));
-            break
-        }
-        else {
-            user_infos.append(&mut parse_to_user(recv));
-        }
-    }
+    let user_infos = parse_to_user(get_mutipage(&url)?);
 
     info!("{:?}", user_infos);
     return Ok(user_infos);
@@ -9,6 +9,7 @@ mod join_team;
 mod out_team;
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
 mod search_team_by_user;
+mod proxy_score;
 mod select_proxy;
 mod types;
 mod search_team_by_mac;
@@ -16,10 +17,9 @@ mod search_team_by_mac;
 #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
 extern crate router_plugin;
 
-use tinc_plugin::ConnectTo;
 use crate::info::UserInfo;
 use crate::rpc::common::login::login;
-use crate::rpc::common::get_online_proxy;
+use crate::rpc::common::get_online_proxy::{self, ProxyCandidate};
 
 use crate::rpc::Result;
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
@@ -42,8 +42,8 @@ impl RpcClient {
         device_add::device_add()
     }
 
-    pub fn client_get_online_proxy(&self) -> Result<Vec<ConnectTo>> {
-        get_online_proxy::get_online_proxy()
+    pub fn client_get_online_proxy(&self) -> Result<Vec<ProxyCandidate>> {
+        get_online_proxy::get_online_proxy_candidates()
     }
 
     pub fn device_select_proxy(&self) -> Result<()> {
@@ -0,0 +1,345 @@
+//! proxy评分, 分数越小越优先.
+//! weighted: rtt_weight * rtt(ms) + loss_weight * 丢包(%) + load_weight * 负载(%)
+//!     + 超载惩罚 + 不在preferred_regions的region_penalty - 当前proxy的stickiness
+//! rtt: 兼容旧逻辑, 只比较rtt, 当前proxy rtt超过100ms且其他proxy rtt不到其一半时才切换
+
+use std::net::IpAddr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tinc_plugin::ConnectTo;
+use dnet_types::status::ProxyScore;
+
+use crate::rpc::common::get_online_proxy::ProxyCandidate;
+use crate::settings::ProxySelection;
+
+/// 负载达到capacity的proxy只在没有其他可选时使用
+const OVERLOAD_PENALTY: f64 = 10000.0;
+const RTT_SWITCH_THRESHOLD_MS: f64 = 100.0;
+const PROBE_THREADS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub rtt_ms:     Option<f64>,
+    /// 0-1
+    pub loss:       f64,
+}
+
+pub struct Metrics<'a> {
+    pub candidate:  &'a ProxyCandidate,
+    pub probe:      &'a Probe,
+    pub load:       Option<f64>,
+    pub current:    bool,
+}
+
+pub trait ScorePolicy {
+    /// 返回None表示proxy不可达
+    fn score(&self, metrics: &Metrics) -> Option<f64>;
+}
+
+pub struct WeightedPolicy<'a> {
+    settings:   &'a ProxySelection,
+}
+
+impl<'a> ScorePolicy for WeightedPolicy<'a> {
+    fn score(&self, metrics: &Metrics) -> Option<f64> {
+        let rtt = metrics.probe.rtt_ms?;
+        let mut score = self.settings.rtt_weight * rtt
+            + self.settings.loss_weight * metrics.probe.loss * 100.0;
+        if let Some(load) = metrics.load {
+            score += self.settings.load_weight * load * 100.0;
+            if load >= 1.0 {
+                score += OVERLOAD_PENALTY;
+            }
+        }
+        if !self.settings.preferred_regions.is_empty()
+            && !metrics.candidate.in_regions(&self.settings.preferred_regions) {
+            score += self.settings.region_penalty;
+        }
+        if metrics.current {
+            score -= self.settings.stickiness;
+        }
+        Some(score)
+    }
+}
+
+pub struct RttPolicy;
+
+impl ScorePolicy for RttPolicy {
+    fn score(&self, metrics: &Metrics) -> Option<f64> {
+        let rtt = metrics.probe.rtt_ms?;
+        if !metrics.current {
+            return Some(rtt);
+        }
+        if rtt > RTT_SWITCH_THRESHOLD_MS {
+            Some(rtt / 2.0)
+        }
+        else {
+            Some(0.0)
+        }
+    }
+}
+
+pub fn policy(settings: &ProxySelection) -> Box<dyn ScorePolicy + '_> {
+    match settings.policy.as_str() {
+        "rtt" => Box::new(RttPolicy),
+        _ => Box::new(WeightedPolicy { settings }),
+    }
+}
+
+/// 按分数升序排列, 不可达的排在最后; 分数相同时当前proxy优先
+pub fn rank(
+    candidates: &[ProxyCandidate],
+    probes:     &[Probe],
+    current:    &[ConnectTo],
+    settings:   &ProxySelection,
+) -> Vec<(ConnectTo, ProxyScore)> {
+    let policy = policy(settings);
+    let mut scores: Vec<(ConnectTo, ProxyScore, bool)> = candidates.iter()
+        .zip(probes)
+        .map(|(candidate, probe)| {
+            let is_current = current.iter().any(|proxy|proxy.vip == candidate.connect_to.vip);
+            let metrics = Metrics {
+                candidate,
+                probe,
+                load: candidate.load(settings.default_capacity),
+                current: is_current,
+            };
+            let score = ProxyScore {
+                id:         candidate.connect_to.id.clone(),
+                vip:        candidate.connect_to.vip,
+                rtt_ms:     probe.rtt_ms.map(|rtt|rtt.round() as u32),
+                loss:       (probe.loss * 100.0).round() as u32,
+                load:       metrics.load.map(|load|(load * 100.0).round() as u32),
+                region:     candidate.region.clone(),
+                score:      policy.score(&metrics).map(|score|score.round() as i64),
+                selected:   false,
+            };
+            (candidate.connect_to.clone(), score, is_current)
+        })
+        .collect();
+
+    scores.sort_by(|(_, a, a_current), (_, b, b_current)| {
+        match (a.score, b.score) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+            .then(b_current.cmp(a_current))
+    });
+    scores.into_iter().map(|(connect_to, score, _)|(connect_to, score)).collect()
+}
+
+/// 并行探测所有proxy, 最多PROBE_THREADS个线程, 空闲的线程依次取下一个proxy.
+/// 每个proxy有count + 1秒的探测时间, 与排在它之前的proxy是否可达无关;
+/// 超过全部轮次的时间仍未完成的proxy视为不可达. 返回结果与addrs顺序一致.
+pub fn probe_all(addrs: &[IpAddr], count: u32) -> Vec<Probe> {
+    let budget = Duration::from_secs(count.max(1) as u64 + 1);
+    probe_all_with(addrs, PROBE_THREADS, budget, move |addr, deadline|probe(addr, count, deadline))
+}
+
+fn probe_all_with<F>(addrs: &[IpAddr], threads: usize, budget: Duration, probe: F) -> Vec<Probe>
+    where F: Fn(IpAddr, Instant) -> Probe + Send + Sync + 'static
+{
+    let mut probes = vec![Probe { rtt_ms: None, loss: 1.0 }; addrs.len()];
+    if addrs.is_empty() {
+        return probes;
+    }
+    let workers = addrs.len().min(threads.max(1));
+    let rounds = (addrs.len() + workers - 1) / workers;
+    let deadline = Instant::now() + budget * rounds as u32;
+
+    let probe = Arc::new(probe);
+    let queue = Arc::new(Mutex::new(addrs.iter().cloned().enumerate().collect::<Vec<_>>().into_iter()));
+    let (probe_tx, probe_rx) = mpsc::channel();
+    for _ in 0..workers {
+        let probe = probe.clone();
+        let queue = queue.clone();
+        let probe_tx = probe_tx.clone();
+        let _ = thread::Builder::new()
+            .name("proxy_probe".to_string())
+            .spawn(move || {
+                let next = || queue.lock().unwrap().next();
+                while let Some((index, addr)) = next() {
+                    if probe_tx.send((index, probe(addr, Instant::now() + budget))).is_err() {
+                        return;
+                    }
+                }
+            })
+            .map_err(|e|error!("start proxy probe {:?}", e));
+    }
+    drop(probe_tx);
+
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            warn!("proxy probe timeout");
+            break;
+        }
+        match probe_rx.recv_timeout(deadline - now) {
+            Ok((index, probe)) => probes[index] = probe,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                warn!("proxy probe timeout");
+                break;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    probes
+}
+
+/// ping count次, 统计平均rtt和丢包率. 超过deadline后不再ping, 未完成的次数计为丢包
+fn probe(addr: IpAddr, count: u32, deadline: Instant) -> Probe {
+    let mut rtts = vec![];
+    for seq in 1..=count.max(1) {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let timeout = Some((deadline - now).min(Duration::from_secs(1)));
+        if let Ok(rtt) = pinger::ping(addr, timeout, None, None, Some(seq as u16), None) {
+            rtts.push(rtt as f64 / 1000.0);
+        }
+    }
+    let count = count.max(1) as f64;
+    Probe {
+        rtt_ms: match rtts.len() {
+            0 => None,
+            n => Some(rtts.iter().sum::<f64>() / n as f64),
+        },
+        loss:   1.0 - rtts.len() as f64 / count,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn candidate(vip: &str, connections: u32, region: &str) -> ProxyCandidate {
+        let vip = vip.parse().unwrap();
+        ProxyCandidate {
+            connect_to:     ConnectTo::from(vip.to_string(), vip, vip, 50069, String::new()),
+            connections:    Some(connections),
+            capacity:       Some(100),
+            region:         Some(region.to_owned()),
+            country:        None,
+            city:           None,
+            draining:       false,
+        }
+    }
+
+    fn settings(policy: &str) -> ProxySelection {
+        ProxySelection {
+            policy:             policy.to_owned(),
+            probe_count:        3,
+            rtt_weight:         1.0,
+            loss_weight:        5.0,
+            load_weight:        1.0,
+            region_penalty:     100.0,
+            preferred_regions:  vec![],
+            stickiness:         30.0,
+            default_capacity:   500,
+        }
+    }
+
+    fn reachable(rtt_ms: f64, loss: f64) -> Probe {
+        Probe { rtt_ms: Some(rtt_ms), loss }
+    }
+
+    #[test]
+    fn test_rank_weighted() {
+        let candidates = vec![
+            candidate("10.253.0.1", 95, "hk"),
+            candidate("10.253.0.2", 10, "sh"),
+            candidate("10.253.0.3", 0, "bj"),
+        ];
+        let probes = vec![reachable(20.0, 0.0), reachable(40.0, 0.0), Probe { rtt_ms: None, loss: 1.0 }];
+
+        // 负载高的proxy即使rtt低也排在后面, 不可达的排在最后
+        let ranked = rank(&candidates, &probes, &[], &settings("weighted"));
+        let vips: Vec<String> = ranked.iter().map(|(proxy, _)|proxy.vip.to_string()).collect();
+        assert_eq!(vips, vec!["10.253.0.2", "10.253.0.1", "10.253.0.3"]);
+        assert_eq!(ranked[0].1.score, Some(50));
+        assert_eq!(ranked[2].1.score, None);
+
+        // 区域偏好
+        let mut settings = settings("weighted");
+        settings.preferred_regions = vec!["HK".to_owned()];
+        settings.load_weight = 0.0;
+        let ranked = rank(&candidates, &probes, &[], &settings);
+        assert_eq!(ranked[0].0.vip.to_string(), "10.253.0.1");
+
+        // 当前proxy差距在stickiness内时不切换
+        let current = vec![candidates[1].connect_to.clone()];
+        let probes = vec![reachable(20.0, 0.0), reachable(40.0, 0.0), reachable(30.0, 0.0)];
+        let mut settings = settings.clone();
+        settings.preferred_regions = vec![];
+        let ranked = rank(&candidates, &probes, &current, &settings);
+        assert_eq!(ranked[0].0.vip.to_string(), "10.253.0.2");
+    }
+
+    #[test]
+    fn test_rank_rtt() {
+        let candidates = vec![
+            candidate("10.253.0.1", 0, "hk"),
+            candidate("10.253.0.2", 0, "sh"),
+        ];
+        let current = vec![candidates[1].connect_to.clone()];
+
+        let ranked = rank(&candidates, &[reachable(20.0, 0.0), reachable(90.0, 0.0)], &current, &settings("rtt"));
+        assert_eq!(ranked[0].0.vip.to_string(), "10.253.0.2");
+
+        let ranked = rank(&candidates, &[reachable(20.0, 0.0), reachable(150.0, 0.0)], &current, &settings("rtt"));
+        assert_eq!(ranked[0].0.vip.to_string(), "10.253.0.1");
+    }
+
+    #[test]
+    fn test_probe_all() {
+        let addrs: Vec<IpAddr> = (1..=6).map(|i|format!("10.253.0.{}", i).parse().unwrap()).collect();
+        let budget = Duration::from_millis(300);
+        let start = Instant::now();
+        // 2个线程探测6个proxy, 10.253.0.1不响应, 用完自己的探测时间;
+        // 之后的proxy仍有完整的探测时间
+        let probes = probe_all_with(&addrs, 2, budget, move |addr, deadline| {
+            assert!(deadline - Instant::now() > budget - Duration::from_millis(50));
+            match addr {
+                IpAddr::V4(ip) if ip.octets()[3] == 1 => {
+                    thread::sleep(deadline - Instant::now());
+                    Probe { rtt_ms: None, loss: 1.0 }
+                }
+                IpAddr::V4(ip) => {
+                    thread::sleep(Duration::from_millis(100));
+                    reachable(ip.octets()[3] as f64, 0.0)
+                }
+                _ => unreachable!(),
+            }
+        });
+        assert!(start.elapsed() < budget * 3);
+        assert_eq!(probes.len(), 6);
+        assert_eq!(probes[0], Probe { rtt_ms: None, loss: 1.0 });
+        for (index, probe) in probes.iter().enumerate().skip(1) {
+            assert_eq!(probe.rtt_ms, Some(index as f64 + 1.0));
+        }
+    }
+
+    #[test]
+    fn test_probe_all_timeout() {
+        let addrs: Vec<IpAddr> = (1..=3).map(|i|format!("10.253.0.{}", i).parse().unwrap()).collect();
+        let start = Instant::now();
+        // 超过探测时间仍未返回的proxy视为不可达
+        let probes = probe_all_with(&addrs, 16, Duration::from_millis(300), |addr, _deadline| {
+            match addr {
+                IpAddr::V4(ip) if ip.octets()[3] == 1 => {
+                    thread::sleep(Duration::from_millis(1000));
+                    reachable(1.0, 0.0)
+                }
+                _ => reachable(2.0, 0.0),
+            }
+        });
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert_eq!(probes[0], Probe { rtt_ms: None, loss: 1.0 });
+        assert_eq!(probes[1], reachable(2.0, 0.0));
+    }
+}
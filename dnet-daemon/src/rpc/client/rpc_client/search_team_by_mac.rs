@@ -5,11 +5,13 @@ use sandbox::route;
 
 use crate::settings::get_settings;
 use crate::rpc::{Result, Error};
-use crate::info::{get_info, get_mut_info};
+use crate::info::{get_info, get_mut_info, state};
 use super::types::ResponseTeam;
 use crate::settings::default_settings::TINC_INTERFACE;
-use crate::rpc::http_request::{get, MAX_PAGE, PAGESIZE, get_records};
-use dnet_types::team::Team;
+use crate::rpc::http_request::get_pages;
+use crate::hooks::{self, HookEvent};
+use crate::tinc_manager::team_firewall;
+use dnet_types::team::{Team, TeamEvent, diff_teams, merge_teams, latest_update_time};
 
 pub fn search_team_by_mac() -> Result<()> {
     let info = get_info().lock().unwrap();
@@ -18,37 +20,52 @@ pub fn search_team_by_mac() -> Result<()> {
     let url = get_settings().common.conductor_url.clone()
         + "/vlan/team/queryByDeviceSerial?deviceSerial=" + &device_id;
 
-    search_team_inner(url, true)?;
+    search_team_inner(url)?;
 
     Ok(())
 }
 
-pub fn search_team_inner(mut url: String, has_other_param: bool) -> Result<()> {
-    let mut teams_vec: Vec<Team> = vec![];
-    for i in 1..MAX_PAGE {
-        if has_other_param {
-            url = url + &format!("&pageNum={}&pageSize={}", i, PAGESIZE);
-        }
-        else {
-            url = url + &format!("?pageNum={}&pageSize={}", i, PAGESIZE);
-        }
-        let recv = get(&url)?;
-        let recv = get_records(&url, recv)?;
-
-        if recv.len() < PAGESIZE {
-            teams_vec.append(&mut parse_to_team(recv)?);
-            break
-        } else {
-            teams_vec.append(&mut parse_to_team(recv)?);
+/// 同步team列表. 本地已有team时携带最新的updateTime请求增量,
+/// conductor返回incremental时只替换有变化的team并删除deletedTeamIds,
+/// 否则视为完整快照. 两种情况都与本地比较得到TeamEvent.
+pub fn search_team_inner(url: String) -> Result<Vec<TeamEvent>> {
+    let old_teams = get_info().lock().unwrap().teams.all_teams.clone();
+
+    let request_url = match latest_update_time(&old_teams) {
+        Some(update_time) => {
+            let update_time = url::form_urlencoded::byte_serialize(update_time.as_bytes())
+                .collect::<String>();
+            let separator = if url.contains("?") { "&" } else { "?" };
+            format!("{}{}updateTime={}", url, separator, update_time)
         }
-    }
+        None => url.clone(),
+    };
+    let pages = get_pages(&request_url)?;
+    let teams_vec = parse_to_team(pages.records)?;
 
     log_of_team(&url, &teams_vec);
 
-    let mut teams = HashMap::new();
-    for team in teams_vec {
-        teams.insert(team.team_id.clone(), team);
+    let incremental = pages.head.get("incremental")
+        .and_then(|incremental|incremental.as_bool())
+        .unwrap_or(false);
+    let teams = if incremental {
+        let deleted = pages.head.get("deletedTeamIds")
+            .and_then(|deleted|deleted.as_array())
+            .map(|deleted|deleted.iter()
+                .filter_map(|team_id|team_id.as_str())
+                .map(|team_id|team_id.to_string())
+                .collect::<Vec<String>>())
+            .unwrap_or(vec![]);
+        merge_teams(&old_teams, teams_vec, &deleted)
     }
+    else {
+        teams_vec.into_iter()
+            .map(|team|(team.team_id.clone(), team))
+            .collect()
+    };
+
+    let events = diff_teams(&old_teams, &teams);
+    info!("team sync incremental: {} events: {:?}", incremental, events);
 
     let mut info = get_mut_info().lock().unwrap();
     info.teams.all_teams = teams;
@@ -56,6 +73,13 @@ pub fn search_team_inner(mut url: String, has_other_param: bool) -> Result<()> {
     let hosts = info.teams.get_connect_hosts(info.client_info.wan.clone(), &info.tinc_info.vip);
     let local_vip = info.tinc_info.vip.clone();
     std::mem::drop(info);
+    state::save_state();
+
+    team_firewall::refresh();
+
+    if !events.is_empty() {
+        hooks::emit(HookEvent::TeamChange { changes: events.clone() });
+    }
 
     info!("route hosts {:?}", hosts);
     let _ = std::thread::Builder::new()
@@ -63,7 +87,7 @@ pub fn search_team_inner(mut url: String, has_other_param: bool) -> Result<()> {
         .spawn(move ||
             route::keep_route(local_vip, hosts, TINC_INTERFACE.to_string())
         );
-    Ok(())
+    Ok(events)
 }
 
 fn log_of_team(url: &str, teams_vec: &Vec<Team>) {
@@ -1,95 +1,65 @@
 use std::net::IpAddr;
-use std::time::Duration;
-use std::collections::HashMap;
-
-extern crate pinger;
 
 use tinc_plugin::ConnectTo;
+use dnet_types::status::ProxyScore;
 
-use crate::info::{get_mut_info, get_info};
+use crate::info::{get_mut_info, get_info, state};
 use crate::rpc::{Error, Result};
+use crate::rpc::common::get_online_proxy::ProxyCandidate;
+use crate::settings::get_settings;
+use crate::hooks::{self, HookEvent};
+use crate::tinc_manager::team_firewall;
+use super::proxy_score;
 
-pub fn select_proxy(connect_to_vec: Vec<ConnectTo>) -> Result<bool> {
-    let mut connect_to_change_restart_tunnel = false;
-
-    let mut proxy_rtt = HashMap::new();
-    for proxy in &connect_to_vec {
-        if let Some(rtt) = ping(proxy.ip) {
-            proxy_rtt.insert(proxy.clone(), rtt);
-        }
+/// 按proxy_selection策略为在线proxy评分, 选择分数最小的proxy, 都不可达时使用第一个.
+/// draining的proxy只在没有其他proxy时使用.
+/// 返回选择的proxy是否改变, 需要重启隧道.
+pub fn select_proxy(candidates: Vec<ProxyCandidate>) -> Result<bool> {
+    if candidates.is_empty() {
+        return Err(Error::http(511));
     }
-
-    let info = get_info().lock().unwrap();
-    let local_connect_to_vec = info.tinc_info.connect_to.clone();
-    std::mem::drop(info);
-
-    let mut connect_to = vec![];
-    if local_connect_to_vec.len() == 0 {
-        let (connect_to_tmp, _) = select_min_rtt_proxys(proxy_rtt);
-        connect_to = connect_to_tmp;
-        connect_to_change_restart_tunnel = true;
+    let candidates: Vec<ProxyCandidate> = if candidates.iter().any(|candidate|!candidate.draining) {
+        candidates.into_iter().filter(|candidate|!candidate.draining).collect()
     }
     else {
-        let mut need_change_proxy = false;
-        let (min_rtt_proxy, min_rtt) = select_min_rtt_proxys(proxy_rtt.clone());
-        for local_connect_to in local_connect_to_vec {
-            // proxy offline
-            if let Some(rtt) = proxy_rtt.get(&local_connect_to).cloned() {
-                // if (min_rtt as f64 / rtt as f64) < 0.7 && rtt > 100
-                //     Bad proxy network need change proxy.
-                if rtt != 0 {
-                    if (min_rtt as f64 / rtt as f64) < 0.5 && rtt > 100000 {
-                        need_change_proxy = true;
-                    }
-                }
-            }
-        }
-        if need_change_proxy {
-            connect_to = min_rtt_proxy;
-            connect_to_change_restart_tunnel = true;
-        }
-    }
+        candidates
+    };
 
-    if connect_to.len() == 0 && connect_to_vec.len() > 0 {
-        let proxy = connect_to_vec[0].to_owned();
-        connect_to = vec![proxy];
-    }
+    let settings = &get_settings().proxy_selection;
+    let addrs: Vec<IpAddr> = candidates.iter().map(|candidate|candidate.connect_to.ip).collect();
+    let probes = proxy_score::probe_all(&addrs, settings.probe_count);
 
-    if connect_to.len() == 0 {
-        return Err(Error::http(511));
-    }
+    let local_connect_to_vec = get_info().lock().unwrap().tinc_info.connect_to.clone();
+    let mut ranked = proxy_score::rank(&candidates, &probes, &local_connect_to_vec, settings);
 
-    let mut info = get_mut_info().lock().unwrap();
-    info.tinc_info.connect_to = connect_to;
-    std::mem::drop(info);
+    // Now, connect to only one proxy.
+    let connect_to = match ranked.first_mut() {
+        Some((proxy, score)) if score.score.is_some() => {
+            score.selected = true;
+            vec![proxy.clone()]
+        }
+        _ => vec![candidates[0].connect_to.clone()],
+    };
+    let scores: Vec<ProxyScore> = ranked.into_iter().map(|(_, score)|score).collect();
+    debug!("proxy scores {:?}", scores);
 
-    Ok(connect_to_change_restart_tunnel)
-}
+    let connect_to_change_restart_tunnel = connect_to != local_connect_to_vec;
 
-// Now, connect to only one proxy.
-fn select_min_rtt_proxys(proxy_rtt: HashMap<ConnectTo, u128>) -> (Vec<ConnectTo>, u128) {
-    let mut min_rtt = 0;
-    let mut connect_to = vec![];
+    let mut info = get_mut_info().lock().unwrap();
+    info.status.proxy_scores = scores;
+    let old = std::mem::replace(&mut info.tinc_info.connect_to, connect_to);
+    let new = info.tinc_info.connect_to.clone();
+    std::mem::drop(info);
+    state::save_state();
 
-    for (proxy, rtt) in proxy_rtt {
-        if min_rtt == 0 || min_rtt > rtt {
-            min_rtt = rtt;
-            connect_to = vec!(proxy.clone());
-        }
+    if old != new {
+        // DNET_TEAM只接受当前proxy的vip
+        team_firewall::refresh();
+        let to_vips = |connect_to: Vec<ConnectTo>| connect_to.into_iter()
+            .map(|proxy| proxy.vip.to_string())
+            .collect::<Vec<String>>();
+        hooks::emit(HookEvent::ProxySwitch { old: to_vips(old), new: to_vips(new) });
     }
-    (connect_to, min_rtt)
-}
 
-pub fn ping(addr: IpAddr) -> Option<u128> {
-    let timeout = Some(Duration::from_secs(1));
-    let ttl = None;
-    let ident = None;
-    let seq_cnt = Some(1);
-    let payload = None;
-    if let Ok(rtt) = pinger::ping(addr, timeout, ttl, ident, seq_cnt, payload) {
-        return Some(rtt);
-    }
-    else {
-        return None;
-    }
-}
\ No newline at end of file
+    Ok(connect_to_change_restart_tunnel)
+}
@@ -158,6 +158,7 @@ pub struct JavaDevice {
     pub latitude:                   Option<String>,
     pub longitude:                  Option<String>,
     pub pubKey:                     String,
+    pub ed25519PubKey:              Option<String>,
     pub region:                     Option<String>,
     pub tincStatus:                 i8,
     pub updateBy:                   Option<String>,
@@ -175,6 +176,7 @@ impl JavaDevice {
         let device_name = info.client_info.device_name.clone();
         let device_type = info.client_info.devicetype.clone() as i8;
         let pubkey = info.tinc_info.pub_key.clone();
+        let ed25519_pubkey = info.tinc_info.ed25519_pub_key.clone();
         let lan = info.client_info.get_lan_str();
         std::mem::drop(info);
 
@@ -197,6 +199,7 @@ impl JavaDevice {
             latitude:                   None,
             longitude:                  None,
             pubKey:                     pubkey,
+            ed25519PubKey:              ed25519_pubkey,
             region:                     None,
             tincStatus:                 0,
             updateBy:                   None,
@@ -9,7 +9,45 @@ use crate::info::get_info;
 use crate::rpc::{Error, Result};
 use crate::rpc::http_request::get_mutipage;
 
+/// 在线proxy及其上报的负载和位置, 用于客户端评分
+#[derive(Clone, Debug)]
+pub struct ProxyCandidate {
+    pub connect_to:     ConnectTo,
+    pub connections:    Option<u32>,
+    pub capacity:       Option<u32>,
+    /// region, 未上报时为country
+    pub region:         Option<String>,
+    pub country:        Option<String>,
+    pub city:           Option<String>,
+    /// proxy即将停止, 客户端应迁移到其他proxy
+    pub draining:       bool,
+}
+
+impl ProxyCandidate {
+    /// connections / capacity, proxy未上报capacity时使用default_capacity
+    pub fn load(&self, default_capacity: u32) -> Option<f64> {
+        let capacity = self.capacity.filter(|capacity|*capacity > 0).unwrap_or(default_capacity);
+        if capacity == 0 {
+            return None;
+        }
+        self.connections.map(|connections|connections as f64 / capacity as f64)
+    }
+
+    pub fn in_regions(&self, regions: &[String]) -> bool {
+        [&self.region, &self.country, &self.city].iter()
+            .filter_map(|location|location.as_ref())
+            .any(|location|regions.iter().any(|region|region.eq_ignore_ascii_case(location)))
+    }
+}
+
 pub fn get_online_proxy() -> Result<Vec<ConnectTo>> {
+    get_online_proxy_candidates()
+        .map(|candidates| candidates.into_iter()
+            .map(|candidate|candidate.connect_to)
+            .collect())
+}
+
+pub fn get_online_proxy_candidates() -> Result<Vec<ProxyCandidate>> {
     let url = get_settings().common.conductor_url.clone()
         + "/vlan/proxy/queryAllOnline";
     let res = get_mutipage(&url)?;
@@ -25,10 +63,10 @@ pub fn get_online_proxy() -> Result<Vec<ConnectTo>> {
     Ok(connect_to)
 }
 
-fn parse_response(proxy_vec: Vec<GetProxyResponse>) -> Result<Vec<ConnectTo>> {
+fn parse_response(proxy_vec: Vec<GetProxyResponse>) -> Result<Vec<ProxyCandidate>> {
     let local_vip = get_info().lock().unwrap().tinc_info.vip.clone();
 
-    let mut connect_to: Vec<ConnectTo> = vec![];
+    let mut connect_to: Vec<ProxyCandidate> = vec![];
     for proxy in proxy_vec {
         let (proxy_id, proxy_ip, proxy_vip, proxy_port, proxy_pubkey)
             = match get_remote_info(&proxy) {
@@ -37,8 +75,17 @@ fn parse_response(proxy_vec: Vec<GetProxyResponse>) -> Result<Vec<ConnectTo>> {
         };
 
         if local_vip != Some(proxy_vip) {
-            let other = ConnectTo::from(proxy_id, proxy_ip, proxy_vip, proxy_port, proxy_pubkey);
-            connect_to.push(other);
+            let mut other = ConnectTo::from(proxy_id, proxy_ip, proxy_vip, proxy_port, proxy_pubkey);
+            other.ed25519_pubkey = proxy.ed25519Pubkey.clone();
+            connect_to.push(ProxyCandidate {
+                connect_to:     other,
+                connections:    proxy.connections,
+                capacity:       proxy.capacity,
+                region:         proxy.region.clone().or(proxy.country.clone()),
+                country:        proxy.country,
+                city:           proxy.city,
+                draining:       proxy.draining.unwrap_or(false),
+            });
         }
     }
 
@@ -86,17 +133,24 @@ fn get_remote_info(proxy: &GetProxyResponse) -> Option<(String, IpAddr, IpAddr,
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct GetProxyResponse {
     authId:                Option<String>,
+    capacity:              Option<u32>,
     city:                  Option<String>,
     companyId:             Option<String>,
+    connections:           Option<u32>,
     country:               Option<String>,
     createBy:              Option<String>,
     createTime:            Option<String>,
+    draining:              Option<bool>,
+    edges:                 Option<u32>,
     id:                    Option<String>,
     ip:                    Option<String>,
     latitude:              Option<String>,
     longitude:             Option<String>,
+    nodes:                 Option<u32>,
     pubkey:                Option<String>,
+    ed25519Pubkey:         Option<String>,
     publicFlag:            Option<bool>,
+    region:                Option<String>,
     serverPort:            Option<u16>,
     status:                Option<i32>,
     tincPort:              Option<u16>,
@@ -2,19 +2,35 @@ use crate::settings::get_settings;
 use crate::rpc::http_request::post;
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
 use crate::info::UserInfo;
-use crate::info::get_mut_info;
-#[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
-use crate::info::get_info;
+use crate::info::{get_info, get_mut_info, state};
 use crate::rpc::{Error, Result};
 
+/// 保存的token未过期时继续使用, 否则用账号登录
 pub fn login() -> Result<()> {
+    let token = get_info().lock().unwrap().node.token.clone();
+    if token_valid(&token, chrono::Utc::now().timestamp()) {
+        info!("reuse session token");
+        #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+            {
+                let mut info = get_mut_info().lock().unwrap();
+                if info.user.name.is_none() {
+                    info.user.name = Some(get_settings().common.username.clone());
+                }
+            }
+        return Ok(());
+    }
+    relogin()
+}
+
+/// 用账号登录获取新token, token失效(401)时也由http_request调用
+pub fn relogin() -> Result<()> {
     let settings = get_settings();
     #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
         {
             let url = settings.common.conductor_url.clone() + "/vlan/login";
             let data = create_request();
             let res = post(&url, &data)?;
-            info!("result: {:?}", res);
+            info!("login success.");
 
             let token = res.get("token")
                 .and_then(|token| {
@@ -34,13 +50,15 @@ pub fn login() -> Result<()> {
             let mut info = get_mut_info().lock().unwrap();
             info.node.token = token.to_owned();
             info.user = user_info;
+            std::mem::drop(info);
+            state::save_state();
         }
     #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
         {
             let url = settings.common.conductor_url.clone() + "/vlan/router/login";
             let data = create_request();
             let res = post(&url, &data)?;
-            info!("result: {:?}", res);
+            info!("login success.");
 
             let token = res.get("token")
                 .and_then(|token| {
@@ -50,10 +68,28 @@ pub fn login() -> Result<()> {
 
             let mut info = get_mut_info().lock().unwrap();
             info.node.token = token.to_owned();
+            std::mem::drop(info);
+            state::save_state();
         }
     Ok(())
 }
 
+/// JWT token的exp在60秒以后视为有效, 无法解析exp的token不复用
+fn token_valid(token: &str, now: i64) -> bool {
+    token_expiry(token).map(|exp|exp > now + 60).unwrap_or(false)
+}
+
+fn token_expiry(token: &str) -> Option<i64> {
+    let payload = token.split('.').nth(1)?;
+    let mut payload = payload.replace('-', "+").replace('_', "/");
+    while payload.len() % 4 != 0 {
+        payload.push('=');
+    }
+    let payload = openssl::base64::decode_block(&payload).ok()?;
+    let payload: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    payload.get("exp")?.as_i64()
+}
+
 fn create_request() -> String {
     #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
         {
@@ -98,4 +134,14 @@ struct LoginResponse {
     updateBy:    Option<String>,
     updateTime:  Option<String>,
     username:    Option<String>,
-}
\ No newline at end of file
+}
+#[test]
+fn test_token_valid() {
+    // {"alg":"HS256"}.{"exp":1600000000}.sig
+    let token = "eyJhbGciOiJIUzI1NiJ9.eyJleHAiOjE2MDAwMDAwMDB9.sig";
+    assert_eq!(token_expiry(token), Some(1600000000));
+    assert!(token_valid(token, 1500000000));
+    assert!(!token_valid(token, 1600000000 - 30));
+    assert!(!token_valid("opaque-token", 0));
+    assert!(!token_valid("", 0));
+}
@@ -16,6 +16,12 @@ pub enum Error {
 
     #[error(display = "Parse response failed.")]
     ResponseParse(String),
+
+    #[error(display = "Send team info to tinc failed.")]
+    TincTeam(Vec<String>),
+
+    #[error(display = "Paged response truncated after max page.")]
+    Truncated(String),
 }
 
 impl Error {
@@ -5,8 +5,10 @@ use std::thread::sleep;
 use reqwest;
 
 use crate::settings::get_settings;
+use crate::settings::EgressProxy;
 use crate::info::get_info;
 
+use super::common::login;
 use super::error::*;
 
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
@@ -18,8 +20,25 @@ pub const PAGESIZE: usize = 3;
 pub const MAX_PAGE: usize = 50;
 
 pub fn post(url: &str, data: &str) -> Result<serde_json::Value> {
-    let res = url_post(url, data)?;
-    http_error(res)
+    relogin_on_unauthorized(
+        url,
+        url_post(url, data).and_then(http_error),
+        || url_post(url, data).and_then(http_error))
+}
+
+/// token失效(401)时重新登录并用新token重试一次, 登录请求本身除外
+fn relogin_on_unauthorized<F>(url: &str, res: Result<serde_json::Value>, retry: F)
+    -> Result<serde_json::Value>
+    where F: FnOnce() -> Result<serde_json::Value>
+{
+    match res {
+        Err(Error::http(401)) if !url.ends_with("/login") => {
+            warn!("{} unauthorized, login again.", url);
+            login::relogin()?;
+            retry()
+        }
+        res => res,
+    }
 }
 
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
@@ -27,7 +46,10 @@ pub fn loop_post(url: &str, data: &str) -> Result<serde_json::Value> {
     let mut wait_sec = 0;
     loop {
         match url_post(&url, &data) {
-            Ok(res) => return Ok(http_error(res)?),
+            Ok(res) => return relogin_on_unauthorized(
+                url,
+                http_error(res),
+                || url_post(url, data).and_then(http_error)),
             Err(e) => {
                 error!("{:?}", e);
                 sleep(std::time::Duration::from_secs(wait_sec));
@@ -52,46 +74,66 @@ fn url_post(url: &str, data: &str)
     Ok(res)
 }
 
+/// 分页结果, head为第一页除records外的字段(total, incremental等)
+pub struct Pages {
+    pub records:    Vec<serde_json::Value>,
+    pub head:       serde_json::Value,
+}
+
 pub fn get_mutipage(url: &str) -> Result<Vec<serde_json::Value>> {
-    let mut output = vec![];
-    let mut page = 1;
+    Ok(get_pages(url)?.records)
+}
+
+/// 获取全部分页, MAX_PAGE页内没有取完或少于total时返回Error::Truncated, 不返回不完整的结果
+pub fn get_pages(url: &str) -> Result<Pages> {
     let is_have_other_param = url.contains("?");
-    loop {
+    collect_pages(url, |page| {
         let page_url = if is_have_other_param {
             format!("{}&pageNum={}&pageSize={}", url, page, PAGESIZE)
         }
         else {
             format!("{}?pageNum={}&pageSize={}", url, page, PAGESIZE)
         };
-        let res = get(&page_url)?
-            .get("records")
-            .ok_or(Error::ResponseParse(url.to_string() + "Not Found records."))?
-            .to_owned();
-        let mut res = res.as_array()
-            .ok_or(Error::ResponseParse(url.to_string() + " Can not parse to array."))
-            .map_err(|err| {
-                error!("get_mutipage {:?}", err);
-                err
-            })?
-            .to_owned();
-        if res.len() < PAGESIZE {
-            output.append(res.as_mut());
-            break
-        }
-        else if page > 50 {
-            break
+        get(&page_url)
+    })
+}
+
+fn collect_pages<F>(url: &str, mut get_page: F) -> Result<Pages>
+    where F: FnMut(usize) -> Result<serde_json::Value> {
+    let mut records = vec![];
+    let mut head = serde_json::Value::Null;
+    for page in 1..=MAX_PAGE {
+        let res = get_page(page)?;
+        let mut page_records = get_records(url, res.clone())?;
+        let is_last = page_records.len() < PAGESIZE;
+        records.append(&mut page_records);
+        if page == 1 {
+            head = res;
+            if let Some(object) = head.as_object_mut() {
+                object.remove("records");
+            }
         }
-        else {
-            output.append(res.as_mut());
-            page += 1;
+
+        let total = head.get("total").and_then(|total|total.as_u64());
+        if is_last || total.map(|total|records.len() as u64 >= total).unwrap_or(false) {
+            if let Some(total) = total {
+                if (records.len() as u64) < total {
+                    error!("get_pages {} got {} records, total {}", url, records.len(), total);
+                    return Err(Error::Truncated(url.to_string()));
+                }
+            }
+            return Ok(Pages { records, head });
         }
     }
-    Ok(output)
+    error!("get_pages {} truncated at {} pages", url, MAX_PAGE);
+    Err(Error::Truncated(url.to_string()))
 }
 
 pub fn get(url: &str) -> Result<serde_json::Value> {
-    let res = loop_get(url)?;
-    http_error(res)
+    relogin_on_unauthorized(
+        url,
+        loop_get(url).and_then(http_error),
+        || loop_get(url).and_then(http_error))
 }
 
 fn loop_get(url: &str)  -> Result<reqwest::Response> {
@@ -148,9 +190,27 @@ fn http_error(mut res: reqwest::Response) -> Result<serde_json::Value> {
     }
 }
 
+/// 配置了[common.egress_proxy]时, 不在no_proxy中的请求通过代理
+pub fn with_egress_proxy(client_build: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    match &get_settings().common.egress_proxy {
+        Some(egress_proxy) => client_build.proxy(egress_proxy_scheme(egress_proxy.clone())),
+        None => client_build,
+    }
+}
+
+fn egress_proxy_scheme(egress_proxy: EgressProxy) -> reqwest::Proxy {
+    reqwest::Proxy::custom(move |url| {
+        let host = url.host_str()?;
+        if egress_proxy.bypass(host) {
+            return None;
+        }
+        Some(egress_proxy.url())
+    })
+}
+
 fn build_request_client(method: reqwest::Method, url: &str) -> Result<reqwest::RequestBuilder> {
     let token = get_info().lock().unwrap().node.token.clone();
-    let client_build = reqwest::ClientBuilder::new()
+    let client_build = with_egress_proxy(reqwest::ClientBuilder::new())
         .timeout(Duration::from_secs(get_settings().common.http_timeout as u64))
         .http1_title_case_headers()
         .gzip(false);
@@ -194,4 +254,59 @@ pub fn get_records(url: &str, res: serde_json::Value) -> Result<Vec<serde_json::
         })?
         .to_owned();
     Ok(out)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+    use super::{collect_pages, Error, PAGESIZE, MAX_PAGE};
+
+    fn page(first: usize, count: usize, total: Option<usize>) -> serde_json::Value {
+        let records = (first..first + count).collect::<Vec<usize>>();
+        match total {
+            Some(total) => json!({ "records": records, "total": total, "incremental": true }),
+            None => json!({ "records": records }),
+        }
+    }
+
+    #[test]
+    fn test_collect_pages() {
+        let total = PAGESIZE * 2 + 1;
+        let pages = collect_pages("url", |page_num| Ok(page_of((page_num - 1) * PAGESIZE, total)))
+            .unwrap();
+        assert_eq!(pages.records.len(), total);
+        assert_eq!(pages.records[total - 1], json!(total - 1));
+        assert_eq!(pages.head, json!({ "total": total, "incremental": true }));
+
+        // 没有total时以不满一页结束
+        let pages = collect_pages("url", |page_num| {
+            Ok(page(0, if page_num == 1 { PAGESIZE } else { 0 }, None))
+        }).unwrap();
+        assert_eq!(pages.records.len(), PAGESIZE);
+
+        fn page_of(first: usize, total: usize) -> serde_json::Value {
+            page(first, PAGESIZE.min(total - first), Some(total))
+        }
+    }
+
+    #[test]
+    fn test_collect_pages_truncated() {
+        // 记录少于total
+        let res = collect_pages("url", |_| Ok(page(0, 1, Some(PAGESIZE + 1))));
+        match res {
+            Err(Error::Truncated(_)) => (),
+            _ => panic!("expect truncated"),
+        }
+
+        // MAX_PAGE页内没有取完
+        let mut requested = 0;
+        let res = collect_pages("url", |page_num| {
+            requested = page_num;
+            Ok(page(0, PAGESIZE, None))
+        });
+        match res {
+            Err(Error::Truncated(_)) => (),
+            _ => panic!("expect truncated"),
+        }
+        assert_eq!(requested, MAX_PAGE);
+    }
+}
@@ -13,4 +13,5 @@ pub mod rpc_cmd;
 
 pub use error::Error;
 pub use error::Result;
-pub use self::rpc_monitor::RpcMonitor;
\ No newline at end of file
+pub use self::rpc_monitor::RpcMonitor;
+pub use self::http_request::with_egress_proxy;
\ No newline at end of file
@@ -2,15 +2,16 @@ use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::daemon::DaemonEvent;
+use crate::daemon::{DaemonEvent, TunnelCommand};
 use crate::traits::RpcTrait;
-use crate::tinc_manager::TincOperator;
+use crate::tinc_manager::{TincOperator, key_rotation, violation_detection};
 use crate::rpc::rpc_cmd::{RpcEvent, RpcProxyCmd};
 
 use super::web_server;
 use super::RpcClient;
 use std::sync::mpsc::Receiver;
-use crate::settings::default_settings::HEARTBEAT_FREQUENCY_SEC;
+use crate::settings::default_settings::{HEARTBEAT_FREQUENCY_SEC, TEAM_RECONCILE_FREQUENCY_SEC,
+                                        HOST_SYNC_FREQUENCY_SEC};
 use crate::settings::get_settings;
 use dnet_types::settings::RunMode;
 
@@ -74,8 +75,11 @@ impl RpcMonitor {
 
     fn run(self) {
         let timeout_secs: u32 = HEARTBEAT_FREQUENCY_SEC;
+        let mut last_team_sync: Option<Instant> = None;
         loop {
             self.init();
+            // init中已全量同步设备公钥
+            let mut last_host_sync = Instant::now();
             loop {
                 let start = Instant::now();
                 if let Err(_) = self.exec_heartbeat() {
@@ -85,6 +89,25 @@ impl RpcMonitor {
                 if let Err(_) = self.exec_online_proxy() {
                     break
                 }
+
+                self.exec_key_rotation();
+
+                if get_settings().proxy.violation_detection {
+                    violation_detection::check();
+                }
+
+                if last_team_sync.map(|last|last.elapsed()
+                    >= Duration::from_secs(TEAM_RECONCILE_FREQUENCY_SEC))
+                    .unwrap_or(true) {
+                    self.exec_team_sync();
+                    last_team_sync = Some(Instant::now());
+                }
+
+                if last_host_sync.elapsed() >= Duration::from_secs(HOST_SYNC_FREQUENCY_SEC) {
+                    self.exec_host_sync();
+                    last_host_sync = Instant::now();
+                }
+
                 if let Some(remaining) = Duration::from_secs(
                     timeout_secs.into())
                     .checked_sub(start.elapsed()) {
@@ -181,6 +204,41 @@ impl RpcMonitor {
         }
     }
 
+    fn exec_key_rotation(&self) {
+        match key_rotation::rotate_key_if_expired(|| self.client.proxy_add()) {
+            Ok(true) => {
+                let _ = self.daemon_event_tx.send(
+                    DaemonEvent::DaemonInnerCmd(TunnelCommand::Reconnect));
+            }
+            Ok(false) => (),
+            Err(e) => error!("exec_key_rotation {:?}", e),
+        }
+    }
+
+    fn exec_team_sync(&self) {
+        match get_settings().common.mode {
+            RunMode::Center => {
+                if let Err(e) = self.client.center_get_team_info() {
+                    error!("center_get_team_info {:?}", e);
+                }
+            }
+            RunMode::Proxy if get_settings().proxy.team_isolation => {
+                if let Err(e) = self.client.proxy_team_sync() {
+                    error!("proxy_team_sync {:?}", e);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn exec_host_sync(&self) {
+        if get_settings().common.mode == RunMode::Proxy {
+            if let Err(e) = self.client.all_device_pubkey() {
+                error!("all_device_pubkey {:?}", e);
+            }
+        }
+    }
+
     fn exec_online_proxy(&self) -> Result<()> {
         trace!("exec_online_proxy");
         let timeout_secs = Duration::from_secs(20);
@@ -226,6 +284,12 @@ fn cmd_handle(rpc_rx: Receiver<RpcEvent>) {
                         error!("center_get_team_info {:?}", e.to_response());
                     }
                 }
+                else if get_settings().proxy.team_isolation {
+                    if let Err(e) = RpcClient::new()
+                        .proxy_team_sync() {
+                        error!("proxy_team_sync {:?}", e.to_response());
+                    }
+                }
             },
             _ => ()
         }
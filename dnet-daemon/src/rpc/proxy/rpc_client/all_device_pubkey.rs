@@ -1,8 +1,11 @@
 use std::net::IpAddr;
 use std::str::FromStr;
 
+use dnet_types::team::NetSegment;
+
+use crate::info::state::{self, HostKey};
 use crate::settings::get_settings;
-use crate::tinc_manager::TincOperator;
+use crate::tinc_manager::{TincOperator, host_store};
 use crate::rpc::{Error, Result};
 use crate::rpc::http_request::get;
 
@@ -12,16 +15,45 @@ pub fn all_device_pubkey() -> Result<()> {
     let res_data = get(&url)?;
 
     let tinc = TincOperator::new();
+    let mut hosts = vec![];
+    let mut present = vec![];
+    let mut lans = vec![];
     for (vip, pubkey_value) in res_data.as_object()
     .ok_or(Error::ResponseParse(res_data.to_string()))? {
         if let Ok(vip) = IpAddr::from_str(vip) {
-            if let Some(pubkey) = pubkey_value.as_str() {
-                if let Err(e) = tinc.set_hosts(None, vip, pubkey) {
+            // 值为RSA公钥字符串, 或 {"pubKey": .., "ed25519PubKey": .., "lan": [..]}
+            let pubkey = pubkey_value.as_str()
+                .or(pubkey_value.get("pubKey").and_then(|pubkey|pubkey.as_str()));
+            let ed25519_pubkey = pubkey_value.get("ed25519PubKey")
+                .and_then(|pubkey|pubkey.as_str());
+            if let Some(pubkey) = pubkey {
+                if host_store::is_revoked(&vip, pubkey) {
+                    warn!("ignore revoked key vip:{}", vip);
+                    continue
+                }
+                present.push(vip);
+                // 对象中可以带有设备的LAN, 违规检测只允许vip和这些子网
+                let lan = pubkey_value.get("lan")
+                    .and_then(|lan|serde_json::from_value::<Vec<NetSegment>>(lan.clone())
+                        .map_err(|e|error!("vip:{} lan {:?}", vip, e))
+                        .ok())
+                    .unwrap_or(vec![]);
+                lans.push((vip, lan));
+                if let Err(e) = tinc.set_hosts(None, vip, pubkey, ed25519_pubkey) {
                     error!("vip:{} err:{:?}", vip.to_string(), e.to_string())
                 }
+                hosts.push(HostKey {
+                    vip,
+                    pubkey:         pubkey.to_owned(),
+                    ed25519_pubkey: ed25519_pubkey.map(str::to_owned),
+                });
             }
         }
     }
 
+    state::save_hosts(hosts);
+    // 全量列表中没有的设备已被删除
+    host_store::set_lans(&lans);
+    host_store::gc(&present);
     return Ok(())
 }
\ No newline at end of file
@@ -5,6 +5,7 @@ mod heartbeat;
 use heartbeat::proxy_heartbeat;
 mod proxy_add;
 use proxy_add::proxy_add;
+mod proxy_team_sync;
 
 use tinc_plugin::ConnectTo;
 use crate::rpc::common::login::login;
@@ -46,6 +47,10 @@ impl RpcClient {
         proxy_add()
     }
 
+    pub fn proxy_team_sync(&self) -> Result<()> {
+        proxy_team_sync::proxy_team_sync()
+    }
+
     pub fn proxy_get_online_proxy(&self) -> Result<Vec<ConnectTo>> {
         get_online_proxy::get_online_proxy()
     }
@@ -62,6 +67,7 @@ impl RpcClient {
                     Some((host.ip, host.port)),
                     host.vip,
                     &host.pubkey,
+                    host.ed25519_pubkey.as_ref().map(|key|key.as_str()),
                 )
                 .map_err(|e| {
                     error!("add_connect_to_host failed {:?} error:{:?}", host, e);
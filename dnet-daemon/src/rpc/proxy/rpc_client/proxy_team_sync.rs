@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::info::get_info;
+use crate::rpc::{Error, Result};
+use crate::rpc::http_request::loop_post;
+use crate::settings::get_settings;
+use crate::tinc_manager::team_reconcile::reconcile_teams;
+
+/// proxy模式下, 按已连接设备所在的team维护tinc group, 不同team的设备之间不转发.
+/// switch模式下转发在tincd内部完成, 不经过proxy的内核, 防火墙规则由客户端安装, 见team_firewall.
+pub fn proxy_team_sync() -> Result<()> {
+    let url = get_settings().common.conductor_url.clone()
+        + "/vlan/team/member/getAllTeammembersVlanTagging";
+    let res_data = loop_post(&url, "")?;
+
+    let all_teams = serde_json::from_value::<HashMap<String, Vec<IpAddr>>>(
+        res_data.clone())
+        .map_err(|e|{
+            error!("response: {:?}", e);
+            Error::ResponseParse(res_data.to_string())
+        })?;
+
+    let connected = get_info().lock().unwrap().tinc_info.current_connect.clone();
+    let desired = all_teams.into_iter()
+        .filter(|(_, members)| members.iter().any(|member| connected.contains(member)))
+        .collect::<HashMap<String, Vec<IpAddr>>>();
+
+    reconcile_teams(&desired)
+        .map_err(Error::TincTeam)?;
+    Ok(())
+}
@@ -68,6 +68,10 @@ pub(super) fn web_server(
                 web::resource("/vppn/tinc/api/v2/proxy/host_up")
                     .route(web::post().to_async(report_key)))
 
+            .service(
+                web::resource("/vppn/tinc/api/v2/proxy/revokekey")
+                    .route(web::post().to_async(revoke_key)))
+
             .service(
                 web::resource("/vppn/tinc/api/v2/proxy/checkpublickey")
                     .route(web::post().to_async(check_key)))
@@ -12,6 +12,7 @@ use futures::{Future, Stream};
 use bytes::BytesMut;
 use serde_json::json;
 use tinc_plugin::{TincTeam, PID_FILENAME};
+use crate::tinc_manager::{team_reconcile, host_store};
 use dnet_types::response::Response;
 
 use crate::tinc_manager::TincOperator;
@@ -40,6 +41,12 @@ pub fn get_key(req: HttpRequest,
 }
 
 
+pub fn revoke_key(req: HttpRequest,
+                  payload: web::Payload
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    parse_payload(req, payload, revoke_key_inner)
+}
+
 pub fn update_team_info(req: HttpRequest,
                         payload: web::Payload
 ) -> impl Future<Item = HttpResponse, Error = Error> {
@@ -52,6 +59,7 @@ pub struct KeyReport {
     deviceSerial:   String,
     vip:            String,
     pubKey:         String,
+    ed25519PubKey:  Option<String>,
     proxyPubkey:    Option<String>,
 }
 impl KeyReport {
@@ -70,17 +78,25 @@ pub fn report_key_inner(body: String) -> Result<HttpResponse, Error> {
             let client_vec: Vec<KeyReport> = key_report;
             debug!("http_report_key - key_report: {:?}", client_vec);
             let operator = TincOperator::new();
+            let mut confirmed = vec![];
 
             for client in client_vec {
                 if client.pubKey.len() > 0 {
                     let res = IpAddr::from_str(&client.vip)
                         .ok()
                         .and_then(|vip| {
+                            if host_store::is_revoked(&vip, &client.pubKey) {
+                                warn!("http_report_key - ignore revoked key vip:{}", vip);
+                                return Some(());
+                            }
                             operator.set_hosts(
                                 None,
                                 vip,
                                 client.pubKey.as_str(),
-                            ).ok()
+                                client.ed25519PubKey.as_ref().map(|key|key.as_str()),
+                            ).ok()?;
+                            confirmed.push(vip);
+                            Some(())
                         });
 
                     if let None = res {
@@ -89,6 +105,7 @@ pub fn report_key_inner(body: String) -> Result<HttpResponse, Error> {
                     }
                 }
             }
+            host_store::confirm(&confirmed);
         },
         Err(_) => error!("http_report_key - response KeyReport {}", body.as_str()),
     }
@@ -105,6 +122,7 @@ pub fn report_key_inner(body: String) -> Result<HttpResponse, Error> {
 struct CheckPubkey {
     vip:            String,
     pubKey:         String,
+    ed25519PubKey:  Option<String>,
     proxypubKey:    String,
 }
 
@@ -117,7 +135,10 @@ fn check_key_inner(body: String) -> Result<HttpResponse, Error> {
             check_pubkey.vip.as_str());
         if let Ok(pubkey) = operator.get_host_pub_key(
             filename.as_str()) {
-            if pubkey == check_pubkey.pubKey {
+            // 未上报Ed25519公钥的旧客户端只校验RSA公钥
+            let ed25519_matched = check_pubkey.ed25519PubKey.is_none()
+                || operator.get_host_ed25519_pub_key(filename.as_str()) == check_pubkey.ed25519PubKey;
+            if pubkey == check_pubkey.pubKey && ed25519_matched {
                 let response = Response {
                     code: 200,
                     data: Some(json!(pubkey)),
@@ -133,8 +154,13 @@ fn check_key_inner(body: String) -> Result<HttpResponse, Error> {
 }
 
 fn get_key_inner(body: String) -> Result<HttpResponse, Error> {
-    let vips = serde_json::from_str::<serde_json::Value>(&body)
-        .ok()
+    let body = serde_json::from_str::<serde_json::Value>(&body).ok();
+    // withEd25519为true时返回 {vip: {pubKey, ed25519PubKey}}, 否则保持 {vip: pubKey}
+    let with_ed25519 = body.as_ref()
+        .and_then(|value|value.get("withEd25519"))
+        .and_then(|value|value.as_bool())
+        .unwrap_or(false);
+    let vips = body
         .as_ref()
         .and_then(|value|value.get("vip"))
         .and_then(|value|value.as_array())
@@ -162,7 +188,16 @@ fn get_key_inner(body: String) -> Result<HttpResponse, Error> {
         }
 
         if let Ok(pubkey) = operator.get_host_pub_key(filename.as_str()) {
-            output.insert(vip_str, pubkey);
+            if with_ed25519 {
+                let ed25519_pubkey = operator.get_host_ed25519_pub_key(filename.as_str());
+                output.insert(vip_str, json!({
+                    "pubKey":           pubkey,
+                    "ed25519PubKey":    ed25519_pubkey,
+                }));
+            }
+            else {
+                output.insert(vip_str, json!(pubkey));
+            }
         }
     }
 
@@ -176,6 +211,37 @@ fn get_key_inner(body: String) -> Result<HttpResponse, Error> {
     Ok(HttpResponse::Ok().json(response)) // <- send response
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[allow(non_snake_case)]
+struct RevokeKey {
+    vip:            String,
+    /// 不指定时吊销当前host文件中的公钥
+    pubKey:         Option<String>,
+}
+
+/// 吊销公钥, 删除host文件并断开节点, 之后上报的同一公钥不再写入
+fn revoke_key_inner(body: String) -> Result<HttpResponse, Error> {
+    info!("http_revoke_key - response data : {}", body);
+
+    let response = match serde_json::from_str::<Vec<RevokeKey>>(&body) {
+        Ok(revoke_keys) => {
+            let revoked: Vec<String> = revoke_keys.into_iter()
+                .filter_map(|revoke_key| {
+                    let vip = IpAddr::from_str(&revoke_key.vip).ok()?;
+                    host_store::revoke(vip, revoke_key.pubKey)
+                })
+                .map(|revoked|revoked.vip.to_string())
+                .collect();
+            Response::success().set_data(Some(json!(revoked)))
+        }
+        Err(_) => {
+            error!("http_revoke_key - can't parse: {}", body);
+            Response::new_from_code(407)
+        }
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
 fn update_team_info_inner(body: String) -> Result<HttpResponse, Error> {
     if get_settings().common.mode == RunMode::Center {
         info!("update_team_info - response data : {}",body);
@@ -188,8 +254,11 @@ fn update_team_info_inner(body: String) -> Result<HttpResponse, Error> {
                     let tinc_pid = get_settings().common.home_path
                         .join("tinc").join(PID_FILENAME)
                         .to_str().unwrap().to_string();
-                    match tinc_team.send_to_tinc(&tinc_pid) {
-                        Ok(_) => response = Response::success(),
+                    match tinc_team.clone().send_to_tinc(&tinc_pid) {
+                        Ok(_) => {
+                            team_reconcile::apply_team_delta(&tinc_team);
+                            response = Response::success()
+                        },
                         Err(failed_team) => {
                             if let Ok(value) = serde_json::to_value(&failed_team) {
                                 response = Response::internal_error()
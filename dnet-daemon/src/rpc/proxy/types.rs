@@ -13,10 +13,12 @@ pub struct JavaProxy {
     authCookie:                 Option<String>,
     authId:                     Option<String>,
     authType:                   Option<String>,
+    capacity:                   Option<u32>,
     city:                       Option<String>,
     companyId:                  Option<String>,
     connections:                u32,
     country:                    Option<String>,
+    draining:                   bool,
     edges:                      u32,
     id:                         Option<String>,
     ip:                         Option<String>,
@@ -25,6 +27,7 @@ pub struct JavaProxy {
     nodes:                      u32,
     os:                         Option<String>,
     pubkey:                     String,
+    ed25519Pubkey:              Option<String>,
     publicFlag:                 bool,
     region:                     Option<String>,
     serverPort:                 u16,
@@ -55,6 +58,8 @@ impl JavaProxy {
         let edges = info.tinc_info.edges.clone();
         let nodes = info.tinc_info.nodes.clone();
         let pubkey = info.tinc_info.pub_key.clone();
+        let ed25519_pubkey = info.tinc_info.ed25519_pub_key.clone();
+        let draining = info.status.draining.is_some();
 
         let settings = get_settings();
         let ip = settings.proxy.local_ip.clone().map(|ip|ip.to_string());
@@ -70,10 +75,12 @@ impl JavaProxy {
             authCookie:     tinc_pid_file_all_string,
             authId:         auth_id,
             authType:       None,
+            capacity:       settings.proxy.capacity,
             city:           None,
             companyId:      None,
             connections,
             country:        None,
+            draining,
             edges,
             id:             None,
             ip,
@@ -82,6 +89,7 @@ impl JavaProxy {
             nodes,
             os:             None,
             pubkey,
+            ed25519Pubkey:  ed25519_pubkey,
             publicFlag:     public,
             region:         None,
             serverPort:     local_port,
@@ -0,0 +1,131 @@
+//! proxy drain时通过隧道通知直连的客户端立即重新选择proxy, 不等待下一次获取在线proxy.
+//! proxy向客户端vip的UDP SELECT_PROXY_NOTIFY_PORT发送通知; 客户端只在本节点vip上监听,
+//! 只接受来自当前连接的proxy vip的通知, 并限制响应频率. 通知只触发重新选择, 是否迁移仍以conductor返回的draining状态为准.
+
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dnet_types::settings::RunMode;
+
+use crate::daemon::DaemonEvent;
+use crate::info::get_info;
+use crate::settings::get_settings;
+use crate::settings::default_settings::{SELECT_PROXY_NOTIFY_PORT, SELECT_PROXY_NOTIFY_INTERVAL_SEC};
+
+const MESSAGE: &[u8] = b"dnet select proxy";
+/// 检查本节点vip是否变化的间隔
+const VIP_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 客户端监听proxy的通知, 收到后发送DaemonEvent::SelectProxy
+pub fn start(daemon_event_tx: Sender<DaemonEvent>) {
+    if get_settings().common.mode != RunMode::Client {
+        return;
+    }
+    let _ = thread::Builder::new()
+        .name("select_proxy_notify".to_string())
+        .spawn(move || listen(daemon_event_tx))
+        .map_err(|e|error!("start select proxy notify {:?}", e));
+}
+
+/// 只绑定本节点vip, 不在WAN上监听. vip配置到dnet网卡之前绑定失败, 之后重试; vip变化时重新绑定
+fn listen(daemon_event_tx: Sender<DaemonEvent>) {
+    let mut buf = [0u8; 64];
+    let mut last = None;
+    let mut bound: Option<(IpAddr, UdpSocket)> = None;
+    loop {
+        let vip = get_info().lock().unwrap().tinc_info.vip;
+        if bound.as_ref().map(|(bound_vip, _)|*bound_vip) != vip {
+            bound = vip.and_then(|vip|bind(vip).map(|socket|(vip, socket)));
+        }
+        let socket = match &bound {
+            Some((_, socket)) => socket,
+            None => {
+                thread::sleep(VIP_CHECK_INTERVAL);
+                continue;
+            }
+        };
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(res) => res,
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => continue,
+            Err(e) => {
+                error!("select proxy notify recv {:?}", e);
+                bound = None;
+                thread::sleep(VIP_CHECK_INTERVAL);
+                continue;
+            }
+        };
+        let proxies: Vec<IpAddr> = get_info().lock().unwrap().tinc_info.connect_to.iter()
+            .map(|connect_to|connect_to.vip)
+            .collect();
+        let now = Instant::now();
+        if !accept(&buf[..len], from.ip(), &proxies, last, now) {
+            debug!("ignore select proxy notify from {}", from);
+            continue;
+        }
+        info!("proxy {} draining, select proxy.", from.ip());
+        last = Some(now);
+        if daemon_event_tx.send(DaemonEvent::SelectProxy).is_err() {
+            return;
+        }
+    }
+}
+
+fn bind(vip: IpAddr) -> Option<UdpSocket> {
+    let socket = UdpSocket::bind(SocketAddr::new(vip, SELECT_PROXY_NOTIFY_PORT))
+        .and_then(|socket|socket.set_read_timeout(Some(VIP_CHECK_INTERVAL)).map(|_|socket));
+    match socket {
+        Ok(socket) => {
+            info!("select proxy notify listen on {}", vip);
+            Some(socket)
+        }
+        Err(e) => {
+            debug!("select proxy notify bind {} {:?}", vip, e);
+            None
+        }
+    }
+}
+
+fn accept(message: &[u8], from: IpAddr, proxies: &[IpAddr], last: Option<Instant>, now: Instant) -> bool {
+    message == MESSAGE
+        && proxies.contains(&from)
+        && last.map(|last|now.duration_since(last) >= Duration::from_secs(SELECT_PROXY_NOTIFY_INTERVAL_SEC))
+            .unwrap_or(true)
+}
+
+/// proxy通知客户端, UDP不保证送达
+pub fn notify(vips: &[IpAddr]) {
+    let socket = match UdpSocket::bind(SocketAddr::new(IpAddr::from(Ipv4Addr::UNSPECIFIED), 0)) {
+        Ok(socket) => socket,
+        Err(e) => {
+            warn!("select proxy notify {:?}", e);
+            return;
+        }
+    };
+    for vip in vips {
+        if let Err(e) = socket.send_to(MESSAGE, SocketAddr::new(*vip, SELECT_PROXY_NOTIFY_PORT)) {
+            debug!("select proxy notify {} {:?}", vip, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_accept() {
+        let proxy: IpAddr = "10.253.0.1".parse().unwrap();
+        let other: IpAddr = "10.253.0.2".parse().unwrap();
+        let now = Instant::now();
+        assert!(accept(MESSAGE, proxy, &[proxy], None, now));
+        assert!(!accept(b"other", proxy, &[proxy], None, now));
+        assert!(!accept(MESSAGE, other, &[proxy], None, now));
+        assert!(!accept(MESSAGE, proxy, &[proxy], Some(now), now + Duration::from_secs(1)));
+        assert!(accept(MESSAGE, proxy, &[proxy], Some(now),
+                       now + Duration::from_secs(SELECT_PROXY_NOTIFY_INTERVAL_SEC)));
+    }
+}
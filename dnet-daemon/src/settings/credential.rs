@@ -0,0 +1,167 @@
+//! 密码和token的本地加密存储.
+//! 密钥由 home_path/credential.key(首次使用时随机生成, 权限0600)与本机machine-id派生,
+//! 密文复制到其他机器无法解密. 没有machine-id(macOS, Windows, 部分路由器)时只使用credential.key,
+//! 此时复制整个home_path即可解密. 密文格式: enc:v1:base64(iv | ciphertext | tag).
+//! dnet login的账号保存在 <profile目录>/credentials.json, 只写入密文.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use openssl::base64;
+use openssl::rand::rand_bytes;
+use openssl::sha::Sha256;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use super::error::*;
+
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const KEY_FILENAME: &str = "credential.key";
+const CREDENTIALS_FILENAME: &str = "credentials.json";
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// dnet login保存的账号, password为密文
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredLogin {
+    pub username:   String,
+    pub password:   String,
+}
+
+pub fn encrypt(home_path: &Path, plain: &str) -> Result<String> {
+    let key = machine_key(home_path)?;
+    let mut iv = [0u8; IV_LEN];
+    rand_bytes(&mut iv).map_err(|e|Error::Credential(e.to_string()))?;
+    let mut tag = [0u8; TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(), &key, Some(&iv), &[], plain.as_bytes(), &mut tag)
+        .map_err(|e|Error::Credential(e.to_string()))?;
+
+    let mut buf = iv.to_vec();
+    buf.extend_from_slice(&ciphertext);
+    buf.extend_from_slice(&tag);
+    Ok(ENCRYPTED_PREFIX.to_string() + &base64::encode_block(&buf))
+}
+
+/// 没有密文前缀的值视为旧版本的明文, 原样返回
+pub fn decrypt(home_path: &Path, value: &str) -> Result<String> {
+    if !value.starts_with(ENCRYPTED_PREFIX) {
+        return Ok(value.to_string());
+    }
+    let buf = base64::decode_block(&value[ENCRYPTED_PREFIX.len()..])
+        .map_err(|e|Error::Credential(e.to_string()))?;
+    if buf.len() < IV_LEN + TAG_LEN {
+        return Err(Error::Credential("ciphertext too short".to_string()));
+    }
+    let (iv, rest) = buf.split_at(IV_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - TAG_LEN);
+    let key = machine_key(home_path)?;
+    let plain = decrypt_aead(Cipher::aes_256_gcm(), &key, Some(iv), &[], ciphertext, tag)
+        .map_err(|_|Error::Credential("decrypt failed, key file or machine changed".to_string()))?;
+    String::from_utf8(plain).map_err(|e|Error::Credential(e.to_string()))
+}
+
+pub fn load_login(dir: &Path) -> Option<StoredLogin> {
+    let buf = fs::read_to_string(dir.join(CREDENTIALS_FILENAME)).ok()?;
+    serde_json::from_str(&buf)
+        .map_err(|e|error!("parse {} {:?}", CREDENTIALS_FILENAME, e))
+        .ok()
+}
+
+pub fn save_login(home_path: &Path, dir: &Path, username: &str, password: &str) -> Result<()> {
+    let login = StoredLogin {
+        username:   username.to_string(),
+        password:   encrypt(home_path, password)?,
+    };
+    let buf = serde_json::to_string(&login).map_err(|e|Error::Credential(e.to_string()))?;
+    write_private(&dir.join(CREDENTIALS_FILENAME), buf.as_bytes())
+}
+
+pub fn remove_login(dir: &Path) {
+    let _ = fs::remove_file(dir.join(CREDENTIALS_FILENAME));
+}
+
+/// 写入只有所有者可读写的文件
+pub fn write_private(path: &Path, buf: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let tmp = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+    options.open(&tmp)
+        .and_then(|mut file|file.write_all(buf))
+        .and_then(|_|fs::rename(&tmp, path))
+        .map_err(|e|Error::Credential(format!("write {:?} {}", path, e)))
+}
+
+fn machine_key(home_path: &Path) -> Result<Vec<u8>> {
+    let path = home_path.join(KEY_FILENAME);
+    let secret = match fs::read(&path) {
+        Ok(secret) => {
+            restrict_permissions(&path);
+            secret
+        }
+        Err(_) => {
+            let mut secret = vec![0u8; 32];
+            rand_bytes(&mut secret).map_err(|e|Error::Credential(e.to_string()))?;
+            write_private(&path, &secret)?;
+            secret
+        }
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(&secret);
+    match machine_id() {
+        Some(id) => hasher.update(id.as_bytes()),
+        None => {
+            static WARN: Once = Once::new();
+            WARN.call_once(||warn!("machine-id not found, credential key bound to {:?} only", path));
+        }
+    }
+    Ok(hasher.finish().to_vec())
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &PathBuf) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.permissions().mode() & 0o077 != 0 {
+            warn!("{:?} readable by others, reset to 0600", path);
+            let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &PathBuf) {}
+
+/// 文件不存在或为空时返回None
+pub fn machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"].iter()
+        .filter_map(|path|fs::read_to_string(path).ok())
+        .map(|id|id.trim().to_string())
+        .find(|id|!id.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let home_path = std::env::temp_dir().join("dnet_credential_test");
+        let encrypted = encrypt(&home_path, "password").unwrap();
+        assert!(encrypted.starts_with(ENCRYPTED_PREFIX));
+        assert!(!encrypted.contains("password"));
+        assert_eq!(decrypt(&home_path, &encrypted).unwrap(), "password");
+        assert_eq!(decrypt(&home_path, "plain").unwrap(), "plain");
+        let _ = fs::remove_dir_all(&home_path);
+    }
+}
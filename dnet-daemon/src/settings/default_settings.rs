@@ -1,6 +1,11 @@
 // common
 pub const DEFAULT_LOG_LEVEL: &str = "Error";
 pub const HTTP_TIMEOUT: u32 = 10;
+pub const LOG_FILENAME: &str = "dnet.log";
+/// 网络变化事件合并的时间, 期间的多个netlink事件只检查一次
+pub const NETWORK_CHANGE_DEBOUNCE_MILLIS: u64 = 2000;
+/// 没有netlink时检查默认路由的周期
+pub const NETWORK_POLL_FREQUENCY_SEC: u64 = 10;
 
 // proxy
 #[cfg(target_os = "linux")]
@@ -10,6 +15,43 @@ pub const DEFAULT_PROXY_TYPE: &str = "other";
 pub const DEFAULT_CLIENT_AUTO_CONNECT: bool = true;
 pub const HEARTBEAT_FREQUENCY_SEC: u32 = 20;
 pub const DEFAULT_PROXY_PUBLIC: bool = false;
+/// 小于systemd默认的TimeoutStopSec(90s)
+pub const DEFAULT_PROXY_DRAIN_TIMEOUT: u32 = 60;
+pub const DRAIN_CHECK_FREQUENCY_SEC: u64 = 5;
+/// drain时proxy通过隧道通知客户端重新选择proxy的UDP端口
+pub const SELECT_PROXY_NOTIFY_PORT: u16 = 50070;
+/// 客户端两次响应通知的最小间隔
+pub const SELECT_PROXY_NOTIFY_INTERVAL_SEC: u64 = 30;
+
+// proxy selection
+pub const DEFAULT_PROXY_SCORE_POLICY: &str = "weighted";
+pub const DEFAULT_PROXY_PROBE_COUNT: u32 = 3;
+pub const DEFAULT_PROXY_CAPACITY: u32 = 500;
+pub const DEFAULT_PROXY_RTT_WEIGHT: f64 = 1.0;
+pub const DEFAULT_PROXY_LOSS_WEIGHT: f64 = 5.0;
+pub const DEFAULT_PROXY_LOAD_WEIGHT: f64 = 1.0;
+pub const DEFAULT_PROXY_REGION_PENALTY: f64 = 100.0;
+pub const DEFAULT_PROXY_STICKINESS: f64 = 30.0;
 
 // tinc
+#[cfg(not(target_os = "macos"))]
 pub const TINC_INTERFACE: &str = "dnet";
+/// macOS使用tuntaposx的/dev/tap0
+#[cfg(target_os = "macos")]
+pub const TINC_INTERFACE: &str = "tap0";
+/// 定期对账tinc group与conductor team
+pub const TEAM_RECONCILE_FREQUENCY_SEC: u64 = 300;
+/// proxy定期全量同步设备公钥, 删除已不存在设备的host文件
+pub const HOST_SYNC_FREQUENCY_SEC: u64 = 3600;
+/// status中保留的最近安全事件数
+pub const SECURITY_EVENT_LIMIT: usize = 20;
+
+// router
+pub const DEFAULT_ROUTER_WAN: [&str; 2] = ["brwan", "ppp0"];
+pub const DEFAULT_ROUTER_LAN: [&str; 4] = ["br0", "br1", "br2", "br3"];
+
+// hooks
+pub const HOOKS_DIR: &str = "hooks.d";
+pub const DEFAULT_HOOK_TIMEOUT: u32 = 10;
+pub const DEFAULT_HOOK_MAX_CONCURRENCY: u32 = 4;
+pub const HOOK_QUEUE_SIZE: usize = 64;
@@ -31,4 +31,7 @@ pub enum Error {
 
     #[error(display = "Using for trance Option to Result")]
     NoneError,
+
+    #[error(display = "Credential storage error")]
+    Credential(String),
 }
\ No newline at end of file
@@ -1,9 +1,12 @@
 extern crate config;
 
 pub(crate) mod default_settings;
+pub mod credential;
 pub mod error;
 mod parse_file;
+mod profile;
 mod run_time_settings;
 
 pub use error::Error;
-pub use run_time_settings::{Settings, get_settings, get_mut_settings};
\ No newline at end of file
+pub use default_settings::LOG_FILENAME;
+pub use run_time_settings::{Settings, ProxySelection, EgressProxy, get_settings, get_mut_settings};
\ No newline at end of file
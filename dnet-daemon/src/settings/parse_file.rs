@@ -1,4 +1,5 @@
 use std::path::Path;
+use std::collections::BTreeMap;
 
 use config::{Config, File};
 
@@ -15,6 +16,19 @@ pub struct Common {
     pub username:                               Option<String>,
     pub password:                               Option<String>,
     pub http_timeout:                           Option<u32>,
+    pub profile:                                Option<String>,
+    pub identity_providers:                     Option<Vec<String>>,
+    pub egress_proxy:                           Option<EgressProxy>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EgressProxy {
+    pub proxy_type:                             Option<String>,
+    pub address:                                Option<String>,
+    pub username:                               Option<String>,
+    pub password:                               Option<String>,
+    pub no_proxy:                               Option<Vec<String>>,
+    pub tinc:                                   Option<bool>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -25,11 +39,31 @@ pub struct Proxy {
     pub local_https_server_privkey_file:        Option<String>,
     pub proxy_type:                             Option<String>,
     pub public:                                 Option<bool>,
+    pub team_isolation:                         Option<bool>,
+    pub capacity:                               Option<u32>,
+    pub drain_on_shutdown:                      Option<bool>,
+    pub drain_timeout:                          Option<u32>,
+    pub drain_threshold:                        Option<u32>,
+    pub violation_detection:                    Option<bool>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct Client {
     pub auto_connect:                              Option<String>,
+    pub team_isolation_firewall:                   Option<bool>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ProxySelection {
+    pub policy:                                    Option<String>,
+    pub probe_count:                               Option<u32>,
+    pub rtt_weight:                                Option<f64>,
+    pub loss_weight:                               Option<f64>,
+    pub load_weight:                               Option<f64>,
+    pub region_penalty:                            Option<f64>,
+    pub preferred_regions:                         Option<Vec<String>>,
+    pub stickiness:                                Option<f64>,
+    pub default_capacity:                          Option<u32>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -41,15 +75,43 @@ pub struct Tinc {
     pub tinc_allowed_out_memory_times:             Option<String>,
     pub tinc_allowed_tcp_failed_times:             Option<String>,
     pub external_boot:                             Option<String>,
+    pub key_max_age_days:                          Option<String>,
+    pub ed25519_key:                               Option<String>,
+    pub extra:                                     Option<BTreeMap<String, String>>,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct Hooks {
+    pub webhooks:                                  Option<Vec<String>>,
+    pub timeout:                                   Option<u32>,
+    pub max_concurrency:                           Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Router {
+    pub wan:                                       Option<Vec<String>>,
+    pub lan:                                       Option<Vec<String>>,
+    pub tunnel:                                    Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Profile {
+    pub conductor_url:                             Option<String>,
+    pub username:                                  Option<String>,
+    pub password:                                  Option<String>,
+    pub accept_conductor_invalid_certs:            Option<bool>,
+}
 
 #[derive(Clone, Debug, Deserialize)]
 pub(crate) struct FileSettings {
     pub common: Option<Common>,
     pub proxy:  Option<Proxy>,
     pub client: Option<Client>,
+    pub proxy_selection: Option<ProxySelection>,
     pub tinc:   Option<Tinc>,
+    pub hooks:  Option<Hooks>,
+    pub router: Option<Router>,
+    pub profiles: Option<BTreeMap<String, Profile>>,
 }
 
 impl FileSettings {
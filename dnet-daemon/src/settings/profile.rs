@@ -0,0 +1,370 @@
+//! 多conductor/账号profile.
+//! settings.toml的[profiles.<name>]为固定profile, 命令添加的profile和当前使用的profile
+//! 保存在 home_path/profiles.json. 使用profile时以其覆盖common中的conductor_url和账号.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dnet_types::settings::{Profile, ProfileList};
+
+use super::credential;
+use super::error::*;
+use super::run_time_settings::Settings;
+
+const PROFILES_FILENAME: &str = "profiles.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProfilesFile {
+    active:     Option<String>,
+    profiles:   Vec<Profile>,
+}
+
+impl Settings {
+    pub(super) fn check_profile(profile: &Profile) -> Result<()> {
+        if !Profile::is_valid_name(&profile.name) {
+            return Err(Error::Config("profile name ".to_string() + &profile.name));
+        }
+        url::Url::parse(&profile.conductor_url)
+            .map_err(|_|Error::invalid_conductro_url)?;
+        Ok(())
+    }
+
+    /// 合并profiles.json, 并应用当前profile
+    pub(super) fn load_profiles(&mut self) -> Result<()> {
+        let file = fs::read_to_string(self.profiles_path()).ok()
+            .and_then(|buf| serde_json::from_str::<ProfilesFile>(&buf)
+                .map_err(|e|error!("parse {} {:?}", PROFILES_FILENAME, e))
+                .ok())
+            .unwrap_or(ProfilesFile::default());
+        for mut profile in file.profiles {
+            if self.profiles.get(&profile.name).map(|profile|profile.from_file).unwrap_or(false) {
+                continue;
+            }
+            profile.from_file = false;
+            profile.password = credential::decrypt(&self.common.home_path, &profile.password)
+                .map_err(|e|error!("profile {} password {:?}", profile.name, e))
+                .unwrap_or(String::new());
+            self.profiles.insert(profile.name.clone(), profile);
+        }
+
+        if let Some(active) = file.active.or(self.common.profile.clone()) {
+            if let Err(e) = self.use_profile_inner(&active) {
+                warn!("ignore profile {} {:?}", active, e);
+                self.common.profile = None;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn profile_list(&self) -> ProfileList {
+        ProfileList {
+            active:     self.common.profile.clone(),
+            profiles:   self.profiles.values()
+                .map(|profile| Profile {
+                    password: String::new(),
+                    ..profile.clone()
+                })
+                .collect(),
+        }
+    }
+
+    /// 添加或替换命令添加的profile
+    pub fn add_profile(&mut self, mut profile: Profile) -> Result<()> {
+        Self::check_profile(&profile)?;
+        if self.profiles.get(&profile.name).map(|profile|profile.from_file).unwrap_or(false) {
+            return Err(Error::Config("profile defined in settings.toml ".to_string() + &profile.name));
+        }
+        profile.from_file = false;
+        self.profiles.insert(profile.name.clone(), profile);
+        self.save_profiles()
+    }
+
+    pub fn remove_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self.profiles.get(name)
+            .ok_or(Error::Config("profile not found ".to_string() + name))?;
+        if profile.from_file {
+            return Err(Error::Config("profile defined in settings.toml ".to_string() + name));
+        }
+        if self.common.profile.as_ref().map(String::as_str) == Some(name) {
+            return Err(Error::Config("profile in use ".to_string() + name));
+        }
+        self.profiles.remove(name);
+        self.save_profiles()
+    }
+
+    /// 以profile覆盖conductor_url和账号, 并记录为当前profile
+    pub fn use_profile(&mut self, name: &str) -> Result<()> {
+        self.use_profile_inner(name)?;
+        self.save_profiles()
+    }
+
+    /// 切换profile失败时恢复切换前的settings和profiles.json中的当前profile
+    pub fn restore_profile(&mut self, previous: Settings) -> Result<()> {
+        *self = previous;
+        self.save_profiles()
+    }
+
+    fn use_profile_inner(&mut self, name: &str) -> Result<()> {
+        let profile = self.profiles.get(name)
+            .ok_or(Error::Config("profile not found ".to_string() + name))?
+            .clone();
+        self.common.conductor_url = profile.conductor_url;
+        self.common.accept_conductor_invalid_certs = profile.accept_conductor_invalid_certs;
+        // 路由器使用SN作为账号
+        #[cfg(any(not(target_arch = "arm"), feature = "router_debug"))]
+            {
+                self.common.username = profile.username;
+                self.common.password = profile.password;
+            }
+        self.common.profile = Some(profile.name);
+        self.load_credentials();
+        Ok(())
+    }
+
+    /// 当前profile的数据目录, 未使用profile时为home_path
+    pub fn profile_dir(&self) -> PathBuf {
+        match &self.common.profile {
+            Some(profile) => self.common.home_path.join("profiles").join(profile),
+            None => self.common.home_path.clone(),
+        }
+    }
+
+    /// settings.toml中的密码可以是密文; dnet login保存的账号优先于配置文件
+    pub(super) fn load_credentials(&mut self) {
+        let home_path = self.common.home_path.clone();
+        self.common.password = credential::decrypt(&home_path, &self.common.password)
+            .map_err(|e|error!("password {:?}", e))
+            .unwrap_or(String::new());
+        if let Some(egress_proxy) = self.common.egress_proxy.as_mut() {
+            egress_proxy.password = credential::decrypt(&home_path, &egress_proxy.password)
+                .map_err(|e|error!("egress_proxy password {:?}", e))
+                .unwrap_or(String::new());
+        }
+        #[cfg(any(not(target_arch = "arm"), feature = "router_debug"))]
+            {
+                if let Some(login) = credential::load_login(&self.profile_dir()) {
+                    match credential::decrypt(&home_path, &login.password) {
+                        Ok(password) => {
+                            self.common.username = login.username;
+                            self.common.password = password;
+                        }
+                        Err(e) => error!("stored login {:?}", e),
+                    }
+                }
+            }
+    }
+
+    /// settings.toml中的明文密码在首次启动时加密保存到credentials.json, 之后优先使用credentials.json.
+    /// 保存成功(或已保存过)后删除settings.toml [common]中的password
+    #[cfg(any(not(target_arch = "arm"), feature = "router_debug"))]
+    pub(super) fn migrate_plain_password(&self, config_dir: &str) {
+        let password = &self.common.password;
+        if password.is_empty() || password.starts_with(credential::ENCRYPTED_PREFIX) {
+            return;
+        }
+        if credential::load_login(&self.common.home_path).is_none() {
+            if let Err(e) = credential::save_login(
+                &self.common.home_path, &self.common.home_path, &self.common.username, password) {
+                error!("migrate plaintext password {:?}", e);
+                return;
+            }
+        }
+
+        let config_file = Path::new(config_dir).join("settings.toml");
+        let res = fs::read_to_string(&config_file)
+            .map_err(|e|Error::Config(e.to_string()))
+            .and_then(|config| match remove_common_password(&config) {
+                Some(config) => credential::write_private(&config_file, config.as_bytes()),
+                None => Ok(()),
+            });
+        match res {
+            Ok(_) => warn!("plaintext password in settings.toml moved to credentials.json"),
+            Err(e) => error!("remove plaintext password from {:?} {:?}", config_file, e),
+        }
+    }
+
+    /// dnet login成功后只保存密文; 当前profile为命令添加时同时更新其账号
+    pub fn save_login(&mut self) -> Result<()> {
+        let active = match &self.common.profile {
+            Some(name) => self.profiles.get_mut(name),
+            None => None,
+        };
+        if let Some(profile) = active.filter(|profile|!profile.from_file) {
+            profile.username = self.common.username.clone();
+            profile.password = self.common.password.clone();
+            self.save_profiles()?;
+        }
+        credential::save_login(
+            &self.common.home_path,
+            &self.profile_dir(),
+            &self.common.username,
+            &self.common.password)
+    }
+
+    pub fn remove_login(&self) {
+        credential::remove_login(&self.profile_dir());
+    }
+
+    fn save_profiles(&self) -> Result<()> {
+        let mut profiles = vec![];
+        for profile in self.profiles.values().filter(|profile|!profile.from_file) {
+            profiles.push(Profile {
+                password: credential::encrypt(&self.common.home_path, &profile.password)?,
+                ..profile.clone()
+            });
+        }
+        let file = ProfilesFile {
+            active:     self.common.profile.clone(),
+            profiles,
+        };
+        let buf = serde_json::to_string_pretty(&file)
+            .map_err(|e|Error::Config(e.to_string()))?;
+        credential::write_private(&self.profiles_path(), buf.as_bytes())
+    }
+
+    fn profiles_path(&self) -> PathBuf {
+        self.common.home_path.join(PROFILES_FILENAME)
+    }
+}
+
+/// 删除[common]中的password行, 保留其它内容和注释. 没有时返回None
+#[cfg(any(not(target_arch = "arm"), feature = "router_debug"))]
+fn remove_common_password(config: &str) -> Option<String> {
+    let mut section = String::new();
+    let mut removed = false;
+    let mut buf = String::new();
+    for line in config.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed.trim_start_matches('[').split(']').next().unwrap_or("").trim().to_string();
+        }
+        else if section == "common"
+            && trimmed.split('=').next().map(|key|key.trim() == "password").unwrap_or(false)
+            && trimmed.contains('=') {
+            removed = true;
+            continue;
+        }
+        buf += line;
+        buf += "\n";
+    }
+    if removed {
+        Some(buf)
+    }
+    else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::parse_file::FileSettings;
+
+    /// 同一home_path再次调用时保留profiles.json等文件, 相当于daemon重启
+    fn test_settings(name: &str) -> Settings {
+        let home_path = std::env::temp_dir().join(name);
+        fs::create_dir_all(&home_path).unwrap();
+        let config = format!(r#"
+[common]
+home_path = "{0}"
+log_dir = "{0}"
+conductor_url = "https://default.example.com"
+username = "default"
+password = "default"
+
+[profiles.office]
+conductor_url = "https://office.example.com"
+username = "office"
+password = "office"
+"#, home_path.to_string_lossy());
+        fs::write(home_path.join("settings.toml"), config).unwrap();
+        let file_settings = FileSettings::load_config(&home_path.to_string_lossy()).unwrap();
+        Settings::parse_file_settings(file_settings).unwrap()
+    }
+
+    fn profile(name: &str, username: &str) -> Profile {
+        Profile {
+            name:           name.to_string(),
+            conductor_url:  format!("https://{}.example.com", name),
+            username:       username.to_string(),
+            password:       username.to_string(),
+            accept_conductor_invalid_certs: false,
+            from_file:      false,
+        }
+    }
+
+    #[test]
+    fn test_load_profiles() {
+        let _ = fs::remove_dir_all(std::env::temp_dir().join("dnet_profile_load_test"));
+        let settings = test_settings("dnet_profile_load_test");
+        // settings.toml中的同名profile优先
+        let file = ProfilesFile {
+            active:     Some("home".to_string()),
+            profiles:   vec![
+                profile("office", "stale"),
+                Profile { password: credential::encrypt(&settings.common.home_path, "home").unwrap(), ..profile("home", "home") },
+            ],
+        };
+        fs::write(settings.profiles_path(), serde_json::to_string(&file).unwrap()).unwrap();
+
+        let mut settings = test_settings("dnet_profile_load_test");
+        settings.load_profiles().unwrap();
+        assert_eq!(settings.profiles["office"].username, "office");
+        assert!(settings.profiles["office"].from_file);
+        assert!(!settings.profiles["home"].from_file);
+        assert_eq!(settings.common.profile, Some("home".to_string()));
+        assert_eq!(settings.common.conductor_url, "https://home.example.com");
+        assert_eq!(settings.common.username, "home");
+        assert_eq!(settings.common.password, "home");
+        let _ = fs::remove_dir_all(&settings.common.home_path);
+    }
+
+    #[test]
+    fn test_use_remove_profile() {
+        let _ = fs::remove_dir_all(std::env::temp_dir().join("dnet_profile_use_test"));
+        let mut settings = test_settings("dnet_profile_use_test");
+        settings.add_profile(profile("home", "home")).unwrap();
+        assert!(settings.add_profile(profile("office", "office")).is_err());
+
+        settings.use_profile("office").unwrap();
+        assert_eq!(settings.common.conductor_url, "https://office.example.com");
+        assert_eq!(settings.profile_dir(), settings.common.home_path.join("profiles").join("office"));
+        assert!(settings.use_profile("missing").is_err());
+        assert!(settings.remove_profile("office").is_err());
+
+        settings.use_profile("home").unwrap();
+        assert!(settings.remove_profile("home").is_err());
+        settings.common.password = "changed".to_string();
+        settings.save_login().unwrap();
+
+        let mut reloaded = test_settings("dnet_profile_use_test");
+        reloaded.load_profiles().unwrap();
+        assert_eq!(reloaded.common.profile, Some("home".to_string()));
+        assert_eq!(reloaded.profiles["home"].password, "changed");
+        assert_eq!(reloaded.common.password, "changed");
+
+        reloaded.use_profile("office").unwrap();
+        reloaded.remove_profile("home").unwrap();
+        assert!(!reloaded.profiles.contains_key("home"));
+        let _ = fs::remove_dir_all(&reloaded.common.home_path);
+    }
+
+    #[cfg(any(not(target_arch = "arm"), feature = "router_debug"))]
+    #[test]
+    fn test_remove_common_password() {
+        let config = "[common]\n\
+                      username = \"dnet\"\n\
+                      password = \"secret\" # 明文\n\
+                      \n\
+                      [profiles.office]\n\
+                      password = \"office\"\n";
+        assert_eq!(remove_common_password(config).unwrap(),
+                   "[common]\n\
+                   username = \"dnet\"\n\
+                   \n\
+                   [profiles.office]\n\
+                   password = \"office\"\n");
+        assert!(remove_common_password("[common]\npassword_file = \"x\"\n").is_none());
+        assert!(remove_common_password("[proxy]\npassword = \"x\"\n").is_none());
+    }
+}
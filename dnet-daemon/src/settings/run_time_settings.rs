@@ -1,22 +1,31 @@
 use std::path::PathBuf;
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
+use dnet_types::identity::IdentityProvider;
 use dnet_types::settings::{
     Settings as TypeSettings,
     Common as TypeCommon,
     Client as TypeClient,
     Proxy as TypeProxy,
+    Profile,
     RunMode
 };
 
-use super::parse_file::FileSettings;
+use super::parse_file::{FileSettings, EgressProxy as FileEgressProxy};
 use super::default_settings::{DEFAULT_PROXY_LOCAL_SERVER_PORT, DEFAULT_PROXY_TYPE, DEFAULT_LOG_LEVEL, DEFAULT_CLIENT_AUTO_CONNECT};
 #[cfg(target_os = "linux")]
 use super::default_settings::DEFAULT_LINUX_DEFAULT_HOME_PATH;
 use super::error::*;
 use std::net::IpAddr;
-use tinc_plugin::DEFAULT_TINC_PORT;
-use crate::settings::default_settings::{DEFAULT_PROXY_PUBLIC, HTTP_TIMEOUT};
+use tinc_plugin::{TincConfig, DEFAULT_TINC_PORT, DEFAULT_KEY_MAX_AGE_DAYS};
+use crate::settings::default_settings::{DEFAULT_PROXY_SCORE_POLICY, DEFAULT_PROXY_PROBE_COUNT,
+                                        DEFAULT_PROXY_CAPACITY, DEFAULT_PROXY_RTT_WEIGHT,
+                                        DEFAULT_PROXY_LOSS_WEIGHT, DEFAULT_PROXY_LOAD_WEIGHT,
+                                        DEFAULT_PROXY_REGION_PENALTY, DEFAULT_PROXY_STICKINESS};
+use crate::settings::default_settings::{DEFAULT_PROXY_PUBLIC, DEFAULT_PROXY_DRAIN_TIMEOUT, HTTP_TIMEOUT,
+                                        DEFAULT_HOOK_TIMEOUT, DEFAULT_HOOK_MAX_CONCURRENCY,
+                                        DEFAULT_ROUTER_WAN, DEFAULT_ROUTER_LAN, TINC_INTERFACE};
 
 static mut EL: *mut Settings = 0 as *mut _;
 
@@ -31,6 +40,12 @@ pub struct Common {
     pub username:                           String,
     pub password:                           String,
     pub http_timeout:                       u32,
+    /// 当前使用的profile, 为None时使用common中的conductor_url和账号
+    pub profile:                            Option<String>,
+    /// 设备身份的解析顺序, 第一次解析到的身份保存后不再变化
+    pub identity_providers:                 Vec<IdentityProvider>,
+    /// 访问conductor使用的出口代理
+    pub egress_proxy:                       Option<EgressProxy>,
 }
 
 impl Common {
@@ -53,6 +68,9 @@ impl Common {
             username,
             password,
             http_timeout: HTTP_TIMEOUT,
+            profile: None,
+            identity_providers: Self::default_identity_providers(),
+            egress_proxy: None,
         })
     }
 
@@ -79,6 +97,173 @@ impl Common {
     fn default_log_level() -> String {
         DEFAULT_LOG_LEVEL.to_owned()
     }
+
+    fn default_identity_providers() -> Vec<IdentityProvider> {
+        #[cfg(target_arch = "arm")]
+            {
+                vec![IdentityProvider::VendorSn, IdentityProvider::MachineId, IdentityProvider::Generated]
+            }
+        #[cfg(not(target_arch = "arm"))]
+            {
+                vec![IdentityProvider::MachineId, IdentityProvider::Dmi, IdentityProvider::Generated]
+            }
+    }
+}
+
+/// 出口代理, http(CONNECT)或socks5
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EgressProxy {
+    pub proxy_type:                         String,
+    pub host:                               String,
+    pub port:                               u16,
+    pub username:                           String,
+    pub password:                           String,
+    /// 不使用代理的主机: 域名(含子域名), IP, CIDR, *表示全部. 未配置时读取环境变量NO_PROXY
+    pub no_proxy:                           Vec<String>,
+    /// tinc meta连接也通过代理(tinc.conf Proxy =)
+    pub tinc:                               bool,
+}
+
+impl EgressProxy {
+    fn parse(file_egress: FileEgressProxy) -> Result<Self> {
+        let proxy_type = file_egress.proxy_type
+            .unwrap_or("http".to_string())
+            .to_lowercase();
+        if proxy_type != "http" && proxy_type != "socks5" {
+            return Err(Error::Config("egress_proxy.proxy_type ".to_string() + &proxy_type));
+        }
+        let address = file_egress.address
+            .ok_or(Error::Config("egress_proxy.address".to_string()))?;
+        let (host, port) = Self::parse_address(&address)
+            .ok_or(Error::Config("egress_proxy.address ".to_string() + &address))?;
+        let no_proxy = file_egress.no_proxy
+            .unwrap_or_else(|| {
+                std::env::var("NO_PROXY")
+                    .or_else(|_|std::env::var("no_proxy"))
+                    .map(|no_proxy|no_proxy.split(',')
+                        .map(|host|host.trim().to_string())
+                        .filter(|host|!host.is_empty())
+                        .collect())
+                    .unwrap_or(vec![])
+            });
+
+        let egress_proxy = EgressProxy {
+            proxy_type,
+            host,
+            port,
+            username:   file_egress.username.unwrap_or(String::new()),
+            password:   file_egress.password.unwrap_or(String::new()),
+            no_proxy,
+            tinc:       file_egress.tinc.unwrap_or(false),
+        };
+        if egress_proxy.tinc {
+            // tinc.conf中以空格分隔
+            if egress_proxy.username.contains(char::is_whitespace)
+                || egress_proxy.password.contains(char::is_whitespace) {
+                return Err(Error::Config("egress_proxy username/password with whitespace".to_string()));
+            }
+            if egress_proxy.proxy_type == "http" && !egress_proxy.username.is_empty() {
+                warn!("tinc http proxy does not support authentication.");
+            }
+        }
+        Ok(egress_proxy)
+    }
+
+    /// host:port, IPv6为[addr]:port
+    fn parse_address(address: &str) -> Option<(String, u16)> {
+        let (host, port) = if address.starts_with('[') {
+            let end = address.find(']')?;
+            let rest = &address[end + 1..];
+            if !rest.starts_with(':') {
+                return None;
+            }
+            (&address[1..end], &rest[1..])
+        }
+        else {
+            let mut split = address.rsplitn(2, ':');
+            let port = split.next()?;
+            (split.next()?, port)
+        };
+        if host.is_empty() || host.contains(char::is_whitespace) {
+            return None;
+        }
+        Some((host.to_string(), port.parse::<u16>().ok().filter(|port|*port > 0)?))
+    }
+
+    /// reqwest使用的代理url, 账号按url编码
+    pub fn url(&self) -> String {
+        let auth = if self.username.is_empty() {
+            String::new()
+        }
+        else {
+            format!("{}:{}@", url_encode(&self.username), url_encode(&self.password))
+        };
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        }
+        else {
+            self.host.clone()
+        };
+        format!("{}://{}{}:{}", self.proxy_type, auth, host, self.port)
+    }
+
+    /// tinc.conf Proxy的值. 密码明文写入tinc.conf, tinc.conf权限为0600.
+    /// tinc的Proxy对所有meta连接生效, no_proxy不起作用
+    pub fn tinc_proxy(&self) -> String {
+        let mut proxy = format!("{} {} {}", self.proxy_type, self.host, self.port);
+        if self.proxy_type == "socks5" && !self.username.is_empty() {
+            proxy += &format!(" {} {}", self.username, self.password);
+        }
+        proxy
+    }
+
+    /// 访问host时是否绕过代理
+    pub fn bypass(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_lowercase();
+        let ip = host.parse::<IpAddr>().ok();
+        self.no_proxy.iter().any(|pattern| {
+            let pattern = pattern.trim().to_lowercase();
+            if pattern == "*" {
+                return true;
+            }
+            if let Some(ip) = ip {
+                if let Some((net, prefix)) = parse_cidr(&pattern) {
+                    return cidr_contains(&net, prefix, &ip);
+                }
+            }
+            let domain = pattern.trim_start_matches("*.").trim_start_matches('.');
+            !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+        })
+    }
+}
+
+fn url_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn parse_cidr(pattern: &str) -> Option<(IpAddr, u32)> {
+    let mut split = pattern.splitn(2, '/');
+    let net = split.next()?.parse::<IpAddr>().ok()?;
+    let bits = if net.is_ipv4() { 32 } else { 128 };
+    let prefix = match split.next() {
+        Some(prefix) => prefix.parse::<u32>().ok().filter(|prefix|*prefix <= bits)?,
+        None => bits,
+    };
+    Some((net, prefix))
+}
+
+fn cidr_contains(net: &IpAddr, prefix: u32, ip: &IpAddr) -> bool {
+    let (net, ip, bits) = match (net, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => (u32::from(*net) as u128, u32::from(*ip) as u128, 32),
+        (IpAddr::V6(net), IpAddr::V6(ip)) => (u128::from(*net), u128::from(*ip), 128),
+        _ => return false,
+    };
+    prefix == 0 || net >> (bits - prefix) == ip >> (bits - prefix)
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -89,6 +274,18 @@ pub struct Proxy {
     pub local_https_server_privkey_file:        String,
     pub proxy_type:                             String,
     pub public:                                 bool,
+    /// proxy按team维护tinc group, 隔离不同team的设备
+    pub team_isolation:                         bool,
+    /// 上报给conductor的最大连接数, 客户端以connections/capacity计算负载
+    pub capacity:                               Option<u32>,
+    /// 收到SIGTERM或dnet shutdown时先drain再停止
+    pub drain_on_shutdown:                      bool,
+    /// drain最长等待秒数
+    pub drain_timeout:                          u32,
+    /// 客户端连接数不超过该值时结束drain
+    pub drain_threshold:                        u32,
+    /// 检测并断开未经conductor确认的节点, 检测不在分配范围内的子网
+    pub violation_detection:                    bool,
 }
 
 impl Proxy {
@@ -100,6 +297,12 @@ impl Proxy {
             local_https_server_privkey_file:       String::new(),
             proxy_type:                            String::new(),
             public:                                DEFAULT_PROXY_PUBLIC,
+            team_isolation:                        false,
+            capacity:                              None,
+            drain_on_shutdown:                     false,
+            drain_timeout:                         DEFAULT_PROXY_DRAIN_TIMEOUT,
+            drain_threshold:                       0,
+            violation_detection:                   false,
         }
     }
 }
@@ -107,11 +310,48 @@ impl Proxy {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Client {
     pub auto_connect:                              bool,
+    /// dnet的INPUT只接受同team设备和proxy的流量
+    pub team_isolation_firewall:                   bool,
 }
 impl Client {
     fn default() -> Self {
         Client {
             auto_connect: false,
+            team_isolation_firewall: false,
+        }
+    }
+}
+
+/// 客户端选择proxy的评分策略
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProxySelection {
+    /// weighted: 综合rtt/丢包/负载/区域; rtt: 只比较rtt
+    pub policy:                                    String,
+    /// 每个proxy的ping次数
+    pub probe_count:                               u32,
+    pub rtt_weight:                                f64,
+    pub loss_weight:                               f64,
+    pub load_weight:                               f64,
+    /// 不在preferred_regions中的proxy的附加分
+    pub region_penalty:                            f64,
+    pub preferred_regions:                         Vec<String>,
+    /// 当前proxy的减分, 避免频繁切换
+    pub stickiness:                                f64,
+    /// proxy未上报capacity时使用
+    pub default_capacity:                          u32,
+}
+impl ProxySelection {
+    fn default() -> Self {
+        ProxySelection {
+            policy:                                DEFAULT_PROXY_SCORE_POLICY.to_string(),
+            probe_count:                           DEFAULT_PROXY_PROBE_COUNT,
+            rtt_weight:                            DEFAULT_PROXY_RTT_WEIGHT,
+            loss_weight:                           DEFAULT_PROXY_LOSS_WEIGHT,
+            load_weight:                           DEFAULT_PROXY_LOAD_WEIGHT,
+            region_penalty:                        DEFAULT_PROXY_REGION_PENALTY,
+            preferred_regions:                     vec![],
+            stickiness:                            DEFAULT_PROXY_STICKINESS,
+            default_capacity:                      DEFAULT_PROXY_CAPACITY,
         }
     }
 }
@@ -124,6 +364,9 @@ pub struct Tinc {
     pub tinc_allowed_out_memory_times:             u32,
     pub tinc_allowed_tcp_failed_times:             u32,
     pub external_boot:                             bool,
+    pub key_max_age_days:                          u32,
+    pub ed25519_key:                               bool,
+    pub extra:                                     BTreeMap<String, String>,
 }
 impl Tinc {
     fn default() -> Self {
@@ -134,6 +377,51 @@ impl Tinc {
             tinc_allowed_out_memory_times:         0,
             tinc_allowed_tcp_failed_times:         0,
             external_boot:                         false,
+            key_max_age_days:                      DEFAULT_KEY_MAX_AGE_DAYS,
+            ed25519_key:                           false,
+            extra:                                 BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Hooks {
+    pub webhooks:                                  Vec<String>,
+    pub timeout:                                   u32,
+    pub max_concurrency:                           u32,
+}
+impl Hooks {
+    fn default() -> Self {
+        Hooks {
+            webhooks:                              vec![],
+            timeout:                               DEFAULT_HOOK_TIMEOUT,
+            max_concurrency:                       DEFAULT_HOOK_MAX_CONCURRENCY,
+        }
+    }
+}
+
+/// 路由器防火墙使用的接口
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Router {
+    pub wan:                                       Vec<String>,
+    pub lan:                                       Vec<String>,
+    pub tunnel:                                    String,
+}
+impl Router {
+    fn default() -> Self {
+        Router {
+            wan:    DEFAULT_ROUTER_WAN.iter().map(|dev|dev.to_string()).collect(),
+            lan:    DEFAULT_ROUTER_LAN.iter().map(|dev|dev.to_string()).collect(),
+            tunnel: TINC_INTERFACE.to_string(),
+        }
+    }
+
+    #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+    pub fn firewall_config(&self) -> router_plugin::firewall::FirewallConfig {
+        router_plugin::firewall::FirewallConfig {
+            wan:    self.wan.clone(),
+            lan:    self.lan.clone(),
+            tunnel: self.tunnel.clone(),
         }
     }
 }
@@ -143,7 +431,11 @@ pub struct Settings {
     pub common:         Common,
     pub proxy:          Proxy,
     pub client:         Client,
+    pub proxy_selection: ProxySelection,
     pub tinc:           Tinc,
+    pub hooks:          Hooks,
+    pub router:         Router,
+    pub profiles:       BTreeMap<String, Profile>,
     pub last_runtime:   String,
 }
 
@@ -154,6 +446,11 @@ impl Settings {
                 Self::parse_file_settings(file_seting)
             })?;
 
+        #[cfg(any(not(target_arch = "arm"), feature = "router_debug"))]
+            settings.migrate_plain_password(config_dir);
+        settings.load_credentials();
+        settings.load_profiles()?;
+
         let now = chrono::Utc::now().to_string();
         settings.last_runtime = now;
 
@@ -163,7 +460,7 @@ impl Settings {
         Ok(())
     }
 
-    fn parse_file_settings(file_settings: FileSettings) -> Result<Self> {
+    pub(super) fn parse_file_settings(file_settings: FileSettings) -> Result<Self> {
         let common = file_settings.common
             .ok_or(Error::NoneError)
             .and_then(|file_common| {
@@ -215,6 +512,22 @@ impl Settings {
                     }
 
                 let http_timeout = file_common.http_timeout.unwrap_or(HTTP_TIMEOUT);
+                let profile = file_common.profile;
+                let identity_providers = match file_common.identity_providers {
+                    Some(providers) => {
+                        let mut identity_providers = vec![];
+                        for provider in providers {
+                            identity_providers.push(provider.parse::<IdentityProvider>()
+                                .map_err(|e|Error::Config("identity_providers ".to_string() + &e))?);
+                        }
+                        identity_providers
+                    }
+                    None => Common::default_identity_providers(),
+                };
+                let egress_proxy = match file_common.egress_proxy {
+                    Some(file_egress) => Some(EgressProxy::parse(file_egress)?),
+                    None => None,
+                };
                 Ok(Common {
                     accept_conductor_invalid_certs,
                     conductor_url,
@@ -225,9 +538,16 @@ impl Settings {
                     username,
                     password,
                     http_timeout,
+                    profile,
+                    identity_providers,
+                    egress_proxy,
                 })
         })
-            .unwrap_or(Common::default()?);
+            .or_else(|e| match e {
+                // 配置错误不使用默认值
+                Error::Config(_) => Err(e),
+                _ => Common::default(),
+            })?;
 
         let proxy = {
             if common.mode == RunMode::Proxy || common.mode == RunMode::Center {
@@ -264,6 +584,16 @@ impl Settings {
                             DEFAULT_PROXY_PUBLIC.to_owned()
                         );
 
+                        let team_isolation = file_proxy.team_isolation.unwrap_or(false);
+
+                        let capacity = file_proxy.capacity.filter(|capacity|*capacity > 0);
+
+                        let drain_on_shutdown = file_proxy.drain_on_shutdown.unwrap_or(false);
+                        let drain_timeout = file_proxy.drain_timeout
+                            .unwrap_or(DEFAULT_PROXY_DRAIN_TIMEOUT);
+                        let drain_threshold = file_proxy.drain_threshold.unwrap_or(0);
+                        let violation_detection = file_proxy.violation_detection.unwrap_or(false);
+
                         Ok(Proxy {
                             local_ip,
                             local_port,
@@ -271,6 +601,12 @@ impl Settings {
                             local_https_server_certificate_file,
                             proxy_type,
                             public,
+                            team_isolation,
+                            capacity,
+                            drain_on_shutdown,
+                            drain_timeout,
+                            drain_threshold,
+                            violation_detection,
                         })
                 })?
             } else {
@@ -293,8 +629,11 @@ impl Settings {
                         .map(|_|false)
                         .unwrap_or(DEFAULT_CLIENT_AUTO_CONNECT.to_owned());
 
+                    let team_isolation_firewall = file_client.team_isolation_firewall.unwrap_or(false);
+
                     Client {
                         auto_connect,
+                        team_isolation_firewall,
                     }
                 })
                     .unwrap_or(Client::default())
@@ -304,8 +643,37 @@ impl Settings {
             }
         };
 
+        let proxy_selection = file_settings.proxy_selection
+            .map(|file_selection| -> Result<ProxySelection> {
+                let default = ProxySelection::default();
+                let policy = file_selection.policy.unwrap_or(default.policy).to_lowercase();
+                if policy != "weighted" && policy != "rtt" {
+                    return Err(Error::Config("proxy_selection.policy ".to_string() + &policy));
+                }
+                let selection = ProxySelection {
+                    policy,
+                    probe_count:        file_selection.probe_count.unwrap_or(default.probe_count).max(1),
+                    rtt_weight:         file_selection.rtt_weight.unwrap_or(default.rtt_weight),
+                    loss_weight:        file_selection.loss_weight.unwrap_or(default.loss_weight),
+                    load_weight:        file_selection.load_weight.unwrap_or(default.load_weight),
+                    region_penalty:     file_selection.region_penalty.unwrap_or(default.region_penalty),
+                    preferred_regions:  file_selection.preferred_regions.unwrap_or(default.preferred_regions),
+                    stickiness:         file_selection.stickiness.unwrap_or(default.stickiness),
+                    default_capacity:   file_selection.default_capacity
+                        .filter(|capacity|*capacity > 0)
+                        .unwrap_or(default.default_capacity),
+                };
+                let weights = [selection.rtt_weight, selection.loss_weight, selection.load_weight,
+                    selection.region_penalty, selection.stickiness];
+                if weights.iter().any(|weight|!weight.is_finite() || *weight < 0.0) {
+                    return Err(Error::Config("proxy_selection weights must be >= 0".to_string()));
+                }
+                Ok(selection)
+            })
+            .unwrap_or(Ok(ProxySelection::default()))?;
+
         let tinc = file_settings.tinc
-            .and_then(|file_settings| {
+            .map(|file_settings| -> Result<Tinc> {
                 let port = file_settings.port
                     .unwrap_or(DEFAULT_TINC_PORT);
                 let tinc_memory_limit = file_settings.tinc_memory_limit
@@ -323,23 +691,88 @@ impl Settings {
                 let external_boot = file_settings.external_boot
                     .and_then(|x|x.parse::<bool>().ok())
                     .unwrap_or(false);
+                let key_max_age_days = file_settings.key_max_age_days
+                    .and_then(|x|x.parse::<u32>().ok())
+                    .unwrap_or(DEFAULT_KEY_MAX_AGE_DAYS);
+                let ed25519_key = file_settings.ed25519_key
+                    .and_then(|x|x.parse::<bool>().ok())
+                    .unwrap_or(false);
+                let extra = file_settings.extra
+                    .map(|extra| TincConfig::validate_extra(&extra)
+                        .map_err(|e|Error::Config(e.to_string())))
+                    .unwrap_or(Ok(BTreeMap::new()))?;
 
-                Some(Tinc {
+                Ok(Tinc {
                     port,
                     tinc_memory_limit,
                     tinc_check_frequency,
                     tinc_allowed_out_memory_times,
                     tinc_allowed_tcp_failed_times,
                     external_boot,
+                    key_max_age_days,
+                    ed25519_key,
+                    extra,
+                })
+            })
+            .unwrap_or(Ok(Tinc::default()))?;
+
+        let hooks = file_settings.hooks
+            .map(|file_hooks| -> Result<Hooks> {
+                let webhooks = file_hooks.webhooks.unwrap_or(vec![]);
+                for webhook in &webhooks {
+                    url::Url::parse(webhook)
+                        .map_err(|_|Error::Config("hooks.webhooks ".to_string() + webhook))?;
+                }
+                Ok(Hooks {
+                    webhooks,
+                    timeout: file_hooks.timeout.unwrap_or(DEFAULT_HOOK_TIMEOUT),
+                    max_concurrency: file_hooks.max_concurrency
+                        .unwrap_or(DEFAULT_HOOK_MAX_CONCURRENCY),
                 })
             })
-            .unwrap_or(Tinc::default());
+            .unwrap_or(Ok(Hooks::default()))?;
+
+        let router = file_settings.router
+            .map(|file_router| -> Result<Router> {
+                let default = Router::default();
+                let router = Router {
+                    wan:    file_router.wan.unwrap_or(default.wan),
+                    lan:    file_router.lan.unwrap_or(default.lan),
+                    tunnel: file_router.tunnel.unwrap_or(default.tunnel),
+                };
+                for dev in router.wan.iter().chain(router.lan.iter()).chain(Some(&router.tunnel)) {
+                    if dev.is_empty() || dev.contains(char::is_whitespace) {
+                        return Err(Error::Config("router interface ".to_string() + dev));
+                    }
+                }
+                Ok(router)
+            })
+            .unwrap_or(Ok(Router::default()))?;
+
+        let mut profiles = BTreeMap::new();
+        for (name, file_profile) in file_settings.profiles.unwrap_or(BTreeMap::new()) {
+            let profile = Profile {
+                name:           name.clone(),
+                conductor_url:  file_profile.conductor_url.unwrap_or(String::new()),
+                username:       file_profile.username.unwrap_or(String::new()),
+                password:       file_profile.password.unwrap_or(String::new()),
+                accept_conductor_invalid_certs:
+                    file_profile.accept_conductor_invalid_certs.unwrap_or(false),
+                from_file:      true,
+            };
+            Self::check_profile(&profile)?;
+            profiles.insert(name, profile);
+        }
 
         Ok(Self {
             common,
             proxy,
             client,
+            proxy_selection,
             tinc,
+            hooks,
+            router,
+            profiles,
             last_runtime: String::new(),
         })
     }
@@ -398,4 +831,27 @@ fn test_setting() {
         })
         .expect("Error: Can not parse settings.");
     let _settings = get_settings();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_egress_proxy() {
+    let egress_proxy = EgressProxy::parse(FileEgressProxy {
+        proxy_type: Some("SOCKS5".to_string()),
+        address:    Some("[fd00::1]:1080".to_string()),
+        username:   Some("user".to_string()),
+        password:   Some("p@ss".to_string()),
+        no_proxy:   Some(vec!["localhost".to_string(), ".corp.local".to_string(), "10.0.0.0/8".to_string()]),
+        tinc:       Some(true),
+    }).unwrap();
+    assert_eq!(egress_proxy.url(), "socks5://user:p%40ss@[fd00::1]:1080");
+    assert_eq!(egress_proxy.tinc_proxy(), "socks5 fd00::1 1080 user p@ss");
+    assert!(egress_proxy.bypass("localhost"));
+    assert!(egress_proxy.bypass("api.corp.local"));
+    assert!(egress_proxy.bypass("10.1.2.3"));
+    assert!(!egress_proxy.bypass("huayi-api.vlan.cn"));
+    assert!(!egress_proxy.bypass("11.0.0.1"));
+
+    assert_eq!(EgressProxy::parse_address("proxy.corp.local:3128"), Some(("proxy.corp.local".to_string(), 3128)));
+    assert_eq!(EgressProxy::parse_address("proxy.corp.local"), None);
+    assert_eq!(EgressProxy::parse_address("[fd00::1]1080"), None);
+}
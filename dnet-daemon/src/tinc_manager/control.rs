@@ -1,4 +1,4 @@
-use tinc_plugin::tinc_tcp_stream::{TincStream, Result, Error};
+use tinc_plugin::tinc_tcp_stream::{TincStream, SourceSubnet, Result, Error};
 
 pub fn tinc_connections(pid_path: &str) -> Result<(u32, u32, u32)> {
     if !std::path::Path::new(pid_path).is_file() {
@@ -10,4 +10,55 @@ pub fn tinc_connections(pid_path: &str) -> Result<(u32, u32, u32)> {
     let edges = tinc_stream.dump_edges()?;
     let nodes = tinc_stream.dump_nodes()?;
     return Ok((connections.len() as u32, edges.len() as u32, nodes.len() as u32));
-}
\ No newline at end of file
+}
+
+/// 直连节点名, 包括控制连接<control>
+pub fn connection_nodes(pid_path: &str) -> Result<Vec<String>> {
+    if !std::path::Path::new(pid_path).is_file() {
+        return Err(Error::pid_path);
+    }
+
+    let connections = TincStream::new(pid_path)?.dump_connections()?;
+    Ok(connections.into_iter().map(|connection|connection.node).collect())
+}
+
+/// 网络变化后立即重连, 不等待重试间隔
+pub fn retry_connections(pid_path: &str) -> Result<()> {
+    if !std::path::Path::new(pid_path).is_file() {
+        return Err(Error::pid_path);
+    }
+
+    TincStream::new(pid_path)?.retry()
+}
+
+/// 断开节点的连接, 并purge清除不可达的节点
+pub fn kick_node(pid_path: &str, name: &str) -> Result<()> {
+    if !std::path::Path::new(pid_path).is_file() {
+        return Err(Error::pid_path);
+    }
+
+    // 节点没有直连时disconnect失败, 仍然需要purge
+    let res = TincStream::new(pid_path)?.disconnect_node(name);
+    TincStream::new(pid_path)?.purge()?;
+    res
+}
+
+/// tinc当前的节点和子网, 返回(本节点名, 其他节点名, 子网)
+pub fn nodes_and_subnets(pid_path: &str) -> Result<(String, Vec<String>, Vec<SourceSubnet>)> {
+    if !std::path::Path::new(pid_path).is_file() {
+        return Err(Error::pid_path);
+    }
+
+    let mut own = String::new();
+    let mut nodes = vec![];
+    for node in TincStream::new(pid_path)?.dump_nodes()? {
+        if node.host == "MYSELF" {
+            own = node.node;
+        }
+        else {
+            nodes.push(node.node);
+        }
+    }
+    let subnets = TincStream::new(pid_path)?.dump_subnets()?;
+    Ok((own, nodes, subnets))
+}
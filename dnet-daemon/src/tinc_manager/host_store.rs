@@ -0,0 +1,255 @@
+//! proxy上客户端host文件的记录, 保存为 tinc/host_store.json.
+//! 每个host文件记录最近一次由conductor确认(keyreport或getAllDevicePubkeys)的时间,
+//! 全量同步后删除不在conductor设备列表中且超过宽限期未确认的host文件.
+//! 吊销的公钥删除host文件, 并通过控制端口断开连接, purge清除不可达的节点.
+//! 吊销的公钥不会由state.json恢复, 全量同步时删除仍使用吊销公钥的host文件.
+//! actix worker和monitor线程都会修改host_store.json, 所有读-改-写都在同一个锁内完成.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard, Once};
+use std::time::UNIX_EPOCH;
+
+use tinc_plugin::{TincTools, PID_FILENAME};
+use dnet_types::team::NetSegment;
+
+use crate::settings::get_settings;
+use super::{TincOperator, control};
+
+const HOST_STORE_FILENAME: &str = "host_store.json";
+/// 不在全量列表中的host文件在最近一次确认后保留的时间, 避免删除全量同步之后才上报的设备
+const HOST_GC_GRACE_SECS: i64 = 600;
+
+static HOST_STORE_LOCK_INIT: Once = Once::new();
+static mut HOST_STORE_LOCK: *const Mutex<()> = 0 as *const _;
+
+fn lock_store() -> MutexGuard<'static, ()> {
+    unsafe {
+        HOST_STORE_LOCK_INIT.call_once(|| HOST_STORE_LOCK = Box::into_raw(Box::new(Mutex::new(()))));
+        (*HOST_STORE_LOCK).lock().unwrap_or_else(|e|e.into_inner())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RevokedKey {
+    pub vip:            IpAddr,
+    pub pubkey:         String,
+    pub revoked_at:     i64,
+}
+
+impl RevokedKey {
+    pub fn matches(&self, vip: &IpAddr, pubkey: &str) -> bool {
+        &self.vip == vip && self.pubkey.trim() == pubkey.trim()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HostStore {
+    /// host文件名 -> 最近确认时间(unix秒)
+    confirmed:  BTreeMap<String, i64>,
+    revoked:    Vec<RevokedKey>,
+    /// host文件名 -> conductor分配的LAN
+    #[serde(default)]
+    lans:       BTreeMap<String, Vec<NetSegment>>,
+}
+
+impl HostStore {
+    fn load() -> Self {
+        fs::read_to_string(tinc_path(HOST_STORE_FILENAME)).ok()
+            .and_then(|buf|serde_json::from_str(&buf)
+                .map_err(|e|error!("load host store {:?}", e))
+                .ok())
+            .unwrap_or(HostStore::default())
+    }
+
+    fn save(&self) {
+        let path = tinc_path(HOST_STORE_FILENAME);
+        let tmp = path.with_extension("tmp");
+        let res = serde_json::to_string(self)
+            .map_err(|e|e.to_string())
+            .and_then(|buf|fs::write(&tmp, buf).map_err(|e|e.to_string()))
+            .and_then(|_|fs::rename(&tmp, &path).map_err(|e|e.to_string()));
+        if let Err(e) = res {
+            error!("save host store {}", e);
+        }
+    }
+}
+
+/// 公钥是否已被吊销
+pub fn is_revoked(vip: &IpAddr, pubkey: &str) -> bool {
+    revoked_keys().iter().any(|revoked|revoked.matches(vip, pubkey))
+}
+
+pub fn revoked_keys() -> Vec<RevokedKey> {
+    let _guard = lock_store();
+    HostStore::load().revoked
+}
+
+/// 记录conductor确认过的设备
+pub fn confirm(vips: &[IpAddr]) {
+    let _guard = lock_store();
+    let mut store = HostStore::load();
+    let now = chrono::Utc::now().timestamp();
+    for vip in vips {
+        store.confirmed.insert(host_name(vip), now);
+    }
+    store.save();
+}
+
+/// 全量同步的设备LAN
+pub fn set_lans(lans: &[(IpAddr, Vec<NetSegment>)]) {
+    let _guard = lock_store();
+    let mut store = HostStore::load();
+    store.lans = lans.iter()
+        .filter(|(_, lan)|!lan.is_empty())
+        .map(|(vip, lan)|(host_name(vip), lan.clone()))
+        .collect();
+    store.save();
+}
+
+/// host文件名 -> conductor分配的LAN
+pub fn lans() -> BTreeMap<String, Vec<NetSegment>> {
+    let _guard = lock_store();
+    HostStore::load().lans
+}
+
+/// 全量同步后删除不在present中且超过宽限期的客户端host文件, 以及仍使用吊销公钥的host文件,
+/// 返回删除的文件名
+pub fn gc(present: &[IpAddr]) -> Vec<String> {
+    let _guard = lock_store();
+    let mut store = HostStore::load();
+    let now = chrono::Utc::now().timestamp();
+    let present: HashSet<String> = present.iter().map(host_name).collect();
+    for name in &present {
+        store.confirmed.insert(name.clone(), now);
+    }
+
+    let files = client_host_files();
+    let tinc = TincOperator::new();
+    let mut stale = stale_hosts(&store.confirmed, &files, &present, now);
+    for (name, _) in &files {
+        let revoked = TincTools::get_vip_by_filename(name)
+            .and_then(|vip|tinc.get_host_pub_key(name).ok()
+                .map(|pubkey|store.revoked.iter().any(|key|key.matches(&vip, &pubkey))))
+            .unwrap_or(false);
+        if revoked && !stale.contains(name) {
+            stale.push(name.clone());
+        }
+    }
+    for name in &stale {
+        info!("remove stale host {}", name);
+        tinc.remove_host(name);
+        store.confirmed.remove(name);
+    }
+    // 已不存在的host文件不再记录
+    store.confirmed.retain(|name, _|files.iter().any(|(file, _)|file == name));
+    store.save();
+    stale
+}
+
+/// 吊销公钥, pubkey为None时吊销当前host文件中的公钥.
+/// 删除host文件后断开该节点, 已经在线的会话立即失效.
+pub fn revoke(vip: IpAddr, pubkey: Option<String>) -> Option<RevokedKey> {
+    let name = host_name(&vip);
+    let tinc = TincOperator::new();
+    let pubkey = pubkey.or_else(||tinc.get_host_pub_key(&name).ok())?;
+
+    let revoked = RevokedKey {
+        vip,
+        pubkey:     pubkey.trim().to_string(),
+        revoked_at: chrono::Utc::now().timestamp(),
+    };
+    {
+        let _guard = lock_store();
+        let mut store = HostStore::load();
+        if !store.revoked.iter().any(|key|key.matches(&revoked.vip, &revoked.pubkey)) {
+            store.revoked.push(revoked.clone());
+        }
+        store.confirmed.remove(&name);
+        store.save();
+    }
+
+    if tinc.get_host_pub_key(&name).map(|current|current.trim() == revoked.pubkey).unwrap_or(false) {
+        tinc.remove_host(&name);
+    }
+    if let Err(e) = control::kick_node(&tinc_path(PID_FILENAME).to_string_lossy(), &name) {
+        warn!("kick revoked host {} {:?}", name, e);
+    }
+    info!("revoked host {} key", name);
+    Some(revoked)
+}
+
+fn stale_hosts(
+    confirmed:  &BTreeMap<String, i64>,
+    files:      &[(String, i64)],
+    present:    &HashSet<String>,
+    now:        i64,
+) -> Vec<String> {
+    files.iter()
+        .filter(|(name, _)|!present.contains(name))
+        .filter(|(name, modified)| {
+            // 没有记录的host文件以修改时间作为最近确认时间
+            let last = confirmed.get(name).cloned().unwrap_or(*modified);
+            now - last > HOST_GC_GRACE_SECS
+        })
+        .map(|(name, _)|name.clone())
+        .collect()
+}
+
+/// 客户端host文件及修改时间, 不包含proxy的host文件
+fn client_host_files() -> Vec<(String, i64)> {
+    let dir = match fs::read_dir(tinc_path("hosts")) {
+        Ok(dir) => dir,
+        Err(_) => return vec![],
+    };
+    dir.filter_map(|entry|entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            if name.starts_with("proxy") || name == "vpnserver"
+                || TincTools::get_vip_by_filename(&name).is_none() {
+                return None;
+            }
+            let modified = entry.metadata().ok()?
+                .modified().ok()?
+                .duration_since(UNIX_EPOCH).ok()?
+                .as_secs() as i64;
+            Some((name, modified))
+        })
+        .collect()
+}
+
+fn host_name(vip: &IpAddr) -> String {
+    TincTools::get_filename_by_vip(false, &vip.to_string())
+}
+
+fn tinc_path(filename: &str) -> PathBuf {
+    get_settings().common.home_path.join("tinc").join(filename)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stale_hosts() {
+        let now = 100_000;
+        let mut confirmed = BTreeMap::new();
+        confirmed.insert("253_0_2".to_string(), now - 10);
+        confirmed.insert("253_0_3".to_string(), now - HOST_GC_GRACE_SECS - 10);
+        let files = vec![
+            ("253_0_2".to_string(), 0),
+            ("253_0_3".to_string(), now),
+            ("253_0_4".to_string(), 0),
+            ("253_0_5".to_string(), now),
+            ("253_0_6".to_string(), 0),
+        ];
+        let mut present = HashSet::new();
+        present.insert("253_0_6".to_string());
+
+        // 最近确认的和刚写入的保留, 在全量列表中的保留
+        assert_eq!(stale_hosts(&confirmed, &files, &present, now),
+                   vec!["253_0_3".to_string(), "253_0_4".to_string()]);
+    }
+}
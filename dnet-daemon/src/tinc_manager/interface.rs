@@ -0,0 +1,67 @@
+use std::net::IpAddr;
+
+use dnet_types::settings::RunMode;
+
+use crate::info::get_info;
+use crate::settings::get_settings;
+use crate::settings::default_settings::TINC_INTERFACE;
+
+/// daemon在TincUp时对dnet网卡做的配置, TincDown时按此撤销
+#[derive(Debug, Clone)]
+pub struct TincInterface {
+    pub vip:        IpAddr,
+    /// CIDR
+    pub netmask:    u32,
+    pub mtu:        Option<u32>,
+    pub routes:     Vec<IpAddr>,
+}
+
+impl TincInterface {
+    /// proxy/center使用/8; client使用/32, 并为连接的proxy添加主机路由.
+    /// MTU取自 [tinc.extra] MTU/PMTU, 未配置时不修改.
+    pub fn from_info() -> Option<Self> {
+        let info = get_info().lock().unwrap();
+        let vip = info.tinc_info.vip?;
+        let settings = get_settings();
+        let (netmask, routes) = match settings.common.mode {
+            RunMode::Proxy | RunMode::Center => (8, vec![]),
+            RunMode::Client => (32, info.tinc_info.connect_to.iter()
+                .map(|connect_to|connect_to.vip)
+                .collect()),
+        };
+        let mtu = settings.tinc.extra.get("MTU")
+            .or(settings.tinc.extra.get("PMTU"))
+            .and_then(|mtu|mtu.parse().ok());
+
+        Some(Self {
+            vip,
+            netmask,
+            mtu,
+            routes,
+        })
+    }
+
+    pub fn configure(&self) -> sandbox::interface::error::Result<()> {
+        info!("configure {} {:?}", TINC_INTERFACE, self);
+        sandbox::interface::set_interface(TINC_INTERFACE, &self.vip, self.netmask, self.mtu)?;
+        for route in &self.routes {
+            #[cfg(target_os = "linux")]
+                sandbox::route::add_route(route, 32, Some(TINC_INTERFACE.to_string()), None);
+            #[cfg(windows)]
+                sandbox::route::add_route(route, 32, TINC_INTERFACE);
+        }
+        Ok(())
+    }
+
+    /// tincd退出时网卡会被删除, 这里只保证tincd未退出的情况下状态被清理
+    pub fn undo(&self) {
+        info!("clear {} {:?}", TINC_INTERFACE, self);
+        for route in &self.routes {
+            sandbox::route::del_route(route, 32, TINC_INTERFACE);
+        }
+        if let Err(e) = sandbox::interface::clear_interface(
+            TINC_INTERFACE, &self.vip, self.netmask) {
+            warn!("clear {} {:?}", TINC_INTERFACE, e);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::Debug;
+
+use tinc_plugin::TincOperatorError;
+
+use crate::info::get_mut_info;
+use super::TincOperator;
+
+pub type Result<T> = std::result::Result<T, TincOperatorError>;
+
+/// 密钥超过 key_max_age_days 时轮换本地密钥对, 并通过report(device_add/proxy_add)上报新公钥.
+/// 上报失败时恢复旧密钥对, 成功后删除旧密钥对. 没有新旧密钥重叠期, 对端更新host文件前连接会中断.
+/// 返回true表示已轮换, 调用方需重启tinc,
+/// tincd只在启动时读取私钥, 重启时set_info_to_local会同时更新本地host文件.
+pub fn rotate_key_if_expired<F, E>(report: F) -> Result<bool>
+    where F: FnOnce() -> std::result::Result<(), E>,
+          E: Debug,
+{
+    let tinc = TincOperator::new();
+    // 清理上次轮换中断留下的旧密钥对
+    tinc.clean_old_key_pair();
+    if !tinc.need_rotate_key_pair() {
+        return Ok(false);
+    }
+
+    info!("tinc key pair expired, rotate.");
+    // rotate_self_key_pair 失败时已恢复旧密钥对
+    tinc.rotate_self_key_pair()?;
+    let (pub_key, ed25519_pub_key) = match tinc.get_pub_key()
+        .and_then(|pub_key| Ok((pub_key, tinc.get_ed25519_pub_key()?))) {
+        Ok(keys) => keys,
+        Err(e) => {
+            restore_old_key_pair(&tinc);
+            return Err(e);
+        }
+    };
+    let (old_pub_key, old_ed25519_pub_key) = {
+        let mut info = get_mut_info().lock().unwrap();
+        (std::mem::replace(&mut info.tinc_info.pub_key, pub_key),
+         std::mem::replace(&mut info.tinc_info.ed25519_pub_key, ed25519_pub_key))
+    };
+
+    if let Err(e) = report() {
+        error!("report rotated key {:?}", e);
+        restore_old_key_pair(&tinc);
+        let mut info = get_mut_info().lock().unwrap();
+        info.tinc_info.pub_key = old_pub_key;
+        info.tinc_info.ed25519_pub_key = old_ed25519_pub_key;
+        return Err(TincOperatorError::KeyRotationError(format!("{:?}", e)));
+    }
+    tinc.clean_old_key_pair();
+    info!("tinc key pair rotated.");
+    Ok(true)
+}
+
+fn restore_old_key_pair(tinc: &TincOperator) {
+    if let Err(e) = tinc.restore_old_key_pair() {
+        error!("restore_old_key_pair {:?}", e);
+    }
+}
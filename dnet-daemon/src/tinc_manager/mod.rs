@@ -1,9 +1,18 @@
 //! tinc相关的操作
 
 mod control;
+#[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+pub mod host_store;
+pub mod interface;
+pub mod key_rotation;
 pub mod operator;
+#[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+pub mod violation_detection;
+pub mod team_firewall;
+pub mod team_reconcile;
 mod tinc_monitor;
 
-pub use self::control::tinc_connections;
+pub use self::control::{tinc_connections, connection_nodes, retry_connections};
+pub use self::interface::TincInterface;
 pub use self::operator::TincOperator;
 pub use self::tinc_monitor::TincMonitor;
@@ -1,9 +1,10 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
 use std::net::IpAddr;
 
 use tinc_plugin::{TincRunMode, TincOperator as PluginTincOperator,
-                  TincOperatorError, TincTools, TincSettings};
+                  TincOperatorError, TincTools, TincSettings, TincTeam, ConfigChange};
 use dnet_types::settings::RunMode;
 
 //#[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
@@ -11,6 +12,7 @@ use dnet_types::settings::RunMode;
 
 use crate::info::{get_info, get_mut_info};
 use crate::settings::get_settings;
+use super::team_reconcile;
 
 pub type Result<T> = std::result::Result<T, TincOperatorError>;
 
@@ -29,6 +31,17 @@ impl TincOperator {
                 RunMode::Center => TincRunMode::Center,
             };
             let port = settings.tinc.port;
+            let mut extra = settings.tinc.extra.clone();
+            // 开启违规检测的proxy只连接ConnectTo中的proxy
+            if settings.common.mode != RunMode::Client && settings.proxy.violation_detection {
+                extra.retain(|key, _|!key.eq_ignore_ascii_case("AutoConnect"));
+                extra.insert("AutoConnect".to_string(), "no".to_string());
+            }
+            // meta连接通过出口代理, 包括no_proxy中的地址(如局域网内的proxy)
+            if let Some(egress_proxy) = settings.common.egress_proxy.as_ref().filter(|egress|egress.tinc) {
+                extra.retain(|key, _|!key.eq_ignore_ascii_case("Proxy"));
+                extra.insert("Proxy".to_string(), egress_proxy.tinc_proxy());
+            }
             let tinc_settings = TincSettings {
                 tinc_home,
                 mode: tinc_run_model,
@@ -38,6 +51,9 @@ impl TincOperator {
                 tinc_allowed_tcp_failed_times: settings.tinc.tinc_allowed_tcp_failed_times,
                 tinc_check_frequency: settings.tinc.tinc_check_frequency,
                 external_boot: settings.tinc.external_boot,
+                key_max_age_days: settings.tinc.key_max_age_days,
+                ed25519_key: settings.tinc.ed25519_key,
+                extra,
             };
 
             PluginTincOperator::new(tinc_settings);
@@ -47,9 +63,14 @@ impl TincOperator {
 
     pub fn init(&self) -> Result<()> {
         self.create_tinc_dirs()?;
+        PluginTincOperator::instance().recover_config()?;
         if !self.check_pub_key() {
             self.create_self_key_pair()?;
         }
+        // 已有RSA密钥的节点开启ed25519_key后补充生成Ed25519密钥
+        if !PluginTincOperator::instance().check_ed25519_key() {
+            PluginTincOperator::instance().create_self_ed25519_key_pair()?;
+        }
         Ok(())
     }
 
@@ -62,11 +83,12 @@ impl TincOperator {
                     .clone()
                     .ok_or(TincOperatorError::TincInfoProxyVipNotFound)?;
                 std::mem::drop(info);
-                router_plugin::firewall::start_tunnel_firewall(&local_vip);
+                router_plugin::firewall::start_tunnel_firewall(
+                    &get_settings().router.firewall_config(), &local_vip);
             }
 
         self.set_info_to_local()?;
-//        self.set_tinc_team_init_file()?;
+        self.set_tinc_team_init_file()?;
 
         PluginTincOperator::mut_instance().start_tinc()?;
         let now = chrono::Utc::now().to_string();
@@ -74,18 +96,26 @@ impl TincOperator {
         return Ok(());
     }
 
+    /// 使用最近一次对账下发的team状态生成tinc.group, tinc启动后即有正确的group
     fn set_tinc_team_init_file(&self) -> Result<()> {
         let run_mode = &get_settings().common.mode;
 
-        if *run_mode == RunMode::Proxy || *run_mode == RunMode::Client {
+        if *run_mode == RunMode::Client
+            || (*run_mode == RunMode::Proxy && !get_settings().proxy.team_isolation) {
             return Ok(());
         }
 
         let team_file = get_settings().common.home_path
             .join("tinc").join("tinc.group")
             .to_str().unwrap().to_string();
-        let tinc_team = get_info().lock().unwrap()
-            .teams.to_tinc_team();
+        let tinc_team = match team_reconcile::load_team_state() {
+            Some(state) => TincTeam {
+                add:    state,
+                delete: HashMap::new(),
+            },
+            None => get_info().lock().unwrap()
+                .teams.to_tinc_team(),
+        };
         tinc_team.set_tinc_init_file(&team_file)
     }
 
@@ -196,6 +226,23 @@ impl TincOperator {
         PluginTincOperator::instance().check_pub_key()
     }
 
+    pub fn need_rotate_key_pair(&self) -> bool {
+        PluginTincOperator::instance().need_rotate_key_pair()
+    }
+
+    /// 备份旧密钥对并生成新的密钥对
+    pub fn rotate_self_key_pair(&self) -> Result<()> {
+        PluginTincOperator::instance().rotate_self_key_pair()
+    }
+
+    pub fn restore_old_key_pair(&self) -> Result<()> {
+        PluginTincOperator::instance().restore_old_key_pair()
+    }
+
+    pub fn clean_old_key_pair(&self) {
+        PluginTincOperator::instance().clean_old_key_pair()
+    }
+
     pub fn check_tinc_status(&mut self) -> Result<()> {
         let tinc = PluginTincOperator::mut_instance();
         tinc.check_tinc_status()?;
@@ -211,22 +258,49 @@ impl TincOperator {
 
     pub fn restart_tinc(&mut self) -> Result<()> {
         self.set_info_to_local()?;
-//        self.set_tinc_team_init_file()?;
+        self.set_tinc_team_init_file()?;
         PluginTincOperator::mut_instance().restart_tinc()?;
         let now = chrono::Utc::now().to_string();
         get_mut_info().lock().unwrap().tinc_info.last_runtime = Some(now);
         Ok(())
     }
 
+    /// 写入最新配置, 能reload的变更不重启tinc, 已有连接不中断
+    pub fn apply_config(&mut self) -> Result<()> {
+        let tinc_info = get_info().lock().unwrap()
+            .to_plugin_tinc_info()
+            .map_err(|_|TincOperatorError::TincInfoError("Vip is None.".to_owned()))?;
+        let change = PluginTincOperator::instance().config_change(&tinc_info)?;
+        info!("apply tinc config {:?}", change);
+        match change {
+            ConfigChange::Restart => self.restart_tinc(),
+            ConfigChange::Reload { removed, .. } => {
+                self.set_info_to_local()?;
+                self.set_tinc_team_init_file()?;
+                match PluginTincOperator::mut_instance().reload_tinc_config(&removed) {
+                    Ok(()) => Ok(()),
+                    // 新配置已回滚, 重启会再次写入同样的配置
+                    Err(TincOperatorError::ConfigRolledBack) => Err(TincOperatorError::ConfigRolledBack),
+                    Err(e) => {
+                        warn!("reload tinc {:?}, restart tinc", e);
+                        self.restart_tinc()
+                    }
+                }
+            }
+        }
+    }
+
     pub fn set_hosts(&self,
-                     ip_port: Option<(IpAddr, u16)>,
-                     vip:     IpAddr,
-                     pubkey:  &str,
+                     ip_port:        Option<(IpAddr, u16)>,
+                     vip:            IpAddr,
+                     pubkey:         &str,
+                     ed25519_pubkey: Option<&str>,
     ) -> Result<()> {
         PluginTincOperator::instance().set_hosts(
             ip_port,
             vip,
             pubkey,
+            ed25519_pubkey,
         )
     }
 
@@ -235,6 +309,11 @@ impl TincOperator {
         PluginTincOperator::instance().get_host_pub_key(host_name)
     }
 
+    /// 获取子设备Ed25519公钥
+    pub fn get_host_ed25519_pub_key(&self, host_name: &str) -> Option<String> {
+        PluginTincOperator::instance().get_host_ed25519_pub_key(host_name)
+    }
+
     /// openssl Rsa 创建2048位密钥对, 并存放到tinc配置文件中
     pub fn create_self_key_pair(&self) -> Result<()> {
         PluginTincOperator::instance().create_self_key_pair()
@@ -245,6 +324,11 @@ impl TincOperator {
         PluginTincOperator::instance().get_local_pub_key()
     }
 
+    /// 读取本地Ed25519公钥, 未开启ed25519_key时返回None
+    pub fn get_ed25519_pub_key(&self) -> Result<Option<String>> {
+        PluginTincOperator::instance().get_local_ed25519_pub_key()
+    }
+
     /// 获取本地tinc虚拟ip
     pub fn get_vip(&self) -> Result<IpAddr> {
         PluginTincOperator::instance().get_local_vip()
@@ -257,6 +341,15 @@ impl TincOperator {
         PluginTincOperator::mut_instance().set_info_to_local(&tinc_info)
     }
 
+    pub fn remove_host(&self, file_name: &str) {
+        PluginTincOperator::instance().remove_host(file_name)
+    }
+
+    /// 删除全部host文件, 切换conductor时旧的公钥不再有效
+    pub fn clear_hosts(&self) {
+        PluginTincOperator::instance().clear_hosts()
+    }
+
     pub fn get_client_filename_by_virtual_ip(&self, vip: &str) -> String {
         TincTools::get_filename_by_vip(false, vip)
     }
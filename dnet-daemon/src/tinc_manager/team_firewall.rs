@@ -0,0 +1,95 @@
+//! team隔离的防火墙规则, 所有规则在 DNET_TEAM 链中, 由INPUT跳转.
+//! switch模式下proxy在tincd内部转发设备之间的帧, 不经过proxy的内核, FORWARD规则无效;
+//! 因此由每个客户端在自己的INPUT上过滤, 只保护开启了 [client] team_isolation_firewall 的节点.
+
+use std::net::IpAddr;
+
+use dnet_types::team::NetSegment;
+use sandbox::firewall::{Chain, Hook, Rule, Table, Target};
+
+use crate::info::get_info;
+use crate::settings::get_settings;
+use crate::settings::default_settings::TINC_INTERFACE;
+
+const TEAM_CHAIN: &str = "DNET_TEAM";
+
+/// 来自同team设备(含其LAN)和proxy的dnet流量ACCEPT, 其余DROP.
+/// 本节点主动访问其他team设备的回包也会被DROP.
+pub fn apply(hosts: &[NetSegment], proxies: &[IpAddr]) {
+    #[cfg(target_os = "linux")]
+        {
+            let chain = Chain::new(Table::Filter, TEAM_CHAIN, Some(Hook::Input));
+            let res = sandbox::firewall::firewall()
+                .and_then(|firewall| firewall.replace_rules(&chain, &render_rules(hosts, proxies)));
+            if let Err(e) = res {
+                warn!("team firewall {:?}", e);
+            }
+        }
+    #[cfg(not(target_os = "linux"))]
+        let _ = (hosts, proxies);
+}
+
+/// 按当前team和选择的proxy重建 DNET_TEAM, team同步和切换proxy后调用.
+/// 未开启 team_isolation_firewall 时不处理
+pub fn refresh() {
+    if !get_settings().client.team_isolation_firewall {
+        return;
+    }
+    let info = get_info().lock().unwrap();
+    let hosts = info.teams.get_connect_hosts(info.client_info.wan.clone(), &info.tinc_info.vip);
+    let proxies = info.tinc_info.connect_to.iter()
+        .map(|connect_to| connect_to.vip)
+        .collect::<Vec<IpAddr>>();
+    std::mem::drop(info);
+    apply(&hosts, &proxies);
+}
+
+/// 清空 DNET_TEAM, 不再隔离
+pub fn clear() {
+    #[cfg(target_os = "linux")]
+        {
+            let chain = Chain::new(Table::Filter, TEAM_CHAIN, Some(Hook::Input));
+            let res = sandbox::firewall::firewall()
+                .and_then(|firewall| firewall.flush_chain(&chain));
+            if let Err(e) = res {
+                warn!("clear team firewall {:?}", e);
+            }
+        }
+}
+
+fn render_rules(hosts: &[NetSegment], proxies: &[IpAddr]) -> Vec<Rule> {
+    let mut srcs = hosts.iter()
+        .map(|host| format!("{}/{}", host.ip, host.mask))
+        .chain(proxies.iter().map(|proxy| proxy.to_string() + "/32"))
+        .collect::<Vec<String>>();
+    srcs.sort();
+    srcs.dedup();
+    let mut rules = srcs.into_iter()
+        .map(|src| Rule::new(Target::Accept)
+            .in_(TINC_INTERFACE)
+            .src(&src))
+        .collect::<Vec<Rule>>();
+    rules.push(Rule::new(Target::Drop)
+        .in_(TINC_INTERFACE));
+    rules
+}
+
+#[test]
+fn test_render_rules() {
+    use std::str::FromStr;
+    let hosts = vec![
+        NetSegment::new(IpAddr::from_str("10.1.1.2").unwrap(), 32, None),
+        NetSegment::new(IpAddr::from_str("192.168.1.0").unwrap(), 24, None),
+        NetSegment::new(IpAddr::from_str("10.1.1.2").unwrap(), 32, None),
+    ];
+    let proxies = vec![IpAddr::from_str("10.253.1.1").unwrap()];
+    let rules = render_rules(&hosts, &proxies).iter()
+        .map(|rule| rule.to_iptables_args().join(" "))
+        .collect::<Vec<String>>();
+    assert_eq!(rules, vec![
+        "-s 10.1.1.2/32 -i dnet -j ACCEPT".to_string(),
+        "-s 10.253.1.1/32 -i dnet -j ACCEPT".to_string(),
+        "-s 192.168.1.0/24 -i dnet -j ACCEPT".to_string(),
+        "-i dnet -j DROP".to_string(),
+    ]);
+}
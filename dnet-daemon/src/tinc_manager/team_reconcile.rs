@@ -0,0 +1,193 @@
+//! tinc group与conductor team对账.
+//! 每次对账读取tinc当前group(dump_group), 只下发与完整team列表的差异,
+//! 成功后保存为 tinc/team_state.json, tinc启动时据此生成tinc.group.
+//! 对账在monitor线程, conductor推送的增量在actix worker, team_state.json的读-改-写在同一个锁内完成.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::sync::{Mutex, MutexGuard, Once};
+
+use tinc_plugin::{TincTeam, PID_FILENAME};
+use dnet_types::status::TeamSyncState;
+
+use crate::info::get_mut_info;
+use crate::settings::get_settings;
+
+const TEAM_STATE_FILENAME: &str = "team_state.json";
+
+static TEAM_STATE_LOCK_INIT: Once = Once::new();
+static mut TEAM_STATE_LOCK: *const Mutex<()> = 0 as *const _;
+
+fn lock_team_state() -> MutexGuard<'static, ()> {
+    unsafe {
+        TEAM_STATE_LOCK_INIT.call_once(|| TEAM_STATE_LOCK = Box::into_raw(Box::new(Mutex::new(()))));
+        (*TEAM_STATE_LOCK).lock().unwrap_or_else(|e|e.into_inner())
+    }
+}
+
+/// 使tinc group与desired一致, 返回实际下发的差异, 失败返回下发失败的team
+pub fn reconcile_teams(desired: &HashMap<String, Vec<IpAddr>>)
+    -> std::result::Result<TincTeam, Vec<String>>
+{
+    let guard = lock_team_state();
+    let res = TincTeam::reconcile(&tinc_path(PID_FILENAME), desired);
+    if res.is_ok() {
+        save_team_state(desired);
+    }
+    std::mem::drop(guard);
+
+    record_reconcile(&mut get_mut_info().lock().unwrap().status.team_sync, &res);
+    res
+}
+
+fn record_reconcile(team_sync: &mut TeamSyncState, res: &std::result::Result<TincTeam, Vec<String>>) {
+    team_sync.last_reconcile = Some(chrono::Utc::now().to_string());
+    team_sync.reconcile_total += 1;
+    match res {
+        Ok(delta) => {
+            let drift = drift_count(delta);
+            if drift > 0 {
+                warn!("tinc group drift {} {:?}", drift, delta);
+                team_sync.drift_total += 1;
+            }
+            team_sync.last_drift = drift;
+            team_sync.failed_teams = vec![];
+        }
+        Err(failed) => {
+            team_sync.failed_teams = failed.clone();
+        }
+    }
+}
+
+/// conductor推送的增量成功下发后, 同步到保存的对账状态
+pub fn apply_team_delta(delta: &TincTeam) {
+    let _guard = lock_team_state();
+    let mut state = load_team_state().unwrap_or(HashMap::new());
+    merge_team_delta(&mut state, delta);
+    save_team_state(&state);
+}
+
+fn merge_team_delta(state: &mut HashMap<String, Vec<IpAddr>>, delta: &TincTeam) {
+    for (team_id, members) in &delta.delete {
+        if members.is_empty() {
+            state.remove(team_id);
+        }
+        else if let Some(current) = state.get_mut(team_id) {
+            current.retain(|member|!members.contains(member));
+        }
+    }
+    for (team_id, members) in &delta.add {
+        let current = state.entry(team_id.clone()).or_insert(vec![]);
+        for member in members {
+            if !current.contains(member) {
+                current.push(member.clone());
+            }
+        }
+    }
+    state.retain(|_, members|!members.is_empty());
+}
+
+/// 最近一次成功下发的team状态
+pub fn load_team_state() -> Option<HashMap<String, Vec<IpAddr>>> {
+    let buf = fs::read_to_string(tinc_path(TEAM_STATE_FILENAME)).ok()?;
+    serde_json::from_str(&buf)
+        .map_err(|e|error!("load team state {:?}", e))
+        .ok()
+}
+
+fn save_team_state(state: &HashMap<String, Vec<IpAddr>>) {
+    let path = tinc_path(TEAM_STATE_FILENAME);
+    let tmp = path.clone() + ".tmp";
+    let res = serde_json::to_string(state)
+        .map_err(|e|e.to_string())
+        .and_then(|buf|fs::write(&tmp, buf).map_err(|e|e.to_string()))
+        .and_then(|_|fs::rename(&tmp, &path).map_err(|e|e.to_string()));
+    if let Err(e) = res {
+        error!("save team state {} {}", path, e);
+    }
+}
+
+fn drift_count(delta: &TincTeam) -> u32 {
+    (delta.add.len() + delta.delete.len()) as u32
+}
+
+fn tinc_path(filename: &str) -> String {
+    get_settings().common.home_path
+        .join("tinc").join(filename)
+        .to_str().unwrap().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    fn teams(teams: &[(&str, &[&str])]) -> HashMap<String, Vec<IpAddr>> {
+        teams.iter()
+            .map(|(team_id, members)|
+                (team_id.to_string(), members.iter().map(|member|ip(member)).collect()))
+            .collect()
+    }
+
+    fn team_sync() -> TeamSyncState {
+        TeamSyncState {
+            last_reconcile:     None,
+            last_drift:         0,
+            reconcile_total:    0,
+            drift_total:        0,
+            failed_teams:       vec![],
+        }
+    }
+
+    #[test]
+    fn test_merge_team_delta() {
+        let mut state = teams(&[("t1", &["10.0.0.1", "10.0.0.2"]), ("t2", &["10.0.0.3"])]);
+
+        // 新增team和已有team的成员, 重复成员不重复添加
+        let mut delta = TincTeam::new();
+        delta.add = teams(&[("t1", &["10.0.0.2", "10.0.0.4"]), ("t3", &["10.0.0.5"])]);
+        merge_team_delta(&mut state, &delta);
+        assert_eq!(state, teams(&[
+            ("t1", &["10.0.0.1", "10.0.0.2", "10.0.0.4"]),
+            ("t2", &["10.0.0.3"]),
+            ("t3", &["10.0.0.5"]),
+        ]));
+
+        // 删除部分成员, 删除最后一个成员时删除team
+        let mut delta = TincTeam::new();
+        delta.delete = teams(&[("t1", &["10.0.0.1"]), ("t3", &["10.0.0.5"])]);
+        merge_team_delta(&mut state, &delta);
+        assert_eq!(state, teams(&[("t1", &["10.0.0.2", "10.0.0.4"]), ("t2", &["10.0.0.3"])]));
+
+        // 成员为空表示删除整个team
+        let mut delta = TincTeam::new();
+        delta.delete = teams(&[("t1", &[]), ("t4", &[])]);
+        merge_team_delta(&mut state, &delta);
+        assert_eq!(state, teams(&[("t2", &["10.0.0.3"])]));
+    }
+
+    #[test]
+    fn test_record_reconcile() {
+        let mut sync = team_sync();
+
+        let mut drift = TincTeam::new();
+        drift.add = teams(&[("t1", &["10.0.0.1"])]);
+        drift.delete = teams(&[("t2", &[])]);
+        record_reconcile(&mut sync, &Ok(drift));
+        assert!(sync.last_reconcile.is_some());
+        assert_eq!((sync.reconcile_total, sync.drift_total, sync.last_drift), (1, 1, 2));
+
+        record_reconcile(&mut sync, &Err(vec!["t1".to_string()]));
+        assert_eq!(sync.failed_teams, vec!["t1".to_string()]);
+        assert_eq!((sync.reconcile_total, sync.drift_total, sync.last_drift), (2, 1, 2));
+
+        // 一致时清除漂移和失败记录, 不增加drift_total
+        record_reconcile(&mut sync, &Ok(TincTeam::new()));
+        assert!(sync.failed_teams.is_empty());
+        assert_eq!((sync.reconcile_total, sync.drift_total, sync.last_drift), (3, 1, 0));
+    }
+}
@@ -10,7 +10,8 @@ use tinc_plugin::TincOperatorError;
 use crate::tinc_manager::TincOperator;
 use crate::traits::TunnelTrait;
 use crate::daemon::{DaemonEvent, TunnelCommand};
-use crate::info::get_mut_info;
+use crate::info::{get_mut_info, set_tunnel_state};
+use crate::hooks;
 
 pub type Result<T> = std::result::Result<T, TincOperatorError>;
 
@@ -84,7 +85,8 @@ impl TincMonitor {
     fn init_tinc(daemon_event_tx: mpsc::Sender<DaemonEvent>) {
         let tinc = TincOperator::new();
         // 初始化tinc操作
-        // 监测tinc pub key 不存在或生成时间超过一个月，将生成tinc pub key
+        // 监测tinc pub key 不存在或生成时间超过key_max_age_days，将生成tinc pub key
+        // 运行中的密钥轮换见 key_rotation::rotate_key_if_expired
         info!("check_pub_key");
         tinc.init()
             .map_err(|e|
@@ -98,8 +100,11 @@ impl TincMonitor {
         let res =
             if info.status.tunnel == TunnelState::Disconnected
                 || info.status.tunnel == TunnelState::Disconnecting {
-                info.status.tunnel = TunnelState::Connecting;
+                let event = info.set_tunnel_state(TunnelState::Connecting);
                 std::mem::drop(info);
+                if let Some(event) = event {
+                    hooks::emit(event);
+                }
 
                 match TincOperator::new().start_tinc() {
                     Ok(_) => {
@@ -123,9 +128,9 @@ impl TincMonitor {
         let _ = std::thread::Builder::new()
             .name("tinc_monitor_reconnect".to_string())
             .spawn(move|| {
-            let res = match TincOperator::new().restart_tinc() {
+            let res = match TincOperator::new().apply_config() {
                 Ok(_) => {
-                    info!("tinc_monitor restart tinc");
+                    info!("tinc_monitor apply tinc config");
                     Response::success()
                 },
                 Err(err) => {
@@ -138,9 +143,7 @@ impl TincMonitor {
     }
 
     fn disconnect(&self) -> Response {
-        let mut info = get_mut_info().lock().unwrap();
-        info.status.tunnel = TunnelState::Disconnecting;
-        std::mem::drop(info);
+        set_tunnel_state(TunnelState::Disconnecting);
         let res =
             if let Err(err) = TincOperator::new().stop_tinc() {
                 Response::internal_error().set_msg(err.to_string())
@@ -199,12 +202,10 @@ impl MonitorInner {
                 if Instant::now() - check_time > Duration::from_secs(TINC_FREQUENCY.into()) {
                     debug!("exec_tinc_check");
                     if let Ok(_) = self.exec_tinc_check() {
-                        get_mut_info().lock().unwrap().status.tunnel = TunnelState::Connected;
+                        set_tunnel_state(TunnelState::Connected);
                     }
                     else {
-                        let mut info = get_mut_info().lock().unwrap();
-                        info.status.tunnel = TunnelState::Disconnected;
-                        std::mem::drop(info);
+                        set_tunnel_state(TunnelState::Disconnected);
                         let (tx, _) = mpsc::channel();
                         let _ = self.tunnel_command_tx.send((TunnelCommand::Reconnect, tx));
                     }
@@ -0,0 +1,202 @@
+//! proxy违规检测: 发现未经conductor确认的节点和子网.
+//! tinc运行在switch模式, 按学习到的MAC转发, IP子网不限制流量, 也不能使用StrictSubnets.
+//! 这里只是在心跳时检查 dump nodes/subnets, 事后发现违规, 不阻止违规之前的流量:
+//! 没有host文件的节点, 以及IP子网不在其vip和conductor分配的LAN内的节点,
+//! 记录安全事件(日志, status, security_violation hook)并断开.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use tinc_plugin::tinc_tcp_stream::SourceSubnet;
+use tinc_plugin::{TincTools, PID_FILENAME};
+use dnet_types::status::{SecurityEvent, SecurityEventKind};
+use dnet_types::team::NetSegment;
+
+use crate::hooks::{self, HookEvent};
+use crate::info::get_mut_info;
+use crate::settings::get_settings;
+use crate::settings::default_settings::SECURITY_EVENT_LIMIT;
+use super::{control, host_store};
+
+/// 检查一次tinc节点和子网, 返回发现的违规数
+pub fn check() -> usize {
+    let tinc_home = get_settings().common.home_path.join("tinc");
+    let pid_path = tinc_home.join(PID_FILENAME).to_string_lossy().to_string();
+    let (own, nodes, subnets) = match control::nodes_and_subnets(&pid_path) {
+        Ok(res) => res,
+        Err(e) => {
+            warn!("violation detection {:?}", e);
+            return 0;
+        }
+    };
+
+    let hosts = tinc_home.join("hosts");
+    let known = |node: &str|hosts.join(node).is_file();
+    let violations = find_violations(&own, &nodes, &subnets, &known, &host_store::lans());
+    for (kind, node, detail) in &violations {
+        let kicked = control::kick_node(&pid_path, node);
+        let recorded = record(SecurityEvent {
+            time:   chrono::Utc::now().to_string(),
+            kind:   kind.clone(),
+            node:   node.clone(),
+            detail: detail.clone(),
+            kicked: kicked.is_ok(),
+        });
+        if let (true, Err(e)) = (recorded, kicked) {
+            // 没有直连的节点需要由与其直连的proxy断开
+            warn!("kick {} {:?}, node may not be directly connected", node, e);
+        }
+    }
+    violations.len()
+}
+
+/// 同一违规持续存在时只在第一次记录日志和触发hook, 返回是否为新的违规
+fn record(event: SecurityEvent) -> bool {
+    let mut info = get_mut_info().lock().unwrap();
+    let events = &mut info.status.security_events;
+    let repeated = events.iter().any(|recorded|recorded.kind == event.kind
+        && recorded.node == event.node
+        && recorded.detail == event.detail);
+    if repeated {
+        return false;
+    }
+    warn!("security event {:?} node:{} {} kicked:{}",
+          event.kind, event.node, event.detail, event.kicked);
+    events.push(event.clone());
+    if events.len() > SECURITY_EVENT_LIMIT {
+        let overflow = events.len() - SECURITY_EVENT_LIMIT;
+        events.drain(..overflow);
+    }
+    std::mem::drop(info);
+    hooks::emit(HookEvent::SecurityViolation { violation: event });
+    true
+}
+
+fn find_violations(
+    own:        &str,
+    nodes:      &[String],
+    subnets:    &[SourceSubnet],
+    known:      &dyn Fn(&str) -> bool,
+    lans:       &BTreeMap<String, Vec<NetSegment>>,
+) -> Vec<(SecurityEventKind, String, String)> {
+    let mut violations = vec![];
+    let mut unknown = vec![];
+    for node in nodes.iter().filter(|node|*node != own && !known(node)) {
+        unknown.push(node.as_str());
+        violations.push((SecurityEventKind::UnknownNode, node.clone(), "no host file".to_string()));
+    }
+
+    for subnet in subnets {
+        // (broadcast)等不属于节点的子网
+        if subnet.name == own || subnet.name.starts_with('(') || unknown.contains(&subnet.name.as_str()) {
+            continue;
+        }
+        let vip = TincTools::get_vip_by_filename(&subnet.name);
+        let lan = lans.get(&subnet.name).map(Vec::as_slice).unwrap_or(&[]);
+        if !subnet_allowed(&subnet.addr, vip, lan) {
+            violations.push((SecurityEventKind::UnassignedSubnet, subnet.name.clone(),
+                             subnet.addr.clone()));
+        }
+    }
+    violations
+}
+
+/// MAC子网总是允许, IP子网必须在vip或分配的LAN内
+fn subnet_allowed(addr: &str, vip: Option<IpAddr>, lans: &[NetSegment]) -> bool {
+    // 去掉 #weight
+    let addr = addr.split('#').next().unwrap_or("");
+    if is_mac(addr) {
+        return true;
+    }
+
+    let mut split = addr.splitn(2, '/');
+    let ip = match split.next().and_then(|ip|ip.parse::<IpAddr>().ok()) {
+        Some(ip) => ip,
+        None => return false,
+    };
+    let prefix = match split.next() {
+        Some(prefix) => match prefix.parse::<u32>() {
+            Ok(prefix) => prefix,
+            Err(_) => return false,
+        },
+        None => max_prefix(&ip),
+    };
+
+    vip.map(|vip|contains(&vip, max_prefix(&vip), &ip, prefix)).unwrap_or(false)
+        || lans.iter().any(|lan|contains(&lan.ip, lan.mask, &ip, prefix))
+}
+
+fn is_mac(addr: &str) -> bool {
+    let parts: Vec<&str> = addr.split(':').collect();
+    parts.len() == 6 && parts.iter()
+        .all(|part|!part.is_empty() && part.len() <= 2 && u8::from_str_radix(part, 16).is_ok())
+}
+
+fn max_prefix(ip: &IpAddr) -> u32 {
+    match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// net_ip/net_prefix 是否包含 ip/prefix
+fn contains(net_ip: &IpAddr, net_prefix: u32, ip: &IpAddr, prefix: u32) -> bool {
+    let (net, addr, bits) = match (net_ip, ip) {
+        (IpAddr::V4(net), IpAddr::V4(addr)) => (u32::from(*net) as u128, u32::from(*addr) as u128, 32),
+        (IpAddr::V6(net), IpAddr::V6(addr)) => (u128::from(*net), u128::from(*addr), 128),
+        _ => return false,
+    };
+    if net_prefix > bits || prefix > bits || prefix < net_prefix {
+        return false;
+    }
+    if net_prefix == 0 {
+        return true;
+    }
+    let shift = bits - net_prefix;
+    net >> shift == addr >> shift
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn subnet(name: &str, addr: &str) -> SourceSubnet {
+        SourceSubnet {
+            name:   name.to_string(),
+            addr:   addr.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_subnet_allowed() {
+        let vip = Some("10.253.0.2".parse().unwrap());
+        let lans = vec![NetSegment::new("192.168.1.0".parse().unwrap(), 24, None)];
+        assert!(subnet_allowed("02:42:ac:11:00:02#10", vip, &[]));
+        assert!(subnet_allowed("10.253.0.2", vip, &[]));
+        assert!(subnet_allowed("10.253.0.2/32#10", vip, &[]));
+        assert!(!subnet_allowed("10.253.0.0/16", vip, &[]));
+        assert!(subnet_allowed("192.168.1.0/25", vip, &lans));
+        assert!(!subnet_allowed("192.168.0.0/16", vip, &lans));
+        assert!(!subnet_allowed("0.0.0.0/0", vip, &lans));
+        assert!(!subnet_allowed("fe80::1/128", vip, &lans));
+    }
+
+    #[test]
+    fn test_find_violations() {
+        let nodes = vec!["253_0_2".to_string(), "253_0_3".to_string(), "proxy_10_253_1_2".to_string()];
+        let subnets = vec![
+            subnet("(broadcast)", "ff:ff:ff:ff:ff:ff"),
+            subnet("proxy_10_253_1_1", "10.0.0.0/8"),
+            subnet("253_0_2", "10.253.0.2"),
+            subnet("253_0_2", "172.16.0.0/12"),
+            subnet("253_0_3", "10.253.0.3"),
+        ];
+        let known = |node: &str|node != "253_0_3";
+        let violations = find_violations(
+            "proxy_10_253_1_1", &nodes, &subnets, &known, &BTreeMap::new());
+        assert_eq!(violations, vec![
+            (SecurityEventKind::UnknownNode, "253_0_3".to_string(), "no host file".to_string()),
+            (SecurityEventKind::UnassignedSubnet, "253_0_2".to_string(), "172.16.0.0/12".to_string()),
+        ]);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::status::{TunnelState, RpcState};
 use crate::settings::Settings;
+use crate::team::NetSegment;
 
 /// An event sent out from the daemon to frontends.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,4 +14,16 @@ pub enum DaemonBroadcast {
 
     /// The daemon settings changed.
     Settings(Settings),
-}
\ No newline at end of file
+
+    /// The default route interface, gateway or address changed.
+    NetworkChanged(NetworkChange),
+}
+
+/// 默认路由网卡, 网关或地址变化后的网络
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkChange {
+    /// 变化前的默认路由网卡, 之前没有默认路由时为None
+    pub old_dev:    Option<String>,
+    pub dev:        String,
+    pub wan:        NetSegment,
+}
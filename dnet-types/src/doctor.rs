@@ -0,0 +1,39 @@
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Verdict {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// dnet doctor 单项检查结果
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DoctorCheck {
+    pub name:       String,
+    pub verdict:    Verdict,
+    pub detail:     String,
+    /// 非Pass时的处理建议
+    pub hint:       Option<String>,
+}
+
+impl DoctorCheck {
+    pub fn pass(name: &str, detail: &str) -> Self {
+        Self::new(name, Verdict::Pass, detail, None)
+    }
+
+    pub fn warn(name: &str, detail: &str, hint: &str) -> Self {
+        Self::new(name, Verdict::Warn, detail, Some(hint))
+    }
+
+    pub fn fail(name: &str, detail: &str, hint: &str) -> Self {
+        Self::new(name, Verdict::Fail, detail, Some(hint))
+    }
+
+    fn new(name: &str, verdict: Verdict, detail: &str, hint: Option<&str>) -> Self {
+        Self {
+            name:       name.to_owned(),
+            verdict,
+            detail:     detail.to_owned(),
+            hint:       hint.map(|hint| hint.to_owned()),
+        }
+    }
+}
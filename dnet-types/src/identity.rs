@@ -0,0 +1,69 @@
+use std::str::FromStr;
+
+/// 设备身份来源
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityProvider {
+    /// 随机生成并保存的ID
+    Generated,
+    /// /etc/machine-id
+    MachineId,
+    /// DMI(SMBIOS)序列号
+    Dmi,
+    /// 路由器厂商SN
+    VendorSn,
+    /// 默认路由网卡的MAC, 旧版本的身份
+    Mac,
+}
+
+impl IdentityProvider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IdentityProvider::Generated => "generated",
+            IdentityProvider::MachineId => "machine_id",
+            IdentityProvider::Dmi       => "dmi",
+            IdentityProvider::VendorSn  => "vendor_sn",
+            IdentityProvider::Mac       => "mac",
+        }
+    }
+}
+
+impl FromStr for IdentityProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "generated"     => Ok(IdentityProvider::Generated),
+            "machine_id"    => Ok(IdentityProvider::MachineId),
+            "dmi"           => Ok(IdentityProvider::Dmi),
+            "vendor_sn"     => Ok(IdentityProvider::VendorSn),
+            "mac"           => Ok(IdentityProvider::Mac),
+            _ => Err(s.to_string()),
+        }
+    }
+}
+
+/// 第一次解析到的设备身份, 之后不随网卡变化
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    pub provider:       IdentityProvider,
+    pub id:             String,
+    pub resolved_at:    String,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct IdentityStatus {
+    pub identity:       Identity,
+    /// 上报给conductor的设备名(deviceSerial)
+    pub device_name:    String,
+}
+
+#[test]
+fn test_identity_provider_from_str() {
+    for provider in &[IdentityProvider::Generated, IdentityProvider::MachineId,
+        IdentityProvider::Dmi, IdentityProvider::VendorSn, IdentityProvider::Mac] {
+        assert_eq!(provider.name().parse::<IdentityProvider>(), Ok(*provider));
+        assert_eq!(serde_json::to_value(provider).unwrap(), provider.name());
+    }
+    assert!("serial".parse::<IdentityProvider>().is_err());
+}
@@ -3,6 +3,8 @@ extern crate serde_derive;
 
 pub mod daemon_broadcast;
 pub mod device_type;
+pub mod doctor;
+pub mod identity;
 pub mod proxy;
 pub mod response;
 pub mod status;
@@ -40,3 +40,39 @@ pub struct Settings {
     pub client:                                 Client,
     pub last_runtime:                           String,
 }
+
+/// 运行时可修改的配置, 为None的项不修改
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SettingsUpdate {
+    pub conductor_url:                          Option<String>,
+    pub log_level:                              Option<String>,
+    pub auto_connect:                           Option<bool>,
+}
+
+/// 命名的conductor地址和账号, 用于在多个conductor/租户之间切换
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name:                                   String,
+    pub conductor_url:                          String,
+    pub username:                               String,
+    pub password:                               String,
+    pub accept_conductor_invalid_certs:         bool,
+    /// 定义在settings.toml中, 不能通过命令删除
+    #[serde(default)]
+    pub from_file:                              bool,
+}
+
+impl Profile {
+    /// profile名作为缓存目录名, 只允许字母数字和-_
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.chars().all(|c|c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+/// profile list的返回, password置空
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProfileList {
+    pub active:                                 Option<String>,
+    pub profiles:                               Vec<Profile>,
+}
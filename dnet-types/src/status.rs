@@ -1,8 +1,19 @@
+use std::net::IpAddr;
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Status {
     pub rpc:        RpcState,
     pub tunnel:     TunnelState,
     pub daemon:     DaemonExecutionState,
+    pub team_sync:  TeamSyncState,
+    /// 以缓存状态运行时为状态文件的保存时间, conductor重新连接后清空
+    pub cached:     Option<String>,
+    /// 最近一次proxy选择的评分, 按score升序
+    pub proxy_scores: Vec<ProxyScore>,
+    /// proxy停止前等待客户端迁移
+    pub draining:   Option<DrainState>,
+    /// proxy违规检测发现的最近的安全事件
+    pub security_events: Vec<SecurityEvent>,
 }
 
 impl Status {
@@ -11,10 +22,66 @@ impl Status {
             rpc:    RpcState::Disconnected,
             tunnel: TunnelState::Disconnected,
             daemon: DaemonExecutionState::Running,
+            team_sync: TeamSyncState::new(),
+            cached: None,
+            proxy_scores: vec![],
+            draining: None,
+            security_events: vec![],
         }
     }
 }
 
+/// tinc group与conductor team的对账状态
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TeamSyncState {
+    /// 最近一次对账时间
+    pub last_reconcile:     Option<String>,
+    /// 最近一次对账修正的group操作数, 0表示与conductor一致
+    pub last_drift:         u32,
+    /// 累计对账次数
+    pub reconcile_total:    u64,
+    /// 累计发现漂移的次数
+    pub drift_total:        u64,
+    /// 最近一次下发失败的team
+    pub failed_teams:       Vec<String>,
+}
+
+impl TeamSyncState {
+    pub fn new() -> Self {
+        Self {
+            last_reconcile:     None,
+            last_drift:         0,
+            reconcile_total:    0,
+            drift_total:        0,
+            failed_teams:       vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DrainState {
+    pub started_at:     String,
+    pub deadline:       String,
+    /// 剩余的客户端连接数
+    pub connections:    u32,
+}
+
+/// proxy选择的评分明细, score越小越优先, 不可达的proxy score为None
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProxyScore {
+    pub id:         String,
+    pub vip:        IpAddr,
+    /// 平均往返时间(ms)
+    pub rtt_ms:     Option<u32>,
+    /// 丢包率(%)
+    pub loss:       u32,
+    /// connections / capacity (%)
+    pub load:       Option<u32>,
+    pub region:     Option<String>,
+    pub score:      Option<i64>,
+    pub selected:   bool,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum RpcState {
     Connecting,
@@ -37,3 +104,22 @@ pub enum DaemonExecutionState {
     Running,
     Finished,
 }
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SecurityEventKind {
+    /// 没有经过conductor确认的节点
+    UnknownNode,
+    /// 节点通告了未分配的子网
+    UnassignedSubnet,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SecurityEvent {
+    pub time:       String,
+    pub kind:       SecurityEventKind,
+    /// tinc节点名
+    pub node:       String,
+    pub detail:     String,
+    /// 是否已断开该节点
+    pub kicked:     bool,
+}
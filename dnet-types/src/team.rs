@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::net::IpAddr;
 
@@ -112,4 +113,232 @@ impl fmt::Debug for NetSegment {
             write!(f, "{}/{}", self.ip.to_string(), self.mask)
         }
     }
-}
\ No newline at end of file
+}
+/// team同步产生的变化, 成员以device_serial区分
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TeamEvent {
+    TeamAdd {
+        team_id:        String,
+    },
+    TeamRemove {
+        team_id:        String,
+    },
+    TeamUpdate {
+        team_id:        String,
+    },
+    MemberAdd {
+        team_id:        String,
+        device_serial:  String,
+        vip:            IpAddr,
+    },
+    MemberRemove {
+        team_id:        String,
+        device_serial:  String,
+        vip:            IpAddr,
+    },
+    MemberUpdate {
+        team_id:        String,
+        device_serial:  String,
+        vip:            IpAddr,
+    },
+}
+
+/// team及成员中最新的update_time, 作为增量同步的起点
+pub fn latest_update_time(teams: &HashMap<String, Team>) -> Option<String> {
+    teams.values()
+        .flat_map(|team| {
+            team.members.iter()
+                .filter_map(|member|member.update_time.as_ref())
+                .chain(team.update_time.as_ref())
+        })
+        .max()
+        .cloned()
+}
+
+/// 增量同步: changed替换或添加到old中, 删除deleted
+pub fn merge_teams(
+    old:        &HashMap<String, Team>,
+    changed:    Vec<Team>,
+    deleted:    &[String],
+) -> HashMap<String, Team> {
+    let mut teams = old.clone();
+    for team_id in deleted {
+        teams.remove(team_id);
+    }
+    for team in changed {
+        teams.insert(team.team_id.clone(), team);
+    }
+    teams
+}
+
+/// 比较两份完整的team快照
+pub fn diff_teams(old: &HashMap<String, Team>, new: &HashMap<String, Team>) -> Vec<TeamEvent> {
+    let mut events = vec![];
+
+    let mut old_ids = old.keys().collect::<Vec<&String>>();
+    old_ids.sort();
+    for team_id in old_ids {
+        if !new.contains_key(team_id) {
+            events.push(TeamEvent::TeamRemove { team_id: team_id.clone() });
+        }
+    }
+
+    let mut new_ids = new.keys().collect::<Vec<&String>>();
+    new_ids.sort();
+    for team_id in new_ids {
+        let new_team = &new[team_id];
+        let old_members: &[TeamMember] = match old.get(team_id) {
+            Some(old_team) => {
+                let mut old_meta = old_team.clone();
+                old_meta.members = vec![];
+                let mut new_meta = new_team.clone();
+                new_meta.members = vec![];
+                if old_meta != new_meta {
+                    events.push(TeamEvent::TeamUpdate { team_id: team_id.clone() });
+                }
+                &old_team.members[..]
+            }
+            None => {
+                events.push(TeamEvent::TeamAdd { team_id: team_id.clone() });
+                &[]
+            }
+        };
+
+        for old_member in old_members {
+            if !new_team.members.iter()
+                .any(|member|member.device_serial == old_member.device_serial) {
+                events.push(TeamEvent::MemberRemove {
+                    team_id:        team_id.clone(),
+                    device_serial:  old_member.device_serial.clone(),
+                    vip:            old_member.vip,
+                });
+            }
+        }
+        for new_member in &new_team.members {
+            let old_member = old_members.iter()
+                .find(|member|member.device_serial == new_member.device_serial);
+            let event = match old_member {
+                None => TeamEvent::MemberAdd {
+                    team_id:        team_id.clone(),
+                    device_serial:  new_member.device_serial.clone(),
+                    vip:            new_member.vip,
+                },
+                Some(old_member) if old_member != new_member => TeamEvent::MemberUpdate {
+                    team_id:        team_id.clone(),
+                    device_serial:  new_member.device_serial.clone(),
+                    vip:            new_member.vip,
+                },
+                Some(_) => continue,
+            };
+            events.push(event);
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn member(device_serial: &str, vip: &str, update_time: &str) -> TeamMember {
+        TeamMember {
+            alias:                  None,
+            app_version:            None,
+            city:                   None,
+            company_id:             None,
+            connection_limit:       None,
+            country:                None,
+            create_by:              None,
+            create_time:            None,
+            device_name:            None,
+            device_serial:          device_serial.to_owned(),
+            device_type:            None,
+            hidden_flag:            None,
+            id:                     None,
+            vip:                    vip.parse().unwrap(),
+            lan:                    vec![],
+            latitude:               None,
+            longitude:              None,
+            pubkey:                 String::new(),
+            region:                 None,
+            tinc_status:            false,
+            connect_status:         true,
+            update_by:              None,
+            update_time:            Some(update_time.to_owned()),
+            username:               None,
+            wan:                    None,
+            is_self:                None,
+            is_local_tinc_host_up:  None,
+        }
+    }
+
+    fn team(team_id: &str, members: Vec<TeamMember>) -> Team {
+        let mut team = Team::new();
+        team.team_id = team_id.to_owned();
+        team.update_time = Some("2020-01-01 00:00:00".to_owned());
+        team.members = members;
+        team
+    }
+
+    fn teams(teams: Vec<Team>) -> HashMap<String, Team> {
+        teams.into_iter().map(|team|(team.team_id.clone(), team)).collect()
+    }
+
+    #[test]
+    fn test_diff_teams() {
+        let old = teams(vec![
+            team("a", vec![
+                member("1", "10.0.0.1", "2020-01-01 00:00:00"),
+                member("2", "10.0.0.2", "2020-01-01 00:00:00"),
+            ]),
+            team("b", vec![]),
+        ]);
+        let mut updated = member("2", "10.0.0.2", "2020-01-02 00:00:00");
+        updated.connect_status = false;
+        let new = teams(vec![
+            team("a", vec![updated, member("3", "10.0.0.3", "2020-01-03 00:00:00")]),
+            team("c", vec![]),
+        ]);
+
+        assert_eq!(diff_teams(&old, &new), vec![
+            TeamEvent::TeamRemove { team_id: "b".to_owned() },
+            TeamEvent::MemberRemove {
+                team_id: "a".to_owned(), device_serial: "1".to_owned(), vip: "10.0.0.1".parse().unwrap() },
+            TeamEvent::MemberUpdate {
+                team_id: "a".to_owned(), device_serial: "2".to_owned(), vip: "10.0.0.2".parse().unwrap() },
+            TeamEvent::MemberAdd {
+                team_id: "a".to_owned(), device_serial: "3".to_owned(), vip: "10.0.0.3".parse().unwrap() },
+            TeamEvent::TeamAdd { team_id: "c".to_owned() },
+        ]);
+        assert_eq!(diff_teams(&new, &new), vec![]);
+        assert_eq!(latest_update_time(&new), Some("2020-01-03 00:00:00".to_owned()));
+    }
+
+    #[test]
+    fn test_merge_teams() {
+        let old = teams(vec![
+            team("a", vec![member("1", "10.0.0.1", "2020-01-01 00:00:00")]),
+            team("b", vec![]),
+            team("c", vec![]),
+        ]);
+        let changed = vec![
+            team("a", vec![
+                member("1", "10.0.0.1", "2020-01-01 00:00:00"),
+                member("2", "10.0.0.2", "2020-01-02 00:00:00"),
+            ]),
+            team("d", vec![]),
+        ];
+        let merged = merge_teams(&old, changed, &["b".to_owned()]);
+
+        let mut team_ids = merged.keys().cloned().collect::<Vec<String>>();
+        team_ids.sort();
+        assert_eq!(team_ids, vec!["a".to_owned(), "c".to_owned(), "d".to_owned()]);
+        assert_eq!(diff_teams(&old, &merged), vec![
+            TeamEvent::TeamRemove { team_id: "b".to_owned() },
+            TeamEvent::MemberAdd {
+                team_id: "a".to_owned(), device_serial: "2".to_owned(), vip: "10.0.0.2".parse().unwrap() },
+            TeamEvent::TeamAdd { team_id: "d".to_owned() },
+        ]);
+    }
+}
@@ -99,6 +99,10 @@ impl DaemonRpcClient {
         self.call("shutdown", &NO_ARGS)
     }
 
+    pub fn drain(&mut self) -> Result<Response> {
+        self.call("drain", &NO_ARGS)
+    }
+
     pub fn group_list(&mut self, ) -> Result<Response> {
         self.call("group_list", &NO_ARGS)
     }
@@ -127,6 +131,46 @@ impl DaemonRpcClient {
         self.call("logout", &NO_ARGS)
     }
 
+    pub fn diagnostics(&mut self) -> Result<Response> {
+        self.call("diagnostics", &NO_ARGS)
+    }
+
+    pub fn doctor(&mut self) -> Result<Response> {
+        self.call("doctor", &NO_ARGS)
+    }
+
+    pub fn traffic(&mut self) -> Result<Response> {
+        self.call("traffic", &NO_ARGS)
+    }
+
+    pub fn settings_update(&mut self, update: String) -> Result<Response> {
+        self.call("settings_update", &update)
+    }
+
+    pub fn profile_list(&mut self) -> Result<Response> {
+        self.call("profile_list", &NO_ARGS)
+    }
+
+    pub fn profile_add(&mut self, profile: String) -> Result<Response> {
+        self.call("profile_add", &profile)
+    }
+
+    pub fn profile_use(&mut self, name: String) -> Result<Response> {
+        self.call("profile_use", &name)
+    }
+
+    pub fn profile_remove(&mut self, name: String) -> Result<Response> {
+        self.call("profile_remove", &name)
+    }
+
+    pub fn identity(&mut self) -> Result<Response> {
+        self.call("identity", &NO_ARGS)
+    }
+
+    pub fn identity_migrate(&mut self, provider: String) -> Result<Response> {
+        self.call("identity_migrate", &provider)
+    }
+
     pub fn host_status_change(&mut self, host_status_change: String) -> Result<()> {
         self.call("host_status_change", &host_status_change)
     }
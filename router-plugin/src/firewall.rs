@@ -1,209 +1,280 @@
+//! 路由器防火墙. 规则由配置的WAN/LAN/隧道接口生成, 通过sandbox::firewall写入dnet拥有的链,
+//! 重复start不会产生重复规则, stop时清理dnet拥有的所有链.
+//! 路由器防火墙重启会清空iptables规则, 同时写入include脚本由防火墙重启时重新应用;
+//! fw4(nftables)不清理table ip dnet, 但其中的accept不能放行fw4拒绝的流量, accept写入fw4的include.
+
 use std::io::Write;
-use std::process::Command;
 use std::net::IpAddr;
 use std::os::unix::fs::PermissionsExt;
+use std::process::Command;
+
+use sandbox::firewall::{Backend, Chain, Hook, Proto, Rule, Table, Target};
+
+/// 防火墙重启时以start参数执行
+const FIREWALL_INCLUDE: &str = "/etc/scripts/firewall/vppn.rule";
+const TUNNEL_FIREWALL_INCLUDE: &str = "/etc/scripts/firewall/vppn_tunnel.rule";
+/// fw4 include目录, <hook>/dnet.nft 中的规则插入到 table inet fw4 对应链的开头
+const FW4_CHAIN_PRE_DIR: &str = "/usr/share/nftables.d/chain-pre/";
+const FW4_INCLUDE_FILENAME: &str = "dnet.nft";
+
+/// 路由器接口配置
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FirewallConfig {
+    /// tinc端口对这些接口开放
+    pub wan:        Vec<String>,
+    /// 隧道流量在这些接口上MASQUERADE
+    pub lan:        Vec<String>,
+    pub tunnel:     String,
+}
+
+fn input_chain() -> Chain {
+    Chain::new(Table::Filter, "DNET_INPUT", Some(Hook::Input))
+}
+
+fn output_chain() -> Chain {
+    Chain::new(Table::Filter, "DNET_OUTPUT", Some(Hook::Output))
+}
+
+fn forward_chain() -> Chain {
+    Chain::new(Table::Filter, "DNET_FORWARD", Some(Hook::Forward))
+}
+
+fn postrouting_chain() -> Chain {
+    Chain::new(Table::Nat, "DNET_POSTROUTING", Some(Hook::Postrouting))
+}
+
+/// 放行隧道及WAN上的tinc端口, 隧道出口MASQUERADE
+pub fn firewall_rules(config: &FirewallConfig, port: u16) -> Vec<(Chain, Rule)> {
+    let tunnel = &config.tunnel;
+    let mut rules = vec![
+        (input_chain(), Rule::new(Target::Accept).in_(tunnel)),
+        (output_chain(), Rule::new(Target::Accept).out(tunnel)),
+    ];
+    for wan in &config.wan {
+        rules.push((input_chain(), Rule::new(Target::Accept).in_(wan).dport(Proto::Udp, port)));
+        rules.push((input_chain(), Rule::new(Target::Accept).in_(wan).dport(Proto::Tcp, port)));
+    }
+    rules.push((forward_chain(), Rule::new(Target::Accept).in_(tunnel)));
+    rules.push((forward_chain(), Rule::new(Target::Accept).out(tunnel)));
+    rules.push((postrouting_chain(), Rule::new(Target::Masquerade).out(tunnel)));
+    rules
+}
+
+/// 本机隧道地址访问LAN/WAN时MASQUERADE
+pub fn tunnel_firewall_rules(config: &FirewallConfig, vip: &IpAddr) -> Vec<(Chain, Rule)> {
+    config.lan.iter()
+        .chain(config.wan.iter())
+        .map(|dev| (postrouting_chain(),
+                    Rule::new(Target::Masquerade).out(dev).src(&vip.to_string())))
+        .collect()
+}
+
+pub fn start_firewall(config: &FirewallConfig, port: u16) {
+    let rules = firewall_rules(config, port);
+    // 清理上次运行或旧配置留下的规则
+    flush_owned();
+    ensure_rules(&rules);
+    match sandbox::firewall::detect_backend() {
+        Some(Backend::Iptables) => write_include(FIREWALL_INCLUDE, &rules),
+        Some(Backend::Nftables) => write_fw4_include(&rules),
+        None => (),
+    }
+}
+
+/// 同时清理start_tunnel_firewall的规则
+pub fn stop_firewall(_config: &FirewallConfig, _port: u16) {
+    flush_owned();
+    remove_includes();
+}
+
+pub fn start_tunnel_firewall(config: &FirewallConfig, vip: &IpAddr) {
+    let rules = tunnel_firewall_rules(config, vip);
+    ensure_rules(&rules);
+    if sandbox::firewall::detect_backend() == Some(Backend::Iptables) {
+        write_include(TUNNEL_FIREWALL_INCLUDE, &rules);
+    }
+}
+
+/// 只删除本机隧道地址的规则, dnet退出时由stop_firewall统一清理
+pub fn stop_tunnel_firewall(config: &FirewallConfig, vip: &IpAddr) {
+    delete_rules(&tunnel_firewall_rules(config, vip));
+    let _ = std::fs::remove_file(TUNNEL_FIREWALL_INCLUDE);
+}
+
+fn ensure_rules(rules: &[(Chain, Rule)]) {
+    let firewall = match sandbox::firewall::firewall() {
+        Ok(firewall) => firewall,
+        Err(_) => return,
+    };
+    for (chain, rule) in rules {
+        let _ = firewall.ensure_chain(chain)
+            .and_then(|_| firewall.ensure_rule(chain, rule));
+    }
+}
+
+fn delete_rules(rules: &[(Chain, Rule)]) {
+    let firewall = match sandbox::firewall::firewall() {
+        Ok(firewall) => firewall,
+        Err(_) => return,
+    };
+    for (chain, rule) in rules {
+        let _ = firewall.delete_rule(chain, rule);
+    }
+}
+
+fn flush_owned() {
+    if let Ok(firewall) = sandbox::firewall::firewall() {
+        let _ = firewall.flush_owned();
+    }
+}
+
+fn write_include(path: &str, rules: &[(Chain, Rule)]) {
+    if let Ok(mut file) = std::fs::File::create(path) {
+        let _ = file.write_all(include_script(rules).as_bytes());
+    }
+    let _ = std::fs::set_permissions(path, PermissionsExt::from_mode(0o755));
+}
+
+fn write_fw4_include(rules: &[(Chain, Rule)]) {
+    for hook in &[Hook::Input, Hook::Output, Hook::Forward] {
+        let buf = fw4_include(rules, *hook);
+        if buf.is_empty() {
+            continue;
+        }
+        let dir = FW4_CHAIN_PRE_DIR.to_string() + &hook.iptables_chain().to_lowercase();
+        let _ = std::fs::create_dir_all(&dir);
+        if let Ok(mut file) = std::fs::File::create(dir + "/" + FW4_INCLUDE_FILENAME) {
+            let _ = file.write_all(buf.as_bytes());
+        }
+    }
+    fw4_reload();
+}
+
+fn remove_includes() {
+    let _ = std::fs::remove_file(FIREWALL_INCLUDE);
+    let _ = std::fs::remove_file(TUNNEL_FIREWALL_INCLUDE);
+    let mut removed = false;
+    for hook in &[Hook::Input, Hook::Output, Hook::Forward] {
+        let path = FW4_CHAIN_PRE_DIR.to_string() + &hook.iptables_chain().to_lowercase()
+            + "/" + FW4_INCLUDE_FILENAME;
+        removed |= std::fs::remove_file(path).is_ok();
+    }
+    if removed {
+        fw4_reload();
+    }
+}
+
+fn fw4_reload() {
+    let _ = Command::new("fw4").args(&["-q", "reload"]).status();
+}
 
-use sandbox::firewall::{imp::{iptables_insert_rule, iptables_find_rule}, types::IptablesRule};
-
-pub fn start_firewall(port: u16) {
-    firewall_script_write(port);
-    firewall_script_start();
-    vpn_tunnel_firewall();
-}
-
-pub fn stop_firewall(port: u16) {
-    firewall_script_write(port);
-    firewall_script_stop();
-}
-
-pub fn start_tunnel_firewall(vip: &IpAddr) {
-    tunnel_firewall_write(vip);
-    tunnel_firewall_script_start();
-}
+/// 防火墙include脚本, start时重建dnet的链和规则, stop时删除规则
+fn include_script(rules: &[(Chain, Rule)]) -> String {
+    let iptables = |table: Table| format!("/usr/sbin/iptables -t {}", table.iptables_name());
+    let mut start = String::new();
+    let mut stop = String::new();
+    let mut chains: Vec<&Chain> = vec![];
+    for (chain, rule) in rules {
+        let ipt = iptables(chain.table);
+        if !chains.contains(&chain) {
+            chains.push(chain);
+            start += &format!("\t{} -N {} 2>/dev/null\n", ipt, chain.name);
+            if let Some(hook) = chain.hook {
+                start += &format!("\t{0} -C {1} -j {2} 2>/dev/null || {0} -I {1} -j {2}\n",
+                                  ipt, hook.iptables_chain(), chain.name);
+            }
+        }
+        let args = rule.to_iptables_args().join(" ");
+        start += &format!("\t{0} -C {1} {2} 2>/dev/null || {0} -A {1} {2}\n", ipt, chain.name, args);
+        stop += &format!("\t{} -D {} {} 2>/dev/null\n", ipt, chain.name, args);
+    }
+    "#! /bin/sh\n".to_string()
+        + "if [ \"$1\" == \"start\" ]; then\n" + &start + "fi;\n"
+        + "if [ \"$1\" == \"stop\" ]; then\n" + &stop + "fi;\n"
+}
 
-pub fn stop_tunnel_firewall(vip: &IpAddr) {
-    tunnel_firewall_write(vip);
-    tunnel_firewall_script_stop();
+/// fw4对应hook链的accept规则, NAT规则在table ip dnet中即可生效
+fn fw4_include(rules: &[(Chain, Rule)], hook: Hook) -> String {
+    rules.iter()
+        .filter(|(chain, rule)| chain.table == Table::Filter
+            && chain.hook == Some(hook)
+            && rule.target == Target::Accept)
+        .map(|(_, rule)| rule.to_nft() + "\n")
+        .collect()
 }
 
-pub fn tunnel_firewall_write(vip: &IpAddr) {
-    let vip_string = vip.to_string();
-    let vip = &vip_string;
-    let buf =
-        format!(
-            "#! /bin/sh\n\
-            if [ \"$1\" == \"start\" ]; then\n\
-                \t/usr/sbin/iptables -t nat -I br0_masq -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -I POSTROUTING -o br1 -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -I POSTROUTING -o br2 -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -I POSTROUTING -o br3 -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -I brwan_masq -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -I ppp0_masq -s {}/32 -j MASQUERADE\n\
-            fi;\n\
-            if [ \"$1\" == \"stop\" ]; then\n\
-                \t/usr/sbin/iptables -t nat -D br0_masq -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -D POSTROUTING -o br1 -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -D POSTROUTING -o br2 -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -D POSTROUTING -o br3 -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -D brwan_masq -s {}/32 -j MASQUERADE\n
-                \t/usr/sbin/iptables -t nat -D ppp0_masq -s {}/32 -j MASQUERADE\n\
-            fi;\n\
-            ",
-            vip, vip, vip, vip, vip, vip,
-            vip, vip, vip, vip, vip, vip,
-        );
-
-    let path = "/etc/scripts/firewall/vppn_tunnel.rule";
-    if let Ok(mut file) = std::fs::File::create(&path) {
-        let _ = file.write_all(buf.as_bytes());
-    }
-    let _ = std::fs::set_permissions(&path, PermissionsExt::from_mode(0o755));
-}
-
-fn firewall_script_write(port: u16) {
-    let port = format!("{}", port);
-
-    let buf = "#! /bin/sh\n\
-    if [ \"$1\" == \"start\" ]; then\n\
-        \t/usr/sbin/iptables -I INPUT -i dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -I OUTPUT -o dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -I INPUT -i brwan -p udp --dport ".to_string() + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -I INPUT -i ppp0 -p udp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -I INPUT -i brwan -p tcp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -I INPUT -i ppp0 -p tcp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -I FORWARD -i dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -I FORWARD -o dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -t nat -I POSTROUTING -o dnet -j MASQUERADE\n\
-    fi;\n\
-    if [ \"$1\" == \"stop\" ]; then\n\
-        \t/usr/sbin/iptables -D INPUT -i dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -D OUTPUT -o dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -D INPUT -i brwan -p udp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -D INPUT -i ppp0 -p udp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -D INPUT -i brwan -p tcp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -D INPUT -i ppp0 -p tcp --dport " + &port + " -j ACCEPT\n\
-        \t/usr/sbin/iptables -D FORWARD -i dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -D FORWARD -o dnet -j ACCEPT\n\
-        \t/usr/sbin/iptables -t nat -D POSTROUTING -o dnet -j MASQUERADE\n\
-    fi;\n\
-    ";
-    let path = "/etc/scripts/firewall/vppn.rule";
-    if let Ok(mut file) = std::fs::File::create(&path) {
-        let _ = file.write_all(buf.as_bytes());
-    }
-    let _ = std::fs::set_permissions(&path, PermissionsExt::from_mode(0o755));
-}
-
-fn firewall_script_start() {
-    if let Ok(mut child) = Command::new("/etc/scripts/firewall/vppn.rule")
-        .arg("start").spawn() {
-        let _ = child.wait();
-    }
-}
-
-fn firewall_script_stop() {
-    if let Ok(mut child) = Command::new("/etc/scripts/firewall/vppn.rule")
-        .arg("stop").spawn() {
-        let _ = child.wait();
-    }
-}
-
-fn tunnel_firewall_script_start() {
-    if let Ok(mut child) = Command::new("/etc/scripts/firewall/vppn_tunnel.rule")
-        .arg("start").spawn() {
-        let _ = child.wait();
-    }
-}
-
-fn tunnel_firewall_script_stop() {
-    if let Ok(mut child) = Command::new("/etc/scripts/firewall/vppn_tunnel.rule")
-        .arg("stop").spawn() {
-        let _ = child.wait();
-    }
-}
-
-fn vpn_tunnel_firewall() {
-    let tun_buf = "dnet";
-
-    /* add rule in nat POSTROUTING */
-    let rule = IptablesRule::new(
-        "nat",
-        "POSTROUTING",
-        "",
-        "dnet",
-        "",
-        "",
-        "MASQUERADE"
-    );
-    if !iptables_find_rule(&rule) {
-        let rule_str = format!("-o {} -j MASQUERADE", tun_buf);
-        iptables_insert_rule("nat", "POSTROUTING", &rule_str, 0);
-    }
-
-    /* add rule in filter FORWARD */
-    let rule = IptablesRule::new(
-        "filter",
-        "FORWARD",
-        "br0",
-        tun_buf,
-        "",
-        "",
-        "ACCEPT"
-    );
-    if !iptables_find_rule(&rule) {
-        let rule_str = format!("-i br0 -o {} -j ACCEPT", tun_buf);
-        iptables_insert_rule("filter",
-                             "FORWARD",
-                             &rule_str,
-                             0);
-    }
-
-    /* add rule in filter FORWARD */
-    let rule = IptablesRule::new(
-        "filter",
-        "FORWARD",
-        tun_buf,
-        "br0",
-        "",
-        "",
-        "ACCEPT"
-    );
-    if !iptables_find_rule(&rule) {
-        let rule_str = format!("-i {} -o br0 -j ACCEPT", tun_buf);
-        iptables_insert_rule("filter",
-                             "FORWARD",
-                             &rule_str,
-                             0);
-    }
-
-    /* add rule in filter INPUT */
-    let rule = IptablesRule::new(
-        "filter",
-        "INPUT",
-        tun_buf,
-        "",
-        "",
-        "",
-        "ACCEPT");
-    if iptables_find_rule(&rule) {
-        let rule_str = format!("-i {} -j ACCEPT", tun_buf);
-        iptables_insert_rule("filter",
-                             "INPUT",
-                             &rule_str,
-                             0);
-    }
-
-    /* add rule in filter OUTPUT */
-    let rule = IptablesRule::new(
-        "filter",
-        "OUTPUT",
-        "",
-        tun_buf,
-        "",
-        "",
-        "ACCEPT");
-    if !iptables_find_rule(&rule) {
-        let rule_str = format!("-o {} -j ACCEPT", tun_buf);
-        iptables_insert_rule("filter", "OUTPUT", &rule_str, 0);
-    }
-}
\ No newline at end of file
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+    use std::str::FromStr;
+    use sandbox::firewall::Hook;
+    use super::{FirewallConfig, firewall_rules, tunnel_firewall_rules, include_script, fw4_include};
+
+    fn config() -> FirewallConfig {
+        FirewallConfig {
+            wan:    vec!["eth0".to_string()],
+            lan:    vec!["br-lan".to_string()],
+            tunnel: "dnet".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_firewall_rules() {
+        let rules = firewall_rules(&config(), 50069).iter()
+            .map(|(chain, rule)| chain.name.clone() + " " + &rule.to_iptables_args().join(" "))
+            .collect::<Vec<String>>();
+        assert_eq!(rules, vec![
+            "DNET_INPUT -i dnet -j ACCEPT",
+            "DNET_OUTPUT -o dnet -j ACCEPT",
+            "DNET_INPUT -i eth0 -p udp -m udp --dport 50069 -j ACCEPT",
+            "DNET_INPUT -i eth0 -p tcp -m tcp --dport 50069 -j ACCEPT",
+            "DNET_FORWARD -i dnet -j ACCEPT",
+            "DNET_FORWARD -o dnet -j ACCEPT",
+            "DNET_POSTROUTING -o dnet -j MASQUERADE",
+        ]);
+    }
+
+    #[test]
+    fn test_tunnel_firewall_rules() {
+        let vip = IpAddr::from_str("10.253.1.1").unwrap();
+        let rules = tunnel_firewall_rules(&config(), &vip).iter()
+            .map(|(_, rule)| rule.to_iptables_args().join(" "))
+            .collect::<Vec<String>>();
+        assert_eq!(rules, vec![
+            "-s 10.253.1.1/32 -o br-lan -j MASQUERADE",
+            "-s 10.253.1.1/32 -o eth0 -j MASQUERADE",
+        ]);
+    }
+
+    #[test]
+    fn test_include_script() {
+        let vip = IpAddr::from_str("10.253.1.1").unwrap();
+        assert_eq!(include_script(&tunnel_firewall_rules(&config(), &vip)),
+                   "#! /bin/sh\n\
+                   if [ \"$1\" == \"start\" ]; then\n\
+                   \t/usr/sbin/iptables -t nat -N DNET_POSTROUTING 2>/dev/null\n\
+                   \t/usr/sbin/iptables -t nat -C POSTROUTING -j DNET_POSTROUTING 2>/dev/null \
+                   || /usr/sbin/iptables -t nat -I POSTROUTING -j DNET_POSTROUTING\n\
+                   \t/usr/sbin/iptables -t nat -C DNET_POSTROUTING -s 10.253.1.1/32 -o br-lan -j MASQUERADE 2>/dev/null \
+                   || /usr/sbin/iptables -t nat -A DNET_POSTROUTING -s 10.253.1.1/32 -o br-lan -j MASQUERADE\n\
+                   \t/usr/sbin/iptables -t nat -C DNET_POSTROUTING -s 10.253.1.1/32 -o eth0 -j MASQUERADE 2>/dev/null \
+                   || /usr/sbin/iptables -t nat -A DNET_POSTROUTING -s 10.253.1.1/32 -o eth0 -j MASQUERADE\n\
+                   fi;\n\
+                   if [ \"$1\" == \"stop\" ]; then\n\
+                   \t/usr/sbin/iptables -t nat -D DNET_POSTROUTING -s 10.253.1.1/32 -o br-lan -j MASQUERADE 2>/dev/null\n\
+                   \t/usr/sbin/iptables -t nat -D DNET_POSTROUTING -s 10.253.1.1/32 -o eth0 -j MASQUERADE 2>/dev/null\n\
+                   fi;\n");
+    }
+
+    #[test]
+    fn test_fw4_include() {
+        let rules = firewall_rules(&config(), 50069);
+        assert_eq!(fw4_include(&rules, Hook::Input),
+                   "iifname \"dnet\" accept\n\
+                   iifname \"eth0\" udp dport 50069 accept\n\
+                   iifname \"eth0\" tcp dport 50069 accept\n");
+        assert_eq!(fw4_include(&rules, Hook::Forward),
+                   "iifname \"dnet\" accept\n\
+                   oifname \"dnet\" accept\n");
+    }
+}
@@ -8,7 +8,7 @@ pub mod firewall;
 pub mod team_status_response;
 
 pub fn get_sn() -> Option<String> {
-    let out = Command::new("artmtd").arg("-r").arg("sn").output().unwrap();
+    let out = Command::new("artmtd").arg("-r").arg("sn").output().ok()?;
     let res = String::from_utf8( out.stdout)
         .ok()?;
     let res: Vec<&str> = res.split("\n").collect();
@@ -16,7 +16,7 @@ pub fn get_sn() -> Option<String> {
         return None
     }
     else {
-        let res = res[0].replace("sn:", "");
-        Some(res)
+        let res = res[0].replace("sn:", "").trim().to_string();
+        Some(res).filter(|res|!res.is_empty())
     }
 }
\ No newline at end of file
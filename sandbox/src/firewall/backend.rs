@@ -0,0 +1,123 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use super::error::{Error, Result};
+use super::rule::{Chain, Rule};
+use super::iptables::Iptables;
+use super::nftables::Nftables;
+
+/// 防火墙后端. dnet的规则只写入自己拥有的链(DNET_*), 基础链中只有跳转规则.
+pub trait Firewall: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// 创建链, 有hook时同时挂到基础链上
+    fn ensure_chain(&self, chain: &Chain) -> Result<()>;
+
+    /// 规则不存在时追加
+    fn ensure_rule(&self, chain: &Chain, rule: &Rule) -> Result<()>;
+
+    fn delete_rule(&self, chain: &Chain, rule: &Rule) -> Result<()>;
+
+    fn flush_chain(&self, chain: &Chain) -> Result<()>;
+
+    /// 删除dnet拥有的所有链及基础链中的跳转
+    fn flush_owned(&self) -> Result<()>;
+
+    /// 列出dnet拥有的所有链及基础链中的跳转, 用于诊断
+    fn dump_owned(&self) -> Result<String>;
+
+    /// 入站方向(INPUT, nftables为所有hook input的链)的规则, 包括其他防火墙的规则, 用于诊断
+    fn input_rules(&self) -> Result<Vec<String>>;
+
+    /// 使链中的规则与rules一致. 默认实现先清空再添加, 期间规则短暂不生效, 后端应尽量原子替换.
+    fn replace_rules(&self, chain: &Chain, rules: &[Rule]) -> Result<()> {
+        self.ensure_chain(chain)?;
+        self.flush_chain(chain)?;
+        for rule in rules {
+            self.ensure_rule(chain, rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    Iptables,
+    Nftables,
+}
+
+/// 优先使用iptables-legacy, 只有nft(或iptables-nft)时使用nftables
+pub fn detect_backend() -> Option<Backend> {
+    let iptables_version = exec("iptables", &["-V"]).ok();
+    let nft = exec("nft", &["--version"]).is_ok();
+    match iptables_version {
+        Some(version) => {
+            if nft && version.contains("nf_tables") {
+                Some(Backend::Nftables)
+            }
+            else {
+                Some(Backend::Iptables)
+            }
+        }
+        None if nft => Some(Backend::Nftables),
+        None => None,
+    }
+}
+
+pub fn new_firewall(backend: Backend) -> Box<dyn Firewall> {
+    match backend {
+        Backend::Iptables => Box::new(Iptables),
+        Backend::Nftables => Box::new(Nftables),
+    }
+}
+
+/// 自动检测后端
+pub fn firewall() -> Result<Box<dyn Firewall>> {
+    detect_backend()
+        .map(new_firewall)
+        .ok_or(Error::NoBackend)
+}
+
+pub(super) fn check_owned(chain: &Chain) -> Result<()> {
+    if chain.is_owned() {
+        Ok(())
+    }
+    else {
+        Err(Error::NotOwnedChain(chain.name.clone()))
+    }
+}
+
+/// 执行命令, 返回stdout
+pub(super) fn exec(cmd: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new(cmd)
+        .args(args)
+        .output()
+        .map_err(Error::ExecCmd)?;
+    debug!("{} {:?} {:?}", cmd, args, output.status);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(Error::CmdFailed(format!("{} {} {}", cmd, args.join(" "), stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// 执行命令并从stdin写入input, 返回stdout
+pub(super) fn exec_with_input(cmd: &str, args: &[&str], input: &str) -> Result<String> {
+    let mut child = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::ExecCmd)?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes()).map_err(Error::ExecCmd)?;
+    }
+    let output = child.wait_with_output().map_err(Error::ExecCmd)?;
+    debug!("{} {:?} {:?}", cmd, args, output.status);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(Error::CmdFailed(format!("{} {} {}", cmd, args.join(" "), stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -1,7 +1,22 @@
 pub type Result<T> = std::result::Result<T, Error>;
 
+#[derive(err_derive::Error, Debug)]
 pub enum Error {
+    #[error(display = "NoTable")]
     NoTable,
+
+    #[error(display = "NoChain")]
     NoChain,
-}
 
+    #[error(display = "Chain {} is not owned by dnet", _0)]
+    NotOwnedChain(String),
+
+    #[error(display = "Neither iptables nor nft found")]
+    NoBackend,
+
+    #[error(display = "Exec firewall command failed")]
+    ExecCmd(#[error(cause)] std::io::Error),
+
+    #[error(display = "Firewall command failed {}", _0)]
+    CmdFailed(String),
+}
@@ -0,0 +1,199 @@
+use super::backend::{Firewall, check_owned, exec};
+use super::error::Result;
+use super::rule::{Chain, Hook, Rule, Table, Target, OWNED_CHAIN_PREFIX};
+
+/// replace_rules时新规则先写入的临时链, 名称为原链名加此后缀
+const STAGING_SUFFIX: &str = "_NEW";
+
+pub struct Iptables;
+
+impl Iptables {
+    fn iptables(table: Table, op: &str, chain: &str, rule: &[String]) -> Result<String> {
+        let mut args = vec!["-t", table.iptables_name(), op, chain];
+        args.append(&mut rule.iter().map(|arg| arg.as_str()).collect());
+        exec("iptables", &args)
+    }
+
+    fn jump(chain: &Chain) -> Vec<String> {
+        Rule::new(Target::Jump(chain.name.clone())).to_iptables_args()
+    }
+
+    fn exists(chain: &Chain) -> bool {
+        Self::iptables(chain.table, "-S", &chain.name, &[]).is_ok()
+    }
+
+    /// iptables -S 输出中dnet拥有的链, 链中的规则和跳转到这些链的规则
+    fn owned_lines(listing: &str) -> Vec<&str> {
+        listing.lines()
+            .filter(|line| {
+                let args = line.split_ascii_whitespace().collect::<Vec<&str>>();
+                args.len() >= 2 && (args[0] == "-N" || args[0] == "-A")
+                    && (args[1].starts_with(OWNED_CHAIN_PREFIX)
+                    || line.contains(&format!("-j {}", OWNED_CHAIN_PREFIX)))
+            })
+            .collect()
+    }
+
+    /// 删除基础链中的跳转和链本身
+    fn remove_chain(chain: &Chain, hook: Hook) -> Result<()> {
+        let jump = Self::jump(chain);
+        while Self::iptables(chain.table, "-C", hook.iptables_chain(), &jump).is_ok() {
+            Self::iptables(chain.table, "-D", hook.iptables_chain(), &jump)?;
+        }
+        Self::iptables(chain.table, "-F", &chain.name, &[])?;
+        Self::iptables(chain.table, "-X", &chain.name, &[]).map(|_| ())
+    }
+}
+
+impl Firewall for Iptables {
+    fn name(&self) -> &'static str {
+        "iptables"
+    }
+
+    fn ensure_chain(&self, chain: &Chain) -> Result<()> {
+        check_owned(chain)?;
+        if Self::iptables(chain.table, "-S", &chain.name, &[]).is_err() {
+            Self::iptables(chain.table, "-N", &chain.name, &[])?;
+        }
+        if let Some(hook) = chain.hook {
+            let jump = Self::jump(chain);
+            if Self::iptables(chain.table, "-C", hook.iptables_chain(), &jump).is_err() {
+                Self::iptables(chain.table, "-I", hook.iptables_chain(), &jump)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn ensure_rule(&self, chain: &Chain, rule: &Rule) -> Result<()> {
+        check_owned(chain)?;
+        let args = rule.to_iptables_args();
+        if Self::iptables(chain.table, "-C", &chain.name, &args).is_err() {
+            Self::iptables(chain.table, "-A", &chain.name, &args)?;
+        }
+        Ok(())
+    }
+
+    fn delete_rule(&self, chain: &Chain, rule: &Rule) -> Result<()> {
+        check_owned(chain)?;
+        let args = rule.to_iptables_args();
+        if Self::iptables(chain.table, "-C", &chain.name, &args).is_ok() {
+            Self::iptables(chain.table, "-D", &chain.name, &args)?;
+        }
+        Ok(())
+    }
+
+    fn flush_chain(&self, chain: &Chain) -> Result<()> {
+        check_owned(chain)?;
+        Self::iptables(chain.table, "-F", &chain.name, &[]).map(|_| ())
+    }
+
+    fn flush_owned(&self) -> Result<()> {
+        for table in &[Table::Filter, Table::Nat] {
+            let listing = exec("iptables", &["-t", table.iptables_name(), "-S"])?;
+            // 先删除基础链中的跳转, 被引用的链不能删除
+            for line in listing.lines() {
+                let args = line.split_ascii_whitespace().collect::<Vec<&str>>();
+                if args.len() > 2 && args[0] == "-A" && !args[1].starts_with(OWNED_CHAIN_PREFIX)
+                    && line.contains(&format!("-j {}", OWNED_CHAIN_PREFIX)) {
+                    let rule = args[2..].iter().map(|arg| arg.to_string()).collect::<Vec<String>>();
+                    Self::iptables(*table, "-D", args[1], &rule)?;
+                }
+            }
+            for line in listing.lines() {
+                let args = line.split_ascii_whitespace().collect::<Vec<&str>>();
+                if args.len() == 2 && args[0] == "-N" && args[1].starts_with(OWNED_CHAIN_PREFIX) {
+                    Self::iptables(*table, "-F", args[1], &[])?;
+                }
+            }
+            for line in listing.lines() {
+                let args = line.split_ascii_whitespace().collect::<Vec<&str>>();
+                if args.len() == 2 && args[0] == "-N" && args[1].starts_with(OWNED_CHAIN_PREFIX) {
+                    Self::iptables(*table, "-X", args[1], &[])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn dump_owned(&self) -> Result<String> {
+        let mut buf = String::new();
+        for table in &[Table::Filter, Table::Nat] {
+            let listing = exec("iptables", &["-t", table.iptables_name(), "-S"])?;
+            buf += &format!("*{}\n", table.iptables_name());
+            for line in Self::owned_lines(&listing) {
+                buf += line;
+                buf += "\n";
+            }
+        }
+        Ok(buf)
+    }
+
+    fn input_rules(&self) -> Result<Vec<String>> {
+        Ok(exec("iptables", &["-S", "INPUT"])?
+            .lines()
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    /// 有hook的链: 新规则写入临时链, 跳转插到旧链之前, 再删除旧链并把临时链改名,
+    /// 替换过程中始终有一条完整的规则链生效.
+    /// 没有hook的链由其他链跳转, 按默认实现清空后添加.
+    fn replace_rules(&self, chain: &Chain, rules: &[Rule]) -> Result<()> {
+        check_owned(chain)?;
+        let hook = match chain.hook {
+            Some(hook) => hook,
+            None => {
+                self.ensure_chain(chain)?;
+                self.flush_chain(chain)?;
+                for rule in rules {
+                    self.ensure_rule(chain, rule)?;
+                }
+                return Ok(());
+            }
+        };
+        let staging = Chain::new(chain.table, &(chain.name.clone() + STAGING_SUFFIX), Some(hook));
+
+        // 上次替换中断: 旧链已删除时临时链就是当前生效的规则, 先改回原名; 否则丢弃
+        if Self::exists(&staging) {
+            if Self::exists(chain) {
+                Self::remove_chain(&staging, hook)?;
+            }
+            else {
+                Self::iptables(chain.table, "-E", &staging.name, &[chain.name.clone()])?;
+            }
+        }
+
+        Self::iptables(chain.table, "-N", &staging.name, &[])?;
+        for rule in rules {
+            Self::iptables(chain.table, "-A", &staging.name, &rule.to_iptables_args())?;
+        }
+        Self::iptables(chain.table, "-I", hook.iptables_chain(), &Self::jump(&staging))?;
+        if Self::exists(chain) {
+            Self::remove_chain(chain, hook)?;
+        }
+        Self::iptables(chain.table, "-E", &staging.name, &[chain.name.clone()]).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Iptables;
+
+    #[test]
+    fn test_owned_lines() {
+        let listing = "-P INPUT ACCEPT\n\
+                       -N DNET_TEAM\n\
+                       -N DOCKER\n\
+                       -A INPUT -j DNET_TEAM\n\
+                       -A INPUT -i eth0 -j ACCEPT\n\
+                       -A DNET_TEAM -s 10.1.1.2/32 -i dnet -j ACCEPT\n\
+                       -A DNET_TEAM -i dnet -j DROP\n\
+                       -A DOCKER -j RETURN\n";
+        assert_eq!(Iptables::owned_lines(listing), vec![
+            "-N DNET_TEAM",
+            "-A INPUT -j DNET_TEAM",
+            "-A DNET_TEAM -s 10.1.1.2/32 -i dnet -j ACCEPT",
+            "-A DNET_TEAM -i dnet -j DROP",
+        ]);
+    }
+}
@@ -29,6 +29,39 @@ pub fn parse_iptables_table_chain(table: &str, chain: &str) -> Vec<IptablesRule>
     return vec![];
 }
 
+/// iptables -S 格式的规则, 第一行为 -P 默认策略
+pub fn iptables_list_rules(table: &str, chain: &str) -> Vec<String> {
+    Command::new("iptables")
+        .args(vec!["-t", table, "-S", chain])
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|out| out.lines()
+            .map(|line| line.to_owned())
+            .collect())
+        .unwrap_or(vec![])
+}
+
+/// 执行iptables并等待结束, 返回是否成功
+pub fn iptables_exec(args: &[&str]) -> bool {
+    Command::new("iptables")
+        .args(args)
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+pub fn iptables_flush_chain(table: &str, chain: &str) {
+    iptables_exec(&["-t", table, "-F", chain]);
+}
+
+/// iptables -C, rule_str按空白分割
+pub fn iptables_rule_exists(table: &str, chain: &str, rule_str: &str) -> bool {
+    let mut args = vec!["-t", table, "-C", chain];
+    args.append(&mut rule_str.split_ascii_whitespace().collect::<Vec<&str>>());
+    iptables_exec(&args)
+}
+
 pub fn iptabels_create_chain(table: &str, chain: &str) {
     let res = Command::new("iptables").args(vec![
         "-t",
@@ -2,4 +2,15 @@
 #[path = "linux.rs"]
 pub mod imp;
 pub mod error;
-pub mod types;
\ No newline at end of file
+pub mod types;
+pub mod rule;
+#[cfg(target_os = "linux")]
+mod backend;
+#[cfg(target_os = "linux")]
+mod iptables;
+#[cfg(target_os = "linux")]
+mod nftables;
+
+#[cfg(target_os = "linux")]
+pub use backend::{Firewall, Backend, detect_backend, new_firewall, firewall};
+pub use rule::{Chain, Hook, Proto, Rule, Table, Target};
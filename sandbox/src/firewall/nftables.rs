@@ -0,0 +1,162 @@
+use super::backend::{Firewall, check_owned, exec, exec_with_input};
+use super::error::Result;
+use super::rule::{Chain, Rule, NFT_TABLE, find_nft_handle};
+
+/// 规则都在 table ip dnet 中. nftables中每个表的hook链都会被依次执行, accept只结束本表的处理,
+/// 其它表(fw4, firewalld)中的drop/reject仍然生效, 因此只有drop和NAT规则是可靠的;
+/// 需要放行的流量要写入防火墙自己的表, 如OpenWrt fw4的include.
+pub struct Nftables;
+
+impl Nftables {
+    fn nft(args: &[&str]) -> Result<String> {
+        exec("nft", args)
+    }
+
+    fn list_chain(chain: &Chain) -> Result<String> {
+        Self::nft(&["-a", "list", "chain", "ip", NFT_TABLE, &chain.name])
+    }
+
+    /// nft list ruleset 中hook input的链的内容, 各表的input链都会被执行
+    fn input_chain_lines(ruleset: &str) -> Vec<String> {
+        let mut lines = vec![];
+        let mut chain: Option<Vec<String>> = None;
+        for line in ruleset.lines().map(|line| line.trim()) {
+            if line.starts_with("chain ") && line.ends_with('{') {
+                chain = Some(vec![]);
+            }
+            else if line == "}" {
+                if let Some(chain) = chain.take() {
+                    if chain.iter().any(|line| line.contains("hook input")) {
+                        lines.extend(chain);
+                    }
+                }
+            }
+            else if let Some(chain) = chain.as_mut() {
+                if !line.is_empty() {
+                    chain.push(line.to_string());
+                }
+            }
+        }
+        lines
+    }
+
+    /// nft -f 在一个事务中执行, 清空和添加规则之间没有空窗
+    fn replace_script(chain: &Chain, rules: &[Rule]) -> String {
+        let mut script = format!("add table ip {}\n", NFT_TABLE);
+        script += format!("add chain ip {} {} {}", NFT_TABLE, chain.name, chain.to_nft()).trim_end();
+        script += "\n";
+        script += &format!("flush chain ip {} {}\n", NFT_TABLE, chain.name);
+        for rule in rules {
+            script += &format!("add rule ip {} {} {}\n", NFT_TABLE, chain.name, rule.to_nft());
+        }
+        script
+    }
+}
+
+impl Firewall for Nftables {
+    fn name(&self) -> &'static str {
+        "nftables"
+    }
+
+    fn ensure_chain(&self, chain: &Chain) -> Result<()> {
+        check_owned(chain)?;
+        // add table/chain 已存在时不报错
+        Self::nft(&["add", "table", "ip", NFT_TABLE])?;
+        let definition = chain.to_nft();
+        if definition.is_empty() {
+            Self::nft(&["add", "chain", "ip", NFT_TABLE, &chain.name])?;
+        }
+        else {
+            Self::nft(&["add", "chain", "ip", NFT_TABLE, &chain.name, &definition])?;
+        }
+        Ok(())
+    }
+
+    fn ensure_rule(&self, chain: &Chain, rule: &Rule) -> Result<()> {
+        check_owned(chain)?;
+        let stmt = rule.to_nft();
+        if find_nft_handle(&Self::list_chain(chain)?, &stmt).is_none() {
+            let mut args = vec!["add", "rule", "ip", NFT_TABLE, &chain.name];
+            args.append(&mut stmt.split(' ').collect());
+            Self::nft(&args)?;
+        }
+        Ok(())
+    }
+
+    fn delete_rule(&self, chain: &Chain, rule: &Rule) -> Result<()> {
+        check_owned(chain)?;
+        if let Some(handle) = find_nft_handle(&Self::list_chain(chain)?, &rule.to_nft()) {
+            let handle = handle.to_string();
+            Self::nft(&["delete", "rule", "ip", NFT_TABLE, &chain.name, "handle", &handle])?;
+        }
+        Ok(())
+    }
+
+    fn flush_chain(&self, chain: &Chain) -> Result<()> {
+        check_owned(chain)?;
+        Self::nft(&["flush", "chain", "ip", NFT_TABLE, &chain.name]).map(|_| ())
+    }
+
+    fn flush_owned(&self) -> Result<()> {
+        if Self::nft(&["list", "table", "ip", NFT_TABLE]).is_ok() {
+            Self::nft(&["delete", "table", "ip", NFT_TABLE])?;
+        }
+        Ok(())
+    }
+
+    fn input_rules(&self) -> Result<Vec<String>> {
+        Ok(Self::input_chain_lines(&Self::nft(&["list", "ruleset"])?))
+    }
+
+    /// dnet的规则都在 table ip dnet 中, 表不存在时返回空
+    fn dump_owned(&self) -> Result<String> {
+        Ok(Self::nft(&["list", "table", "ip", NFT_TABLE]).unwrap_or_default())
+    }
+
+    fn replace_rules(&self, chain: &Chain, rules: &[Rule]) -> Result<()> {
+        check_owned(chain)?;
+        exec_with_input("nft", &["-f", "/dev/stdin"], &Self::replace_script(chain, rules))
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Nftables;
+    use super::super::rule::{Chain, Hook, Rule, Table, Target};
+
+    #[test]
+    fn test_input_chain_lines() {
+        let ruleset = "table inet fw4 {\n\
+                       \tchain input {\n\
+                       \t\ttype filter hook input priority filter; policy drop;\n\
+                       \t\tudp dport 50069 accept\n\
+                       \t}\n\
+                       \tchain forward {\n\
+                       \t\ttype filter hook forward priority filter; policy drop;\n\
+                       \t}\n\
+                       \tchain input_wan {\n\
+                       \t\ttcp dport 22 reject\n\
+                       \t}\n\
+                       }\n";
+        assert_eq!(Nftables::input_chain_lines(ruleset), vec![
+            "type filter hook input priority filter; policy drop;".to_string(),
+            "udp dport 50069 accept".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn test_replace_script() {
+        let chain = Chain::new(Table::Filter, "DNET_TEAM", Some(Hook::Input));
+        let rules = vec![
+            Rule::new(Target::Accept).in_("dnet").src("10.1.1.2"),
+            Rule::new(Target::Drop).in_("dnet"),
+        ];
+        assert_eq!(Nftables::replace_script(&chain, &rules),
+                   "add table ip dnet\n\
+                   add chain ip dnet DNET_TEAM { type filter hook input priority 0; policy accept; }\n\
+                   flush chain ip dnet DNET_TEAM\n\
+                   add rule ip dnet DNET_TEAM iifname \"dnet\" ip saddr 10.1.1.2 accept\n\
+                   add rule ip dnet DNET_TEAM iifname \"dnet\" drop\n");
+    }
+}
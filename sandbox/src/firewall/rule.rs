@@ -0,0 +1,307 @@
+//! 与后端无关的防火墙规则, 分别渲染为iptables参数和nft语句
+
+/// dnet拥有的链都以此为前缀, flush_owned只清理这些链
+pub const OWNED_CHAIN_PREFIX: &str = "DNET_";
+/// nftables下dnet的规则都在这个表中
+pub const NFT_TABLE: &str = "dnet";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Table {
+    Filter,
+    Nat,
+}
+
+impl Table {
+    pub fn iptables_name(&self) -> &'static str {
+        match self {
+            Table::Filter => "filter",
+            Table::Nat => "nat",
+        }
+    }
+}
+
+/// 挂载dnet链的基础链
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Hook {
+    Input,
+    Forward,
+    Output,
+    Postrouting,
+}
+
+impl Hook {
+    pub fn iptables_chain(&self) -> &'static str {
+        match self {
+            Hook::Input => "INPUT",
+            Hook::Forward => "FORWARD",
+            Hook::Output => "OUTPUT",
+            Hook::Postrouting => "POSTROUTING",
+        }
+    }
+
+    fn nft_hook(&self) -> &'static str {
+        match self {
+            Hook::Input => "input",
+            Hook::Forward => "forward",
+            Hook::Output => "output",
+            Hook::Postrouting => "postrouting",
+        }
+    }
+}
+
+/// dnet拥有的链. hook不为空时由对应的基础链无条件跳转过来.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chain {
+    pub table:      Table,
+    pub name:       String,
+    pub hook:       Option<Hook>,
+}
+
+impl Chain {
+    pub fn new(table: Table, name: &str, hook: Option<Hook>) -> Self {
+        Self {
+            table,
+            name: name.to_string(),
+            hook,
+        }
+    }
+
+    pub fn is_owned(&self) -> bool {
+        self.name.starts_with(OWNED_CHAIN_PREFIX)
+    }
+
+    /// nft add chain 的链定义
+    pub fn to_nft(&self) -> String {
+        match self.hook {
+            Some(hook) => {
+                let (chain_type, priority) = match self.table {
+                    Table::Filter => ("filter", 0),
+                    Table::Nat => ("nat", 100),
+                };
+                format!("{{ type {} hook {} priority {}; policy accept; }}",
+                        chain_type, hook.nft_hook(), priority)
+            }
+            None => String::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Proto {
+    Tcp,
+    Udp,
+}
+
+impl Proto {
+    fn name(&self) -> &'static str {
+        match self {
+            Proto::Tcp => "tcp",
+            Proto::Udp => "udp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Accept,
+    Drop,
+    Masquerade,
+    Jump(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub in_:        Option<String>,
+    pub out:        Option<String>,
+    /// ip或cidr
+    pub src:        Option<String>,
+    pub dst:        Option<String>,
+    pub proto:      Option<Proto>,
+    pub dport:      Option<u16>,
+    pub target:     Target,
+}
+
+impl Rule {
+    pub fn new(target: Target) -> Self {
+        Self {
+            in_:    None,
+            out:    None,
+            src:    None,
+            dst:    None,
+            proto:  None,
+            dport:  None,
+            target,
+        }
+    }
+
+    pub fn in_(mut self, dev: &str) -> Self {
+        self.in_ = Some(dev.to_string());
+        self
+    }
+
+    pub fn out(mut self, dev: &str) -> Self {
+        self.out = Some(dev.to_string());
+        self
+    }
+
+    pub fn src(mut self, src: &str) -> Self {
+        self.src = Some(src.to_string());
+        self
+    }
+
+    pub fn dst(mut self, dst: &str) -> Self {
+        self.dst = Some(dst.to_string());
+        self
+    }
+
+    pub fn dport(mut self, proto: Proto, port: u16) -> Self {
+        self.proto = Some(proto);
+        self.dport = Some(port);
+        self
+    }
+
+    /// iptables -A <chain> 之后的参数, 与 iptables -S 输出的顺序一致
+    pub fn to_iptables_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(src) = &self.src {
+            args.push("-s".to_string());
+            args.push(with_prefix(src));
+        }
+        if let Some(dst) = &self.dst {
+            args.push("-d".to_string());
+            args.push(with_prefix(dst));
+        }
+        if let Some(in_) = &self.in_ {
+            args.push("-i".to_string());
+            args.push(in_.clone());
+        }
+        if let Some(out) = &self.out {
+            args.push("-o".to_string());
+            args.push(out.clone());
+        }
+        if let Some(proto) = &self.proto {
+            args.push("-p".to_string());
+            args.push(proto.name().to_string());
+            if let Some(dport) = self.dport {
+                args.push("-m".to_string());
+                args.push(proto.name().to_string());
+                args.push("--dport".to_string());
+                args.push(dport.to_string());
+            }
+        }
+        args.push("-j".to_string());
+        args.push(match &self.target {
+            Target::Accept => "ACCEPT".to_string(),
+            Target::Drop => "DROP".to_string(),
+            Target::Masquerade => "MASQUERADE".to_string(),
+            Target::Jump(chain) => chain.clone(),
+        });
+        args
+    }
+
+    /// nft add rule 的语句, 与 nft list chain 输出的格式一致
+    pub fn to_nft(&self) -> String {
+        let mut stmts = vec![];
+        if let Some(in_) = &self.in_ {
+            stmts.push(format!("iifname \"{}\"", in_));
+        }
+        if let Some(out) = &self.out {
+            stmts.push(format!("oifname \"{}\"", out));
+        }
+        if let Some(src) = &self.src {
+            stmts.push(format!("ip saddr {}", without_host_prefix(src)));
+        }
+        if let Some(dst) = &self.dst {
+            stmts.push(format!("ip daddr {}", without_host_prefix(dst)));
+        }
+        if let Some(proto) = &self.proto {
+            match self.dport {
+                Some(dport) => stmts.push(format!("{} dport {}", proto.name(), dport)),
+                None => stmts.push(format!("meta l4proto {}", proto.name())),
+            }
+        }
+        stmts.push(match &self.target {
+            Target::Accept => "accept".to_string(),
+            Target::Drop => "drop".to_string(),
+            Target::Masquerade => "masquerade".to_string(),
+            Target::Jump(chain) => format!("jump {}", chain),
+        });
+        stmts.join(" ")
+    }
+}
+
+/// iptables -S 总是输出前缀长度
+fn with_prefix(addr: &str) -> String {
+    if addr.contains('/') {
+        addr.to_string()
+    }
+    else {
+        addr.to_string() + "/32"
+    }
+}
+
+/// nft list 省略 /32
+fn without_host_prefix(addr: &str) -> String {
+    addr.trim_end_matches("/32").to_string()
+}
+
+/// nft -a list chain 的输出中查找规则的handle
+pub fn find_nft_handle(listing: &str, rule: &str) -> Option<u64> {
+    listing.lines()
+        .filter_map(|line| {
+            let mut parts = line.trim().rsplitn(2, " # handle ");
+            let handle = parts.next()?.parse::<u64>().ok()?;
+            let stmt = parts.next()?;
+            Some((stmt.trim().to_string(), handle))
+        })
+        .find(|(stmt, _)| stmt == rule)
+        .map(|(_, handle)| handle)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chain, Hook, Proto, Rule, Table, Target, find_nft_handle};
+
+    #[test]
+    fn test_render_rule() {
+        let rule = Rule::new(Target::Accept)
+            .in_("dnet")
+            .out("dnet")
+            .src("10.1.1.1")
+            .dst("10.1.1.0/24");
+        assert_eq!(rule.to_iptables_args().join(" "),
+                   "-s 10.1.1.1/32 -d 10.1.1.0/24 -i dnet -o dnet -j ACCEPT");
+        assert_eq!(rule.to_nft(),
+                   "iifname \"dnet\" oifname \"dnet\" ip saddr 10.1.1.1 ip daddr 10.1.1.0/24 accept");
+
+        let rule = Rule::new(Target::Accept).in_("ppp0").dport(Proto::Udp, 50069);
+        assert_eq!(rule.to_iptables_args().join(" "),
+                   "-i ppp0 -p udp -m udp --dport 50069 -j ACCEPT");
+        assert_eq!(rule.to_nft(), "iifname \"ppp0\" udp dport 50069 accept");
+
+        let rule = Rule::new(Target::Jump("DNET_TEAM".to_string()));
+        assert_eq!(rule.to_iptables_args().join(" "), "-j DNET_TEAM");
+        assert_eq!(rule.to_nft(), "jump DNET_TEAM");
+    }
+
+    #[test]
+    fn test_render_chain() {
+        let chain = Chain::new(Table::Nat, "DNET_POSTROUTING", Some(Hook::Postrouting));
+        assert!(chain.is_owned());
+        assert_eq!(chain.to_nft(),
+                   "{ type nat hook postrouting priority 100; policy accept; }");
+        assert!(!Chain::new(Table::Filter, "FORWARD", None).is_owned());
+    }
+
+    #[test]
+    fn test_find_nft_handle() {
+        let listing = "table ip dnet {\n\
+            \tchain DNET_TEAM { # handle 2\n\
+            \t\tiifname \"dnet\" oifname \"dnet\" ip saddr 10.1.1.1 ip daddr 10.1.1.2 accept # handle 5\n\
+            \t\tiifname \"dnet\" oifname \"dnet\" drop # handle 6\n\
+            \t}\n\
+            }\n";
+        assert_eq!(find_nft_handle(listing, "iifname \"dnet\" oifname \"dnet\" drop"), Some(6));
+        assert_eq!(find_nft_handle(listing, "accept"), None);
+    }
+}
@@ -0,0 +1,149 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::process::Command;
+
+use super::error::{Error, Result};
+
+/// 设置网卡地址, 掩码(CIDR)和MTU, 并启用网卡.
+/// 参数直接作为命令参数传递, 不经过shell.
+pub fn set_interface(dev: &str, ip: &IpAddr, netmask: u32, mtu: Option<u32>) -> Result<()> {
+    if !ip.is_ipv4() {
+        return Err(Error::ipv6_not_support);
+    }
+    for (cmd, args) in set_interface_cmds(dev, ip, netmask, mtu) {
+        exec(cmd, &args.iter().map(|arg|arg.as_str()).collect::<Vec<&str>>())?;
+    }
+    Ok(())
+}
+
+/// 删除set_interface设置的地址
+pub fn clear_interface(dev: &str, ip: &IpAddr, netmask: u32) -> Result<()> {
+    for (cmd, args) in clear_interface_cmds(dev, ip, netmask) {
+        exec(cmd, &args.iter().map(|arg|arg.as_str()).collect::<Vec<&str>>())?;
+    }
+    Ok(())
+}
+
+fn set_interface_cmds(dev: &str, ip: &IpAddr, netmask: u32, mtu: Option<u32>)
+    -> Vec<(&'static str, Vec<String>)> {
+    let mut cmds = vec![];
+    #[cfg(target_os = "linux")]
+        {
+            cmds.push(("ip", args(&["link", "set", "dev", dev, "up"])));
+            cmds.push(("ip", args(&["addr", "replace", &format!("{}/{}", ip, netmask), "dev", dev])));
+            if let Some(mtu) = mtu {
+                cmds.push(("ip", args(&["link", "set", "dev", dev, "mtu", &mtu.to_string()])));
+            }
+        }
+    #[cfg(target_os = "macos")]
+        {
+            cmds.push(("ifconfig", args(&[dev, "inet", &ip.to_string(),
+                "netmask", &cidr_to_netmask(netmask).to_string(), "up"])));
+            if let Some(mtu) = mtu {
+                cmds.push(("ifconfig", args(&[dev, "mtu", &mtu.to_string()])));
+            }
+        }
+    #[cfg(windows)]
+        {
+            cmds.push(("netsh", args(&["interface", "ipv4", "set", "address",
+                &format!("name={}", dev), "source=static",
+                &format!("addr={}", ip), &format!("mask={}", cidr_to_netmask(netmask))])));
+            if let Some(mtu) = mtu {
+                cmds.push(("netsh", args(&["interface", "ipv4", "set", "subinterface", dev,
+                    &format!("mtu={}", mtu), "store=active"])));
+            }
+        }
+    cmds
+}
+
+fn clear_interface_cmds(dev: &str, ip: &IpAddr, netmask: u32) -> Vec<(&'static str, Vec<String>)> {
+    let mut cmds = vec![];
+    #[cfg(target_os = "linux")]
+        cmds.push(("ip", args(&["addr", "del", &format!("{}/{}", ip, netmask), "dev", dev])));
+    #[cfg(target_os = "macos")]
+        {
+            let _ = netmask;
+            cmds.push(("ifconfig", args(&[dev, "inet", &ip.to_string(), "delete"])));
+        }
+    #[cfg(windows)]
+        {
+            let _ = (ip, netmask);
+            cmds.push(("netsh", args(&["interface", "ipv4", "set", "address",
+                &format!("name={}", dev), "source=dhcp"])));
+        }
+    cmds
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg|arg.to_string()).collect()
+}
+
+pub fn cidr_to_netmask(netmask: u32) -> Ipv4Addr {
+    let mask = u32::max_value()
+        .checked_shl(32 - netmask.min(32))
+        .unwrap_or(0);
+    Ipv4Addr::from(mask)
+}
+
+fn exec(cmd: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(cmd)
+        .args(args)
+        .output()
+        .map_err(Error::exec_interface_cmd)?;
+    info!("{} {:?} {:?}", cmd, args, output.status);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(Error::interface_cmd_failed(format!("{} {} {}", cmd, args.join(" "), stderr)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{cidr_to_netmask, set_interface_cmds, clear_interface_cmds};
+
+    #[test]
+    fn test_cidr_to_netmask() {
+        assert_eq!(cidr_to_netmask(8), Ipv4Addr::new(255, 0, 0, 0));
+        assert_eq!(cidr_to_netmask(32), Ipv4Addr::new(255, 255, 255, 255));
+        assert_eq!(cidr_to_netmask(0), Ipv4Addr::new(0, 0, 0, 0));
+    }
+
+    fn render(cmds: Vec<(&str, Vec<String>)>) -> Vec<String> {
+        cmds.into_iter()
+            .map(|(cmd, args)|cmd.to_string() + " " + &args.join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn test_interface_cmds() {
+        let ip = IpAddr::from(Ipv4Addr::new(10, 253, 1, 2));
+        let set = render(set_interface_cmds("dnet", &ip, 8, Some(1400)));
+        let clear = render(clear_interface_cmds("dnet", &ip, 8));
+        #[cfg(target_os = "linux")]
+            {
+                assert_eq!(set, vec![
+                    "ip link set dev dnet up",
+                    "ip addr replace 10.253.1.2/8 dev dnet",
+                    "ip link set dev dnet mtu 1400",
+                ]);
+                assert_eq!(clear, vec!["ip addr del 10.253.1.2/8 dev dnet"]);
+            }
+        #[cfg(target_os = "macos")]
+            {
+                assert_eq!(set, vec![
+                    "ifconfig dnet inet 10.253.1.2 netmask 255.0.0.0 up",
+                    "ifconfig dnet mtu 1400",
+                ]);
+                assert_eq!(clear, vec!["ifconfig dnet inet 10.253.1.2 delete"]);
+            }
+        #[cfg(windows)]
+            {
+                assert_eq!(set, vec![
+                    "netsh interface ipv4 set address name=dnet source=static addr=10.253.1.2 mask=255.0.0.0",
+                    "netsh interface ipv4 set subinterface dnet mtu=1400 store=active",
+                ]);
+                assert_eq!(clear, vec!["netsh interface ipv4 set address name=dnet source=dhcp"]);
+            }
+    }
+}
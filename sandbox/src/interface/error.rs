@@ -10,4 +10,13 @@ pub enum Error {
 
     #[error(display = "default_interface_not_found")]
     default_interface_not_found,
+
+    #[error(display = "exec_interface_cmd")]
+    exec_interface_cmd(#[error(cause)] std::io::Error),
+
+    #[error(display = "interface_cmd_failed {}", _0)]
+    interface_cmd_failed(String),
+
+    #[error(display = "ipv6_not_support")]
+    ipv6_not_support,
 }
@@ -1,4 +1,6 @@
 pub mod error;
+mod config;
+pub use config::{set_interface, clear_interface, cidr_to_netmask};
 use error::{Error, Result};
 
 use std::net::{IpAddr, Ipv4Addr};
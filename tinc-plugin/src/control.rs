@@ -1,7 +1,7 @@
 use super::tinc_tcp_stream::Result;
 
 use super::tinc_tcp_stream::TincStream;
-use crate::tinc_tcp_stream::SourceConnection;
+use crate::tinc_tcp_stream::{SourceConnection, SourceTraffic};
 
 pub fn stop(pid_path: &str) -> Result<()> {
     let mut tinc_stream = TincStream::new(pid_path)?;
@@ -77,10 +77,9 @@ pub fn disconnect(pid_path: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn dump_traffic(pid_path: &str) -> Result<()> {
+pub fn dump_traffic(pid_path: &str) -> Result<Vec<SourceTraffic>> {
     let mut tinc_stream = TincStream::new(pid_path)?;
-    tinc_stream.dump_traffic()?;
-    Ok(())
+    tinc_stream.dump_traffic()
 }
 
 pub fn pcap(pid_path: &str) -> Result<()> {
@@ -14,6 +14,7 @@ pub struct ConnectTo {
     pub vip:                IpAddr,
     pub port:               u16,
     pub pubkey:             String,
+    pub ed25519_pubkey:     Option<String>,
 }
 impl ConnectTo {
     pub fn from(id: String, ip:IpAddr, vip:IpAddr, port: u16, pubkey:String) -> Self {
@@ -23,6 +24,7 @@ impl ConnectTo {
             vip,
             port,
             pubkey,
+            ed25519_pubkey: None,
         }
     }
 }
@@ -33,6 +35,7 @@ pub struct TincInfo {
     pub vip:        IpAddr,
     pub port:       u16,
     pub pub_key:    String,
+    pub ed25519_pub_key: Option<String>,
     pub mode:       TincRunMode,
     pub connect_to: Vec<ConnectTo>,
 }
@@ -52,6 +55,7 @@ impl TincInfo {
             vip,
             port: self_tinc_port,
             pub_key,
+            ed25519_pub_key: None,
             mode,
             connect_to,
         }
@@ -6,8 +6,9 @@ extern crate serde;
 extern crate serde_derive;
 
 mod operator;
-pub use operator::{TincSettings, TincTools, TincOperator,
-                   Error as TincOperatorError, PUB_KEY_FILENAME, PID_FILENAME, DEFAULT_TINC_PORT};
+pub use operator::{TincSettings, TincTools, TincOperator, TincConfig, ConfigChange,
+                   Error as TincOperatorError, PUB_KEY_FILENAME, PID_FILENAME, TINC_BIN_FILENAME, DEFAULT_TINC_PORT,
+                   DEFAULT_KEY_MAX_AGE_DAYS};
 mod info;
 pub mod tinc_tcp_stream;
 pub mod control;
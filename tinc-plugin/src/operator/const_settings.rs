@@ -32,6 +32,20 @@ pub const PRIV_KEY_FILENAME: &str = "rsa_key.priv";
 
 pub const PUB_KEY_FILENAME: &str = "rsa_key.pub";
 
+pub const OLD_PRIV_KEY_FILENAME: &str = "rsa_key.priv.old";
+
+pub const OLD_PUB_KEY_FILENAME: &str = "rsa_key.pub.old";
+
+pub const ED25519_PRIV_KEY_FILENAME: &str = "ed25519_key.priv";
+
+pub const ED25519_PUB_KEY_FILENAME: &str = "ed25519_key.pub";
+
+pub const OLD_ED25519_PRIV_KEY_FILENAME: &str = "ed25519_key.priv.old";
+
+pub const OLD_ED25519_PUB_KEY_FILENAME: &str = "ed25519_key.pub.old";
+
 pub const PID_FILENAME: &str = "tinc.pid";
 
-pub const DEFAULT_TINC_PORT: u16 = (50069 as u16);
\ No newline at end of file
+pub const DEFAULT_TINC_PORT: u16 = (50069 as u16);
+
+pub const DEFAULT_KEY_MAX_AGE_DAYS: u32 = 30;
\ No newline at end of file
@@ -65,6 +65,18 @@ pub enum Error {
     #[error(display = "Tinc can't create key pair")]
     CreatePubKeyError,
 
+    /// Failed to publish rotated key, old key pair restored
+    #[error(display = "Key rotation failed")]
+    KeyRotationError(String),
+
+    /// New config made tinc unhealthy, previous config restored and running
+    #[error(display = "Tinc config rolled back")]
+    ConfigRolledBack,
+
+    /// Invalid tinc.conf variable
+    #[error(display = "Invalid tinc config {}", _0)]
+    TincConfigError(String),
+
     /// Invalid tinc info
     #[error(display = "Invalid tinc info")]
     TincInfoError(String),
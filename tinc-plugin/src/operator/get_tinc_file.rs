@@ -5,7 +5,7 @@ use std::str::FromStr;
 
 use super::{Error, Result};
 use super::TincOperator;
-use super::{PUB_KEY_FILENAME, TINC_UP_FILENAME};
+use super::{PUB_KEY_FILENAME, ED25519_PUB_KEY_FILENAME, TINC_UP_FILENAME};
 
 impl TincOperator {
     /// 从pub_key文件读取pub_key
@@ -20,6 +20,17 @@ impl TincOperator {
         Ok(buf)
     }
 
+    /// 读取本地Ed25519公钥, 未开启ed25519_key时返回None
+    pub fn get_local_ed25519_pub_key(&self) -> Result<Option<String>> {
+        if !self.tinc_settings.ed25519_key {
+            return Ok(None);
+        }
+        let path = self.tinc_settings.tinc_home.clone() + ED25519_PUB_KEY_FILENAME;
+        fs::read_to_string(&path)
+            .map(|key|Some(key.trim().to_string()))
+            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))
+    }
+
     /// 获取本地tinc虚拟ip
     pub fn get_local_vip(&self) -> Result<IpAddr> {
         let _guard = self.mutex.lock().unwrap();
@@ -32,18 +43,11 @@ impl TincOperator {
         let mut res = String::new();
         file.read_to_string(&mut res)
             .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
-        #[cfg(unix)]
-            let res: Vec<&str> = res.split("vpngw=").collect();
-        #[cfg(windows)]
-            let res: Vec<&str> = res.split("addr=").collect();
+        let res: Vec<&str> = res.split("vpngw=").collect();
         if res.len() > 1 {
-            let res = res[1].to_string();
-            #[cfg(unix)]
-                let res: Vec<&str> = res.split("\n").collect();
-            #[cfg(windows)]
-                let res: Vec<&str> = res.split(" mask").collect();
+            let res: Vec<&str> = res[1].split("\n").collect();
             if res.len() > 1 {
-                out = res[0].to_string();
+                out = res[0].trim().to_string();
             }
         }
         Ok(IpAddr::from(Ipv4Addr::from_str(&out).map_err(Error::ParseLocalVipError)?))
@@ -58,9 +62,30 @@ impl TincOperator {
         let mut contents = String::new();
         file.read_to_string(&mut contents)
             .map_err(|_| Error::FileNotExist(file_path.to_string()))?;
+        // 只返回RSA公钥部分, 与上报的pubKey保持一致
+        if !contents.contains("Ed25519PublicKey") {
+            return Ok(contents);
+        }
+        let contents = contents
+            .split_terminator('\n')
+            .filter(|line|!line.starts_with("Ed25519PublicKey"))
+            .map(|line|line.to_string() + "\n")
+            .collect::<String>();
         Ok(contents)
     }
 
+    /// 获取子设备Ed25519公钥
+    pub fn get_host_ed25519_pub_key(&self, host_name: &str) -> Option<String> {
+        let _guard = self.mutex.lock().unwrap();
+        let file_path = self.tinc_settings.tinc_home.to_string() + "hosts/" + host_name;
+        let contents = fs::read_to_string(&file_path).ok()?;
+        contents.lines()
+            .filter(|line|line.starts_with("Ed25519PublicKey"))
+            .filter_map(|line|line.splitn(2, '=').nth(1))
+            .map(|key|key.trim().to_string())
+            .next()
+    }
+
     pub fn get_tinc_pid(path: &str) -> Result<u64> {
         let mut file = fs::File::open(path)
             .map_err(|_|Error::TincNotExist)?;
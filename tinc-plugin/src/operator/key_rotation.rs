@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::{Error, Result, TincOperator,
+            PRIV_KEY_FILENAME, PUB_KEY_FILENAME, OLD_PRIV_KEY_FILENAME, OLD_PUB_KEY_FILENAME,
+            ED25519_PRIV_KEY_FILENAME, ED25519_PUB_KEY_FILENAME,
+            OLD_ED25519_PRIV_KEY_FILENAME, OLD_ED25519_PUB_KEY_FILENAME};
+
+const KEY_FILES: [(&str, &str); 4] = [
+    (PRIV_KEY_FILENAME, OLD_PRIV_KEY_FILENAME),
+    (PUB_KEY_FILENAME, OLD_PUB_KEY_FILENAME),
+    (ED25519_PRIV_KEY_FILENAME, OLD_ED25519_PRIV_KEY_FILENAME),
+    (ED25519_PUB_KEY_FILENAME, OLD_ED25519_PUB_KEY_FILENAME),
+];
+
+impl TincOperator {
+    /// 公钥存在且未超过 key_max_age_days 返回true
+    pub fn check_pub_key(&self) -> bool {
+        let pubkey_path = self.tinc_settings.tinc_home.to_owned() + PUB_KEY_FILENAME;
+        match Self::file_age(&pubkey_path) {
+            Some(age) => !key_expired(age, self.tinc_settings.key_max_age_days),
+            None => false,
+        }
+    }
+
+    /// 是否需要轮换本地密钥对, 密钥不存在时由init创建, 不在此处理
+    pub fn need_rotate_key_pair(&self) -> bool {
+        let pubkey_path = self.tinc_settings.tinc_home.to_owned() + PUB_KEY_FILENAME;
+        if self.tinc_settings.key_max_age_days == 0 || !Path::new(&pubkey_path).is_file() {
+            return false;
+        }
+        !self.check_pub_key()
+    }
+
+    /// 备份当前密钥对为 *.old, 并生成新的密钥对.
+    /// *.old 只用于新公钥上报失败时通过 restore_old_key_pair 回滚, 不会发布给对端.
+    /// tinc的host文件只能有一个公钥, 没有新旧密钥重叠期, 对端收到新公钥前与本节点的连接会断开.
+    /// 失败时恢复旧密钥对, 不留下写了一半的密钥对.
+    pub fn rotate_self_key_pair(&self) -> Result<()> {
+        let res = self.backup_key_pair().and_then(|_| self.create_self_key_pair());
+        if res.is_err() {
+            if let Err(e) = self.restore_old_key_pair() {
+                error!("restore_old_key_pair {:?}", e);
+            }
+        }
+        res
+    }
+
+    fn backup_key_pair(&self) -> Result<()> {
+        let tinc_home = self.tinc_settings.tinc_home.to_string();
+        for (from, to) in KEY_FILES.iter() {
+            if !Path::new(&(tinc_home.clone() + from)).is_file() {
+                continue;
+            }
+            fs::copy(tinc_home.clone() + from, tinc_home.clone() + to)
+                .map_err(|e|Error::IoError(tinc_home.clone() + to + " " + &e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// 使用 *.old 覆盖当前密钥对
+    pub fn restore_old_key_pair(&self) -> Result<()> {
+        let tinc_home = self.tinc_settings.tinc_home.to_string();
+        for (to, from) in KEY_FILES.iter() {
+            if !Path::new(&(tinc_home.clone() + from)).is_file() {
+                continue;
+            }
+            fs::rename(tinc_home.clone() + from, tinc_home.clone() + to)
+                .map_err(|e|Error::IoError(tinc_home.clone() + from + " " + &e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// 新公钥上报成功后删除 *.old
+    pub fn clean_old_key_pair(&self) {
+        let tinc_home = self.tinc_settings.tinc_home.to_string();
+        for (_, old) in KEY_FILES.iter() {
+            let _ = fs::remove_file(tinc_home.clone() + old);
+        }
+    }
+
+    fn file_age(path: &str) -> Option<Duration> {
+        fs::metadata(path).ok()?
+            .modified().ok()
+            .and_then(|time| SystemTime::now().duration_since(time).ok())
+    }
+}
+
+/// 密钥是否超过 max_age_days, 0 表示不轮换
+fn key_expired(age: Duration, max_age_days: u32) -> bool {
+    max_age_days != 0 && age >= Duration::from_secs(max_age_days as u64 * 24 * 60 * 60)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+    use super::*;
+    use crate::{TincSettings, TincRunMode};
+
+    fn operator(tinc_home: &str) -> TincOperator {
+        TincOperator {
+            mutex:                  Mutex::new(0),
+            tinc_settings:          TincSettings {
+                tinc_home:          tinc_home.to_string(),
+                mode:               TincRunMode::Client,
+                ..TincSettings::default()
+            },
+            tinc_out_memory_times:  0,
+            applied:                None,
+        }
+    }
+
+    #[test]
+    fn test_key_expired() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        assert!(!key_expired(day * 29, 30));
+        assert!(key_expired(day * 30, 30));
+        assert!(!key_expired(day * 3650, 0));
+    }
+
+    #[test]
+    fn test_rotate_and_restore() {
+        let tinc_home = std::env::temp_dir()
+            .join("dnet_test_key_rotation")
+            .to_str().unwrap().to_string() + "/";
+        let _ = fs::remove_dir_all(&tinc_home);
+        fs::create_dir_all(&tinc_home).unwrap();
+
+        let tinc = operator(&tinc_home);
+        tinc.create_self_key_pair().unwrap();
+        let pub_key = fs::read_to_string(tinc_home.clone() + PUB_KEY_FILENAME).unwrap();
+        // 新密钥未过期
+        assert!(tinc.check_pub_key());
+        assert!(!tinc.need_rotate_key_pair());
+
+        tinc.rotate_self_key_pair().unwrap();
+        let rotated = fs::read_to_string(tinc_home.clone() + PUB_KEY_FILENAME).unwrap();
+        assert_ne!(rotated, pub_key);
+        assert_eq!(fs::read_to_string(tinc_home.clone() + OLD_PUB_KEY_FILENAME).unwrap(), pub_key);
+
+        tinc.restore_old_key_pair().unwrap();
+        assert_eq!(fs::read_to_string(tinc_home.clone() + PUB_KEY_FILENAME).unwrap(), pub_key);
+        assert!(!Path::new(&(tinc_home.clone() + OLD_PUB_KEY_FILENAME)).exists());
+
+        // 上报成功后删除旧密钥
+        tinc.rotate_self_key_pair().unwrap();
+        tinc.clean_old_key_pair();
+        assert!(!Path::new(&(tinc_home.clone() + OLD_PUB_KEY_FILENAME)).exists());
+        assert!(!Path::new(&(tinc_home.clone() + OLD_PRIV_KEY_FILENAME)).exists());
+
+        let _ = fs::remove_dir_all(&tinc_home);
+    }
+}
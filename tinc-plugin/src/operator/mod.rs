@@ -2,12 +2,17 @@ mod check;
 mod const_settings;
 mod error;
 mod get_tinc_file;
+mod key_rotation;
 mod operator;
 mod set_tinc_file;
 mod start_stop;
+mod tinc_config;
 mod tools;
+mod transaction;
 
 pub use const_settings::*;
 pub use error::{Error, Result};
 pub use operator::{TincOperator, TincSettings};
+pub use tinc_config::{TincConfig, ConfigChange};
 pub use tools::TincTools;
+pub use transaction::ConfigTransaction;
@@ -1,6 +1,8 @@
 use std::sync::Mutex;
+use std::collections::BTreeMap;
 
 use crate::TincRunMode;
+use super::{TincConfig, DEFAULT_KEY_MAX_AGE_DAYS};
 
 static mut EL: *mut TincOperator = 0 as *mut _;
 
@@ -13,6 +15,12 @@ pub struct TincSettings {
     pub tinc_allowed_tcp_failed_times:      u32,
     pub tinc_check_frequency:               u32,
     pub external_boot:                      bool,
+    /// 密钥最长使用天数, 0 表示不轮换
+    pub key_max_age_days:                   u32,
+    /// 同时生成Ed25519密钥(tinc 1.1 SPTPS), RSA密钥保留给未升级的节点
+    pub ed25519_key:                        bool,
+    /// 覆盖tinc.conf默认值, 见 TincConfig::apply_extra
+    pub extra:                              BTreeMap<String, String>,
 }
 
 impl Default for TincSettings {
@@ -26,15 +34,27 @@ impl Default for TincSettings {
             tinc_allowed_tcp_failed_times:      0,
             tinc_check_frequency:               0,
             external_boot:                      false,
+            key_max_age_days:                   DEFAULT_KEY_MAX_AGE_DAYS,
+            ed25519_key:                        false,
+            extra:                              BTreeMap::new(),
         }
     }
 }
 
+/// 最近一次写入的tinc.conf和本节点公钥, 用于判断配置变更能否reload
+#[derive(Debug, Clone)]
+pub struct AppliedConfig {
+    pub config:             TincConfig,
+    pub pub_key:            String,
+    pub ed25519_pub_key:    Option<String>,
+}
+
 /// Tinc operator
 pub struct TincOperator {
     pub mutex:                  Mutex<i32>,
     pub tinc_settings:          TincSettings,
     pub tinc_out_memory_times:  u32,
+    pub applied:                Option<AppliedConfig>,
 }
 
 impl TincOperator {
@@ -44,6 +64,7 @@ impl TincOperator {
             mutex:                  Mutex::new(0),
             tinc_out_memory_times:  0,
             tinc_settings,
+            applied:                None,
         };
 
         operator.clear_hosts();
@@ -1,35 +1,41 @@
 use std::fs;
-use std::path;
 use std::io::Write;
-use std::time::SystemTime;
 use std::path::Path;
 use std::net::IpAddr;
 
 use crate::info::{TincRunMode, TincInfo};
-use super::{Error, Result, TincOperator, TincTools,
+use super::transaction::{ConfigTransaction, write_synced, write_synced_private, write_atomic};
+use super::operator::AppliedConfig;
+use super::{Error, Result, TincOperator, TincTools, TincConfig,
             PUB_KEY_FILENAME, TINC_UP_FILENAME, PRIV_KEY_FILENAME,
+            ED25519_PRIV_KEY_FILENAME, ED25519_PUB_KEY_FILENAME,
             HOST_UP_FILENAME, TINC_DOWN_FILENAME, HOST_DOWN_FILENAME};
 
 impl TincOperator {
+    /// 所有配置写入staging后一次提交, 上一代配置保留用于回滚
     pub fn set_info_to_local(&mut self, info: &TincInfo) -> Result<()> {
-        self.set_tinc_conf_file(info)?;
+        let transaction = ConfigTransaction::begin(&self.tinc_settings.tinc_home)?;
+        let dir = transaction.staging_dir().to_string();
+
+        let config = self.tinc_config(info)?;
+        self.set_tinc_conf_file(&config, &dir)?;
         let is_proxy = match self.tinc_settings.mode {
             TincRunMode::Proxy => true,
             TincRunMode::Center => true,
             TincRunMode::Client => false,
         };
 
-        self.set_tinc_up(&info)?;
-        self.set_tinc_down(info)?;
-        self.clean_host_up();
-        self.clean_host_down();
-        self.set_host_up()?;
-        self.set_host_down()?;
+        self.set_tinc_up(&info, &dir)?;
+        self.set_tinc_down(info, &dir)?;
+        self.set_host_up(&dir)?;
+        self.set_host_down(&dir)?;
 
         for online_proxy in info.connect_to.clone() {
-            self.set_hosts(Some((online_proxy.ip.clone(), online_proxy.port)),
+            self.set_hosts_in(&dir,
+                           Some((online_proxy.ip.clone(), online_proxy.port)),
                            online_proxy.vip,
                            &online_proxy.pubkey,
+                           online_proxy.ed25519_pubkey.as_ref().map(|key|key.as_str()),
             )?;
         };
 
@@ -40,193 +46,79 @@ impl TincOperator {
         else {
             None
         };
-        self.set_hosts(
+        self.set_hosts_in(
+            &dir,
             ip_port,
             info.vip,
-            &info.pub_key)
+            &info.pub_key,
+            info.ed25519_pub_key.as_ref().map(|key|key.as_str()))?;
+
+        transaction.commit()?;
+        self.applied = Some(AppliedConfig {
+            config,
+            pub_key:            info.pub_key.clone(),
+            ed25519_pub_key:    info.ed25519_pub_key.clone(),
+        });
+        Ok(())
     }
 
-    fn set_tinc_up(&self, tinc_info: &TincInfo) -> Result<()> {
+    /// tinc-up只通知daemon, 网卡地址, 掩码, MTU和路由由daemon收到TincUp后配置.
+    /// vpngw 仅作为记录, 供get_local_vip读取.
+    fn set_tinc_up(&self, tinc_info: &TincInfo, dir: &str) -> Result<()> {
         let _guard = self.mutex.lock().unwrap();
+        let buf = tinc_up_script(&self.tinc_settings.tinc_home, &tinc_info.vip);
 
-        let netmask = match self.tinc_settings.mode {
-            TincRunMode::Proxy => "255.0.0.0",
-            TincRunMode::Center => "255.0.0.0",
-            TincRunMode::Client => "255.255.255.255",
-        };
-
-        let buf;
-
-        #[cfg(target_arch = "arm")]
-            {
-                buf = "#!/bin/sh\n\
-                dev=dnet\n\
-                vpngw=".to_string() + &tinc_info.vip.to_string() + "\n" +
-                    "ifconfig ${dev} up\n\
-                     ifconfig ${dev} ${vpngw} netmask " + netmask
-                    + "\n" + &self.tinc_settings.tinc_home + "tinc-report -u";
-            }
-        #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
-            {
-                buf = "#!/bin/bash\n\
-            dev=dnet\n\
-            vpngw=".to_string() + &tinc_info.vip.to_string() + "\n" +
-                    "ifconfig ${dev} ${vpngw} netmask " + netmask
-                    + "\n" + &self.tinc_settings.tinc_home + "tinc-report -u";
-//          Example for global proxy
-//
-// ```
-//            if TincRunMode::Client == self.tinc_settings.mode {
-//                if tinc_info.connect_to.is_empty() {
-//                    return Err(Error::TincInfo_connect_to_is_empty)
-//                }
-//
-//                buf = buf + "\n"
-//                    + "route add -host " + &tinc_info.connect_to[0].ip.to_string() + " gw _gateway";
-//                buf = buf + "\n"
-//                    + "route add -host 10.255.255.254 dev dnet";
-//                buf = buf + "\n"
-//                    + "route add default gw " + &tinc_info.connect_to[0].vip.to_string();
-//            }
-// ```
-            }
-        #[cfg(target_os = "macos")]
-            {
-                buf = "#!/bin/bash\n\
-                   dev=tap0\n\
-                   vpngw=".to_string()
-                    + &tinc_info.vip.to_string() + "\n"
-                    + "ifconfig ${dev} ${vpngw} netmask " + netmask + "\n";
-
-// Example for global proxy
-// ```
-//  if TincRunMode::Client == self.tinc_settings.mode {
-//      let default_gateway = get_default_gateway()?.to_string();
-//      buf = buf
-//          + "route -q -n delete -net 0.0.0.0\n\
-//      route -q -n add -host " + &tinc_info.connect_to[0].ip.to_string()
-//          + " -gateway " + &default_gateway + "\n"
-//          + "route add -host 10.255.255.254 -interface tap0 -iface -cloning\n"
-//          + "route add -net 0.0.0.0 -gateway 10.255.255.254";
-//  }
-// ```
-
-            }
-        #[cfg(windows)]
-            {
-                buf = "netsh interface ipv4 set address name=\"dnet\" source=static addr=".to_string() +
-                    &tinc_info.vip.to_string() + " mask=" + netmask + "\r\n";
-
-//          Example for global proxy
-//            if TincRunMode::Client == self.tinc_settings.mode {
-//                let default_gateway = get_default_gateway()?.to_string();
-//                let vnic_index = format!("{}", get_vnic_index()?);
-//
-//                buf = buf
-//                    + "route add " + &tinc_info.connect_to[0].ip.to_string()
-//                        + " mask 255.255.255.255 " + &default_gateway + "\r\n"
-//                    + "route add 10.255.255.254 mask 255.255.255.255 10.255.255.254 if "
-//                        + &vnic_index + "\r\n"
-//                    + "route add 0.0.0.0 mask 0.0.0.0 10.255.255.254 if "
-//                        + &vnic_index + "\r\n";
-//            }
-            }
-
-        let path = self.tinc_settings.tinc_home.clone() + TINC_UP_FILENAME;
-        let mut file = fs::File::create(path.clone())
-            .map_err(|e|Error::FileCreateError(path.clone() + " " + &e.to_string()))?;
-        file.write(buf.as_bytes())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
+        let path = dir.to_string() + TINC_UP_FILENAME;
+        write_synced(&path, &buf)?;
         #[cfg(unix)]
             TincTools::set_script_permissions(&path)?;
         Ok(())
     }
 
-
-    fn set_tinc_down(&self, _tinc_info: &TincInfo) -> Result<()> {
+    fn set_tinc_down(&self, _tinc_info: &TincInfo, dir: &str) -> Result<()> {
         let _guard = self.mutex.lock().unwrap();
-        let buf;
-        #[cfg(target_arch = "arm")]
-            {
-                buf = "#!/bin/sh\n".to_string() + &self.tinc_settings.tinc_home + "tinc-report -d";
-            }
-        #[cfg(all(target_os = "linux", not(target_arch = "arm")))]
-            {
-                buf = "#!/bin/bash\n".to_string() + &self.tinc_settings.tinc_home + "tinc-report -d";
-            }
-        #[cfg(target_os = "macos")]
-            {
-                let default_gateway = get_default_gateway()?.to_string();
-                buf = "#!/bin/bash\n".to_string() + &self.tinc_settings.tinc_home + "tinc-report -d";
+        let buf = tinc_down_script(&self.tinc_settings.tinc_home);
 
-//              Example for global proxy
-//                    + "route -n -q delete -host " + &_tinc_info.connect_to[0].ip.to_string() + "\n"
-//                    + "route -n -q delete -net 0.0.0.0 \n\
-//                       route -n -q add -net 0.0.0.0 -gateway " + &default_gateway;
-            }
-        #[cfg(windows)]
-            {
-                buf = self.tinc_settings.tinc_home.to_string() + "tinc-report.exe -d";
-//              Example for global proxy
-//                buf = "route delete 0.0.0.0 mask 0.0.0.0 10.255.255.254 if ".to_string()
-//                    + &vnic_index + "\r\n"
-//                    + &self.tinc_settings.tinc_home.to_string() + "tinc-report.exe -d";
-            }
-
-        let path = self.tinc_settings.tinc_home.clone() + TINC_DOWN_FILENAME;
-        let mut file = fs::File::create(path.clone())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
-        file.write(buf.as_bytes())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
+        let path = dir.to_string() + TINC_DOWN_FILENAME;
+        write_synced(&path, &buf)?;
         #[cfg(unix)]
             TincTools::set_script_permissions(&path)?;
         Ok(())
     }
 
-    fn clean_host_up(&self) {
-        let path = self.tinc_settings.tinc_home.clone() + HOST_UP_FILENAME;
-        let _ = fs::remove_file(path);
-    }
-
-    fn set_host_up(&self) -> Result<()> {
+    fn set_host_up(&self, dir: &str) -> Result<()> {
         let _guard = self.mutex.lock().unwrap();
         #[cfg(windows)]
             let buf = &(self.tinc_settings.tinc_home.to_string() + "tinc-report.exe -hu ${NODE}");
         #[cfg(unix)]
             let buf = "#!/bin/bash\n".to_string() + &self.tinc_settings.tinc_home + "tinc-report -hu ${NODE}";
 
-        let path = self.tinc_settings.tinc_home.clone() + HOST_UP_FILENAME;
-        let mut file = fs::File::create(path.clone())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
-        file.write(buf.as_bytes())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
+        let path = dir.to_string() + HOST_UP_FILENAME;
+        write_synced(&path, &buf)?;
         #[cfg(unix)]
             TincTools::set_script_permissions(&path)?;
         Ok(())
     }
 
-    fn clean_host_down(&self) {
-        let path = self.tinc_settings.tinc_home.clone() + HOST_DOWN_FILENAME;
-        let _ = fs::remove_file(path);
-    }
-
-    fn set_host_down(&self) -> Result<()> {
+    fn set_host_down(&self, dir: &str) -> Result<()> {
         let _guard = self.mutex.lock().unwrap();
         #[cfg(windows)]
             let buf = &(self.tinc_settings.tinc_home.to_string() + "tinc-report.exe -hd ${NODE}");
         #[cfg(unix)]
             let buf = "#!/bin/bash\n".to_string() + &self.tinc_settings.tinc_home + "tinc-report -hd ${NODE}";
 
-        let path = self.tinc_settings.tinc_home.clone() + HOST_DOWN_FILENAME;
-        let mut file = fs::File::create(path.clone())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
-        file.write(buf.as_bytes())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
+        let path = dir.to_string() + HOST_DOWN_FILENAME;
+        write_synced(&path, &buf)?;
         #[cfg(unix)]
             TincTools::set_script_permissions(&path)?;
         Ok(())
     }
 
+    /// 完成上次中断的配置提交或回滚
+    pub fn recover_config(&self) -> Result<()> {
+        ConfigTransaction::recover(&self.tinc_settings.tinc_home)
+    }
+
     pub fn create_tinc_dirs(&self) -> Result<()> {
         let path_str = self.tinc_settings.tinc_home.clone() + "hosts";
         if !std::path::Path::new(&path_str).is_dir() {
@@ -236,41 +128,37 @@ impl TincOperator {
         Ok(())
     }
 
-
-    pub fn check_pub_key(&self) -> bool {
-        let pubkey_path = self.tinc_settings.tinc_home.to_owned() + PUB_KEY_FILENAME;
-        let path = path::Path::new(&pubkey_path);
-        if let Ok(fs) = std::fs::metadata(path) {
-            if let Ok(time) = fs.modified() {
-                if let Ok(now) = SystemTime::now().duration_since(time) {
-                    if now.as_secs() / 60 / 60 / 24 < 30 {
-                        return true;
-                    }
-                }
-            }
-        }
-        return false;
-    }
-
     /// openssl Rsa 创建2048位密钥对, 并存放到tinc配置文件中
+    /// 开启ed25519_key时同时创建Ed25519密钥对
     pub fn create_self_key_pair(&self) -> Result<()> {
         let (priv_key, pubkey) = TincTools::create_key_pair()?;
+        self.write_key_file(PRIV_KEY_FILENAME, &priv_key)?;
+        self.write_key_file(PUB_KEY_FILENAME, &pubkey)?;
+        if self.tinc_settings.ed25519_key {
+            self.create_self_ed25519_key_pair()?;
+        }
+        Ok(())
+    }
 
-        let mut file = fs::File::create(
-            self.tinc_settings.tinc_home.to_string() + PRIV_KEY_FILENAME)
-            .map_err(|e|
-                Error::FileCreateError((self.tinc_settings.tinc_home.to_string() + PRIV_KEY_FILENAME)
-                    + " " + &e.to_string()))?;
-        file.write_all(priv_key.as_bytes())
-            .map_err(|_|Error::CreatePubKeyError)?;
-        drop(file);
+    /// 创建Ed25519密钥对, 不影响已有的RSA密钥
+    pub fn create_self_ed25519_key_pair(&self) -> Result<()> {
+        let (priv_key, pubkey) = TincTools::create_ed25519_key_pair()?;
+        self.write_key_file(ED25519_PRIV_KEY_FILENAME, &priv_key)?;
+        self.write_key_file(ED25519_PUB_KEY_FILENAME, &pubkey)
+    }
 
-        let path = self.tinc_settings.tinc_home.to_string() + PUB_KEY_FILENAME;
+    /// 开启ed25519_key但Ed25519密钥不存在时返回false, 用于RSA节点升级
+    pub fn check_ed25519_key(&self) -> bool {
+        !self.tinc_settings.ed25519_key
+            || Path::new(&(self.tinc_settings.tinc_home.to_string() + ED25519_PRIV_KEY_FILENAME)).is_file()
+    }
+
+    fn write_key_file(&self, filename: &str, key: &str) -> Result<()> {
+        let path = self.tinc_settings.tinc_home.to_string() + filename;
         let mut file = fs::File::create(&path)
             .map_err(|e|Error::FileCreateError(path.clone() + " " + &e.to_string()))?;
-        file.write_all(pubkey.as_bytes())
+        file.write_all(key.as_bytes())
             .map_err(|_|Error::CreatePubKeyError)?;
-        drop(file);
         Ok(())
     }
 
@@ -286,9 +174,7 @@ impl TincOperator {
     }
 
     /// 通过Info修改tinc.conf
-    fn set_tinc_conf_file(&self, tinc_info: &TincInfo) -> Result<()> {
-        let _guard = self.mutex.lock().unwrap();
-
+    pub(crate) fn tinc_config(&self, tinc_info: &TincInfo) -> Result<TincConfig> {
         let is_proxy = match self.tinc_settings.mode {
             TincRunMode::Proxy => true,
             TincRunMode::Center => true,
@@ -305,80 +191,45 @@ impl TincOperator {
             connect_to.push(online_proxy_name);
         }
 
-        let mut buf_connect_to = String::new();
-        for other in connect_to {
-            let buf = "ConnectTo = ".to_string() + &other + "\n";
-            buf_connect_to += &buf;
-        }
-
-        let port = self.tinc_settings.port;
+        let mut config = TincConfig::new(&name, connect_to, self.tinc_settings.port);
+        config.apply_extra(&self.tinc_settings.extra)?;
+        Ok(config)
+    }
 
-        let buf;
-        #[cfg(target_os = "linux")]
-            {
-                buf = "Name = ".to_string() + &name + "\n"
-                    + &buf_connect_to
-                    + "DeviceType=tap\n\
-                   Mode=switch\n\
-                   Interface=dnet\n\
-                   BindToAddress = * "  + &format!("{}", port) + "\n\
-                   ProcessPriority = high\n\
-                   PingTimeout=3\n\
-                   Device = /dev/net/tun\n\
-                   AutoConnect = yes\n\
-                   MaxConnectionBurst=1000\n";
-//                   Type = " + tinc_type + "\n\
-            }
-        #[cfg(target_os = "macos")]
-            {
-                buf = "Name = ".to_string() + &name + "\n"
-                    + &buf_connect_to
-                    + "DeviceType=tap\n\
-                   Mode=switch\n\
-                   Interface=dnet\n\
-                   BindToAddress = * "  + &format!("{}", port) + "\n\
-                   ProcessPriority = high\n\
-                   PingTimeout=3\n\
-                   Device = /dev/tap0\n\
-                   AutoConnect=yes\n\
-                   MaxConnectionBurst=1000\n";
-//                   Type = " + tinc_type + "\n\
-            }
-        #[cfg(windows)]
-            {
-                buf = "Name = ".to_string() + &name + "\n"
-                    + &buf_connect_to
-                    + "DeviceType=tap\n\
-                   Mode=switch\n\
-                   Interface=dnet\n\
-                   BindToAddress = * "  + &format!("{}", port) + "\n\
-                   ProcessPriority = high\n\
-                   PingTimeout=3\n\
-                   AutoConnect=yes\n\
-                   MaxConnectionBurst=1000\n";
-//                   Type = " + tinc_type + "\n\
-            }
+    fn set_tinc_conf_file(&self, config: &TincConfig, dir: &str) -> Result<()> {
+        let _guard = self.mutex.lock().unwrap();
+        let buf = config.render();
 
-        let path = self.tinc_settings.tinc_home.clone() + "tinc.conf";
-        let mut file = fs::File::create(path.clone())
-            .map_err(|e|Error::FileCreateError(path.clone() + " " + &e.to_string()))?;
-        file.write(buf.as_bytes())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
-        return Ok(());
+        // 可能包含出口代理的密码
+        let path = dir.to_string() + "tinc.conf";
+        write_synced_private(&path, &buf)
     }
 
     /// 添加hosts文件
     /// if is_proxy{ 文件名=proxy_10_253_x_x }
     /// else { 文件名=虚拟ip后三位b_c_d }
+    /// ed25519_pubkey 存在时追加 Ed25519PublicKey 行, 对端为tinc 1.1时使用SPTPS
     pub fn set_hosts(&self,
-                     ip_port: Option<(IpAddr, u16)>,
-                     vip:     IpAddr,
-                     pubkey:  &str,
+                     ip_port:        Option<(IpAddr, u16)>,
+                     vip:            IpAddr,
+                     pubkey:         &str,
+                     ed25519_pubkey: Option<&str>,
+    ) -> Result<()> {
+        let tinc_home = self.tinc_settings.tinc_home.clone();
+        self.set_hosts_in(&tinc_home, ip_port, vip, pubkey, ed25519_pubkey)
+    }
+
+    fn set_hosts_in(&self,
+                    dir:            &str,
+                    ip_port:        Option<(IpAddr, u16)>,
+                    vip:            IpAddr,
+                    pubkey:         &str,
+                    ed25519_pubkey: Option<&str>,
     ) -> Result<()> {
         let vip = vip.to_string();
         let _guard = self.mutex.lock().unwrap();
 
-        let buf;
+        let mut buf;
         let file_name = if let Some((ip, port)) = ip_port {
             let ip = ip.to_string();
             let port = format!("{}", port);
@@ -392,16 +243,66 @@ impl TincOperator {
             TincTools::get_filename_by_vip(false, &vip)
         };
 
-        let path = self.tinc_settings.tinc_home.clone() + "hosts/" + &file_name;
-        let mut file = fs::File::create(path.clone())
-            .map_err(|e|Error::FileCreateError(path.clone() + " " + &e.to_string()))?;
-        file.write(buf.as_bytes())
-            .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
+        if let Some(ed25519_pubkey) = ed25519_pubkey {
+            if !buf.ends_with("\n") {
+                buf += "\n";
+            }
+            buf = buf + "Ed25519PublicKey = " + ed25519_pubkey + "\n";
+        }
 
-        Ok(())
+        let path = dir.to_string() + "hosts/" + &file_name;
+        write_atomic(&path, &buf)
+    }
+
+    pub fn remove_host(&self, file_name: &str) {
+        let _ = std::fs::remove_file(self.tinc_settings.tinc_home.clone() + "hosts/" + file_name);
     }
 
     pub fn clear_hosts(&self) {
         let _ = std::fs::remove_dir_all(Path::new(&(self.tinc_settings.tinc_home.clone() + "hosts/")));
     }
-}
\ No newline at end of file
+}
+
+fn tinc_up_script(tinc_home: &str, vip: &IpAddr) -> String {
+    #[cfg(unix)]
+        let buf = "#!/bin/sh\n".to_string()
+            + "vpngw=" + &vip.to_string() + "\n"
+            + tinc_home + "tinc-report -u";
+    #[cfg(windows)]
+        let buf = "set vpngw=".to_string() + &vip.to_string() + "\r\n"
+            + tinc_home + "tinc-report.exe -u";
+    buf
+}
+
+fn tinc_down_script(tinc_home: &str) -> String {
+    #[cfg(unix)]
+        let buf = "#!/bin/sh\n".to_string() + tinc_home + "tinc-report -d";
+    #[cfg(windows)]
+        let buf = tinc_home.to_string() + "tinc-report.exe -d";
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+    use super::{tinc_up_script, tinc_down_script};
+
+    #[test]
+    fn test_tinc_up_down_script() {
+        let vip = IpAddr::from(Ipv4Addr::new(10, 253, 1, 2));
+        #[cfg(unix)]
+            {
+                assert_eq!(tinc_up_script("/opt/dnet/tinc/", &vip),
+                           "#!/bin/sh\nvpngw=10.253.1.2\n/opt/dnet/tinc/tinc-report -u");
+                assert_eq!(tinc_down_script("/opt/dnet/tinc/"),
+                           "#!/bin/sh\n/opt/dnet/tinc/tinc-report -d");
+            }
+        #[cfg(windows)]
+            {
+                assert_eq!(tinc_up_script("C:\\dnet\\tinc\\", &vip),
+                           "set vpngw=10.253.1.2\r\nC:\\dnet\\tinc\\tinc-report.exe -u");
+                assert_eq!(tinc_down_script("C:\\dnet\\tinc\\"),
+                           "C:\\dnet\\tinc\\tinc-report.exe -d");
+            }
+    }
+}
@@ -4,10 +4,15 @@ use sysinfo::{System, SystemExt, ProcessExt};
 use sysinfo::Signal;
 #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
 use crate::tinc_tcp_stream::TincStream;
-use super::{Error, Result, TincOperator, PID_FILENAME, TINC_BIN_FILENAME};
+use crate::info::TincInfo;
+use super::{Error, Result, TincOperator, TincConfig, ConfigChange, ConfigTransaction,
+            PID_FILENAME, TINC_BIN_FILENAME};
+
+/// 启动后等待tinc正常运行的最长时间
+const TINC_HEALTHY_TIMEOUT_SECS: u64 = 5;
 
 impl TincOperator {
-    pub fn start_tinc(&self) -> Result<()> {
+    pub fn start_tinc(&mut self) -> Result<()> {
         if self.tinc_settings.external_boot {
             Ok(())
         }
@@ -15,7 +20,62 @@ impl TincOperator {
             if let Err(Error::StopTincError) = self.hard_stop() {
                 return Err(Error::StopTincError);
             }
-            self.start_tinc_inner()
+            self.start_tinc_inner()?;
+            self.rollback_if_unhealthy()
+        }
+    }
+
+    fn wait_tinc_healthy(&self) -> bool {
+        let start = std::time::Instant::now();
+        while start.elapsed() < std::time::Duration::from_secs(TINC_HEALTHY_TIMEOUT_SECS) {
+            std::thread::sleep(std::time::Duration::from_millis(500));
+            #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+                {
+                    if self.check_tinc_status().is_ok() && self.check_tinc_listen().is_ok() {
+                        return true;
+                    }
+                }
+            #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+                {
+                    if self.check_tinc_status().is_ok() {
+                        return true;
+                    }
+                }
+        }
+        false
+    }
+
+    /// tinc启动或reload后不正常时恢复上一代配置并重新启动, 回滚成功返回 ConfigRolledBack.
+    /// 运行正常时删除上一代配置, 之后的失败不会回滚到比当前更早的配置.
+    /// 返回错误时清空applied, 下一次apply_config完整重启
+    fn rollback_if_unhealthy(&mut self) -> Result<()> {
+        let res = self.rollback_if_unhealthy_inner();
+        if res.is_err() {
+            self.applied = None;
+        }
+        res
+    }
+
+    fn rollback_if_unhealthy_inner(&self) -> Result<()> {
+        let tinc_home = &self.tinc_settings.tinc_home;
+        if self.wait_tinc_healthy() {
+            ConfigTransaction::discard_previous(tinc_home);
+            return Ok(());
+        }
+        if !ConfigTransaction::has_previous(tinc_home) {
+            error!("tinc not healthy, no previous config to roll back.");
+            return Err(Error::StartTincError);
+        }
+
+        error!("tinc not healthy, roll back to previous config.");
+        ConfigTransaction::rollback(tinc_home)?;
+        let _ = self.hard_stop();
+        self.start_tinc_inner()?;
+        if self.wait_tinc_healthy() {
+            Err(Error::ConfigRolledBack)
+        }
+        else {
+            Err(Error::StartTincError)
         }
     }
 
@@ -122,6 +182,77 @@ impl TincOperator {
         Ok(())
     }
 
+    /// 通知tinc重新加载配置及hosts文件
+    pub fn reload_tinc(&self) -> Result<()> {
+        if self.tinc_settings.external_boot {
+            return Ok(());
+        }
+        #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+            {
+                let tinc_pid = self.tinc_settings.tinc_home.to_string() + PID_FILENAME;
+                TincStream::new(&tinc_pid)
+                    .and_then(|mut tinc_stream| tinc_stream.reload())
+                    .map_err(|_| Error::TincStreamError)
+            }
+        #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+            {
+                let status = Command::new("killall").args(vec!["-HUP", "tincd"]).spawn()
+                    .and_then(|mut child| child.wait())
+                    .map_err(|e| Error::IoError(e.to_string()))?;
+                // tincd未运行时killall失败
+                if !status.success() {
+                    return Err(Error::IoError("killall -HUP tincd ".to_string() + &status.to_string()));
+                }
+                Ok(())
+            }
+    }
+
+    /// 与最近一次写入的配置比较, 未运行, 本节点密钥变化或不能reload的变更需要重启
+    pub fn config_change(&self, info: &TincInfo) -> Result<ConfigChange> {
+        let applied = match &self.applied {
+            Some(applied) => applied,
+            None => return Ok(ConfigChange::Restart),
+        };
+        if self.check_tinc_status().is_err() {
+            return Ok(ConfigChange::Restart);
+        }
+        if applied.pub_key != info.pub_key || applied.ed25519_pub_key != info.ed25519_pub_key {
+            return Ok(ConfigChange::Restart);
+        }
+        let config = self.tinc_config(info)?;
+        Ok(TincConfig::classify_change(&applied.config, &config))
+    }
+
+    /// reload后断开已移除的ConnectTo节点, 并立即重试连接, 其它连接不受影响.
+    /// 新配置不能加载时tincd会退出, 与启动后相同地检查并回滚到上一代配置, 回滚后返回 ConfigRolledBack
+    pub fn reload_tinc_config(&mut self, removed: &[String]) -> Result<()> {
+        self.reload_tinc()?;
+        if self.tinc_settings.external_boot {
+            return Ok(());
+        }
+        self.rollback_if_unhealthy()?;
+        #[cfg(all(not(target_arch = "arm"), not(feature = "router_debug")))]
+            {
+                let tinc_pid = self.tinc_settings.tinc_home.to_string() + PID_FILENAME;
+                for name in removed {
+                    // reload时tinc可能已经断开
+                    if let Err(e) = TincStream::new(&tinc_pid)
+                        .and_then(|mut tinc_stream| tinc_stream.disconnect_node(name)) {
+                        debug!("disconnect {} {:?}", name, e);
+                    }
+                }
+                TincStream::new(&tinc_pid)
+                    .and_then(|mut tinc_stream| tinc_stream.retry())
+                    .map_err(|_| Error::TincStreamError)?;
+            }
+        #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
+            {
+                // 路由器通过SIGHUP reload, tinc自行断开已移除的ConnectTo
+                let _ = removed;
+            }
+        Ok(())
+    }
+
     pub fn restart_tinc(&mut self) -> Result<()> {
         if self.tinc_settings.external_boot {
             Ok(())
@@ -138,10 +269,10 @@ impl TincOperator {
                             self.stop_tinc()?;
                             self.start_tinc_inner()?;
                         },
-                        Err(Error::TincNeverStart) => (),
+                        Err(Error::TincNeverStart) => return Ok(()),
                         Err(_) => self.start_tinc_inner()?,
                     }
-                    Ok(())
+                    self.rollback_if_unhealthy()
                 }
             #[cfg(all(target_os = "linux", any(target_arch = "arm", feature = "router_debug")))]
                 {
@@ -164,7 +295,7 @@ mod test {
         let mut tinc_settings = TincSettings::default();
         tinc_settings.mode = TincRunMode::Client;
         TincOperator::new(tinc_settings);
-        let tinc = TincOperator::instance();
+        let tinc = TincOperator::mut_instance();
         let _ = tinc.start_tinc()
             .map_err(|e|println!("{:?}", e));
 
@@ -185,7 +316,7 @@ mod test {
         let mut tinc_settings = TincSettings::default();
         tinc_settings.mode = TincRunMode::Client;
         TincOperator::new(tinc_settings);
-        let tinc = TincOperator::instance();
+        let tinc = TincOperator::mut_instance();
         let _ = tinc.start_tinc()
             .map_err(|e|println!("{:?}", e));
 
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+
+use super::{Error, Result};
+
+/// 由TincOperator管理, 不允许通过 [tinc.extra] 修改
+const MANAGED_KEYS: [&str; 4] = ["Name", "ConnectTo", "Interface", "BindToAddress"];
+
+/// [tinc.extra] 可以设置的tinc.conf变量
+const KNOWN_KEYS: &[&str] = &[
+    "AddressFamily", "AutoConnect", "BindToInterface", "Broadcast", "Cipher", "ClampMSS",
+    "Compression", "DecrementTTL", "Device", "DeviceStandby", "DeviceType", "Digest",
+    "DirectOnly", "Ed25519PrivateKeyFile", "ExperimentalProtocol", "Forwarding", "FWMark",
+    "Hostnames", "IffOneQueue", "IndirectData", "KeyExpire", "ListenAddress", "LocalDiscovery",
+    "LogLevel", "MACExpire", "MACLength", "MaxConnectionBurst", "MaxOutputBufferSize",
+    "MaxTimeout", "Mode", "MTU", "PingInterval", "PingTimeout", "PMTU", "PMTUDiscovery",
+    "PriorityInheritance", "PrivateKeyFile", "ProcessPriority", "Proxy", "ReplayWindow",
+    "StrictSubnets", "TCPOnly", "TunnelServer", "UDPDiscovery", "UDPDiscoveryTimeout",
+    "UDPInfoInterval", "UDPRcvBuf", "UDPSndBuf", "UPnP", "Weight",
+];
+
+/// reload不会重新打开网卡和监听端口, 这些变量变化时需要重启tinc
+const RESTART_KEYS: &[&str] = &[
+    "AddressFamily", "BindToInterface", "ListenAddress", "IffOneQueue",
+    "PrivateKeyFile", "Ed25519PrivateKeyFile",
+];
+
+/// 应用配置变更的最小操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChange {
+    /// Name, 端口, 网卡等tinc不能reload的变更
+    Restart,
+    /// reload重新读取tinc.conf和hosts, 断开不再ConnectTo的节点
+    Reload {
+        removed:    Vec<String>,
+        added:      Vec<String>,
+    },
+}
+
+/// tinc.conf
+#[derive(Debug, Clone, PartialEq)]
+pub struct TincConfig {
+    pub name:                   String,
+    pub connect_to:             Vec<String>,
+    pub port:                   u16,
+    pub device_type:            String,
+    pub mode:                   String,
+    pub interface:              String,
+    pub device:                 Option<String>,
+    pub process_priority:       String,
+    pub ping_timeout:           u32,
+    pub auto_connect:           bool,
+    pub max_connection_burst:   u32,
+    /// 其它tinc.conf变量, 按key排序输出
+    pub extra:                  BTreeMap<String, String>,
+}
+
+impl TincConfig {
+    pub fn new(name: &str, connect_to: Vec<String>, port: u16) -> Self {
+        let device;
+        #[cfg(target_os = "linux")]
+            {
+                device = Some("/dev/net/tun".to_string());
+            }
+        #[cfg(target_os = "macos")]
+            {
+                device = Some("/dev/tap0".to_string());
+            }
+        #[cfg(windows)]
+            {
+                device = None;
+            }
+
+        Self {
+            name:                   name.to_string(),
+            connect_to,
+            port,
+            device_type:            "tap".to_string(),
+            mode:                   "switch".to_string(),
+            interface:              "dnet".to_string(),
+            device,
+            process_priority:       "high".to_string(),
+            ping_timeout:           3,
+            auto_connect:           true,
+            max_connection_burst:   1000,
+            extra:                  BTreeMap::new(),
+        }
+    }
+
+    /// 校验 [tinc.extra], 返回规范化后的key (tinc变量名不区分大小写)
+    pub fn validate_extra(extra: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+        let mut out = BTreeMap::new();
+        for (key, value) in extra {
+            if let Some(managed) = MANAGED_KEYS.iter()
+                .find(|managed| managed.eq_ignore_ascii_case(key)) {
+                return Err(Error::TincConfigError(format!("{} is managed by dnet", managed)));
+            }
+            let key = KNOWN_KEYS.iter()
+                .find(|known| known.eq_ignore_ascii_case(key))
+                .ok_or(Error::TincConfigError(format!("unknown tinc.conf variable {}", key)))?;
+            let value = value.trim();
+            Self::validate_value(key, value)?;
+            out.insert(key.to_string(), value.to_string());
+        }
+        Ok(out)
+    }
+
+    fn validate_value(key: &str, value: &str) -> Result<()> {
+        let invalid = || Error::TincConfigError(format!("invalid value {} = {}", key, value));
+        match key {
+            "AutoConnect" | "DecrementTTL" | "DeviceStandby" | "DirectOnly"
+            | "ExperimentalProtocol" | "Hostnames" | "IffOneQueue" | "IndirectData"
+            | "LocalDiscovery" | "PMTUDiscovery" | "PriorityInheritance" | "StrictSubnets"
+            | "TCPOnly" | "TunnelServer" | "UDPDiscovery" => {
+                if value != "yes" && value != "no" {
+                    return Err(invalid());
+                }
+            }
+            "ClampMSS" | "KeyExpire" | "MACExpire" | "MACLength" | "MaxConnectionBurst"
+            | "MaxOutputBufferSize" | "MaxTimeout" | "MTU" | "PingInterval" | "PingTimeout"
+            | "PMTU" | "ReplayWindow" | "UDPDiscoveryTimeout" | "UDPInfoInterval"
+            | "UDPRcvBuf" | "UDPSndBuf" | "Weight" | "FWMark" => {
+                value.parse::<u32>().map_err(|_| invalid())?;
+            }
+            "Compression" => {
+                let level = value.parse::<u32>().map_err(|_| invalid())?;
+                if level > 12 {
+                    return Err(invalid());
+                }
+            }
+            "Mode" => {
+                if !["router", "switch", "hub"].contains(&value) {
+                    return Err(invalid());
+                }
+            }
+            "DeviceType" => {
+                if !["tun", "tap", "dummy", "raw", "uml", "vde", "multicast", "fd"].contains(&value) {
+                    return Err(invalid());
+                }
+            }
+            "ProcessPriority" => {
+                if !["normal", "low", "high"].contains(&value.to_lowercase().as_str()) {
+                    return Err(invalid());
+                }
+            }
+            _ => {
+                if value.is_empty() || value.contains('\n') {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 使用 [tinc.extra] 覆盖默认值
+    pub fn apply_extra(&mut self, extra: &BTreeMap<String, String>) -> Result<()> {
+        for (key, value) in Self::validate_extra(extra)? {
+            match key.as_str() {
+                "DeviceType" => self.device_type = value,
+                "Mode" => self.mode = value,
+                "Device" => self.device = Some(value),
+                "ProcessPriority" => self.process_priority = value,
+                "PingTimeout" => self.ping_timeout = value.parse().unwrap_or(self.ping_timeout),
+                "AutoConnect" => self.auto_connect = value == "yes",
+                "MaxConnectionBurst" => self.max_connection_burst = value.parse()
+                    .unwrap_or(self.max_connection_burst),
+                // tinc没有MTU变量, 对应为本节点的PMTU
+                "MTU" => {
+                    self.extra.insert("PMTU".to_string(), value);
+                }
+                _ => {
+                    self.extra.insert(key, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 比较运行中的配置和新配置, 选择对已有连接影响最小的操作
+    pub fn classify_change(old: &TincConfig, new: &TincConfig) -> ConfigChange {
+        let restart = old.name != new.name
+            || old.port != new.port
+            || old.interface != new.interface
+            || old.device != new.device
+            || old.device_type != new.device_type
+            || old.mode != new.mode
+            || RESTART_KEYS.iter().any(|key| old.extra.get(*key) != new.extra.get(*key));
+        if restart {
+            return ConfigChange::Restart;
+        }
+
+        ConfigChange::Reload {
+            removed:    old.connect_to.iter()
+                .filter(|name| !new.connect_to.contains(name))
+                .cloned()
+                .collect(),
+            added:      new.connect_to.iter()
+                .filter(|name| !old.connect_to.contains(name))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut buf = format!("Name = {}\n", self.name);
+        for other in &self.connect_to {
+            buf += &format!("ConnectTo = {}\n", other);
+        }
+        buf += &format!("DeviceType = {}\n", self.device_type);
+        buf += &format!("Mode = {}\n", self.mode);
+        buf += &format!("Interface = {}\n", self.interface);
+        buf += &format!("BindToAddress = * {}\n", self.port);
+        buf += &format!("ProcessPriority = {}\n", self.process_priority);
+        buf += &format!("PingTimeout = {}\n", self.ping_timeout);
+        if let Some(device) = &self.device {
+            buf += &format!("Device = {}\n", device);
+        }
+        buf += &format!("AutoConnect = {}\n", if self.auto_connect { "yes" } else { "no" });
+        buf += &format!("MaxConnectionBurst = {}\n", self.max_connection_burst);
+        for (key, value) in &self.extra {
+            buf += &format!("{} = {}\n", key, value);
+        }
+        buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+    use super::{TincConfig, ConfigChange};
+
+    #[test]
+    fn test_render_with_extra() {
+        let mut extra = BTreeMap::new();
+        extra.insert("cipher".to_string(), "aes-256-cbc".to_string());
+        extra.insert("PingTimeout".to_string(), "10".to_string());
+        extra.insert("MTU".to_string(), "1400".to_string());
+
+        let mut config = TincConfig::new("proxy_10_253_1_1", vec!["proxy_10_253_1_2".to_string()], 50069);
+        config.apply_extra(&extra).unwrap();
+        let buf = config.render();
+        assert!(buf.starts_with("Name = proxy_10_253_1_1\nConnectTo = proxy_10_253_1_2\n"));
+        assert!(buf.contains("PingTimeout = 10\n"));
+        assert!(buf.contains("Cipher = aes-256-cbc\n"));
+        assert!(buf.contains("PMTU = 1400\n"));
+    }
+
+    #[test]
+    fn test_validate_extra() {
+        let mut extra = BTreeMap::new();
+        extra.insert("Name".to_string(), "other".to_string());
+        assert!(TincConfig::validate_extra(&extra).is_err());
+
+        let mut extra = BTreeMap::new();
+        extra.insert("NoSuchVariable".to_string(), "1".to_string());
+        assert!(TincConfig::validate_extra(&extra).is_err());
+
+        let mut extra = BTreeMap::new();
+        extra.insert("Compression".to_string(), "high".to_string());
+        assert!(TincConfig::validate_extra(&extra).is_err());
+    }
+
+    #[test]
+    fn test_classify_change() {
+        let old = TincConfig::new("10_253_1_2", vec!["proxy_10_253_1_1".to_string()], 50069);
+
+        let mut new = old.clone();
+        new.connect_to = vec!["proxy_10_253_1_3".to_string()];
+        new.ping_timeout = 10;
+        assert_eq!(TincConfig::classify_change(&old, &new), ConfigChange::Reload {
+            removed:    vec!["proxy_10_253_1_1".to_string()],
+            added:      vec!["proxy_10_253_1_3".to_string()],
+        });
+
+        let mut new = old.clone();
+        new.port = 50070;
+        assert_eq!(TincConfig::classify_change(&old, &new), ConfigChange::Restart);
+
+        let mut new = old.clone();
+        new.extra.insert("ListenAddress".to_string(), "0.0.0.0".to_string());
+        assert_eq!(TincConfig::classify_change(&old, &new), ConfigChange::Restart);
+    }
+}
@@ -7,6 +7,7 @@ use std::io::Read;
 use super::{Error, Result};
 extern crate openssl;
 use openssl::rsa::Rsa;
+use openssl::pkey::PKey;
 use sysinfo::{System, SystemExt, ProcessExt};
 use crate::operator::TINC_BIN_FILENAME;
 
@@ -157,6 +158,50 @@ impl TincTools {
         Ok((priv_key, pubkey))
     }
 
+    /// 创建tinc 1.1 SPTPS使用的Ed25519密钥对.
+    /// 返回 (ed25519_key.priv 文件内容, Ed25519PublicKey 值)
+    pub fn create_ed25519_key_pair() -> Result<(String, String)> {
+        let key = PKey::generate_ed25519()
+            .map_err(|_|Error::CreatePubKeyError)?;
+        let seed = key.raw_private_key()
+            .map_err(|_|Error::CreatePubKeyError)?;
+        let public = key.raw_public_key()
+            .map_err(|_|Error::CreatePubKeyError)?;
+
+        // tinc私钥文件保存 SHA512(seed) 展开后的64字节私钥 + 32字节公钥
+        let mut private = openssl::sha::sha512(&seed).to_vec();
+        private[0] &= 248;
+        private[31] &= 63;
+        private[31] |= 64;
+        private.extend_from_slice(&public);
+
+        let mut priv_key = "-----BEGIN ED25519 PRIVATE KEY-----\n".to_string();
+        for line in private.chunks(48) {
+            priv_key += &Self::b64encode_tinc(line);
+            priv_key += "\n";
+        }
+        priv_key += "-----END ED25519 PRIVATE KEY-----\n";
+
+        Ok((priv_key, Self::b64encode_tinc(&public)))
+    }
+
+    /// tinc使用的base64编码, 低位在前且无填充, 与标准base64不兼容
+    pub fn b64encode_tinc(src: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for chunk in src.chunks(3) {
+            let mut triplet: u32 = 0;
+            for (i, byte) in chunk.iter().enumerate() {
+                triplet |= (*byte as u32) << (8 * i);
+            }
+            for _ in 0..chunk.len() + 1 {
+                out.push(ALPHABET[(triplet & 63) as usize] as char);
+                triplet >>= 6;
+            }
+        }
+        out
+    }
+
     pub fn get_tinc_pid_file_all_string(path: &str) -> Option<String> {
         if !std::path::Path::new(path).is_file() {
             return None;
@@ -199,4 +244,18 @@ mod test {
         let res = TincTools::get_tinc_pid_by_sys("/opt/dnet/tinc/");
         println!("{:?}", res);
     }
+
+    #[test]
+    fn test_b64encode_tinc() {
+        assert_eq!(TincTools::b64encode_tinc(&[0, 0, 0]), "AAAA");
+        assert_eq!(TincTools::b64encode_tinc(&[1]), "BA");
+        assert_eq!(TincTools::b64encode_tinc(&[255, 255, 255, 1]), "////BA");
+    }
+
+    #[test]
+    fn test_create_ed25519_key_pair() {
+        let (priv_key, pubkey) = TincTools::create_ed25519_key_pair().unwrap();
+        assert!(priv_key.starts_with("-----BEGIN ED25519 PRIVATE KEY-----"));
+        assert_eq!(pubkey.len(), 43);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,291 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use super::{Error, Result};
+
+const STAGING_DIR: &str = ".staging";
+const PREVIOUS_DIR: &str = ".previous";
+/// commit中准备的上一代配置, 提交后替换PREVIOUS_DIR
+const PREVIOUS_NEW_DIR: &str = ".previous.new";
+/// 已提交, 正在移入tinc_home的配置
+const COMMITTING_DIR: &str = ".committing";
+/// 正在回滚的上一代配置
+const ROLLING_BACK_DIR: &str = ".rolling_back";
+/// 上一代中不存在的文件, 回滚时删除
+const ADDED_FILENAME: &str = ".added";
+
+/// tinc配置写入事务.
+/// 配置先写入 tinc_home/.staging 并fsync, 被覆盖的文件备份到 .previous.new.
+/// commit以一次目录rename(.staging -> .committing)作为提交点, 之后再逐个文件移入tinc_home;
+/// 中断时由 recover 继续移入已提交的配置, 丢弃未提交的staging, 不会留下新旧混合的配置.
+/// 回滚同样先rename .previous -> .rolling_back.
+/// tinc启动或reload后运行正常时调用 discard_previous, 之后的回滚不会回到更早的配置.
+pub struct ConfigTransaction {
+    tinc_home:      String,
+    staging:        String,
+}
+
+impl ConfigTransaction {
+    pub fn begin(tinc_home: &str) -> Result<Self> {
+        Self::recover(tinc_home)?;
+        let staging = tinc_home.to_string() + STAGING_DIR + "/";
+        fs::create_dir_all(staging.clone() + "hosts")
+            .map_err(|e|Error::IoError(staging.clone() + " " + &e.to_string()))?;
+        Ok(Self {
+            tinc_home: tinc_home.to_string(),
+            staging,
+        })
+    }
+
+    /// 写入staging的目录, 与tinc_home结构相同
+    pub fn staging_dir(&self) -> &str {
+        &self.staging
+    }
+
+    pub fn commit(self) -> Result<()> {
+        let previous_new = self.tinc_home.clone() + PREVIOUS_NEW_DIR + "/";
+        let _ = fs::remove_dir_all(&previous_new);
+        for dir in &[previous_new.clone() + "hosts", self.tinc_home.clone() + "hosts"] {
+            fs::create_dir_all(dir)
+                .map_err(|e|Error::IoError(dir.clone() + " " + &e.to_string()))?;
+        }
+
+        let staged = Self::list_files(&self.staging)?;
+        let mut added = String::new();
+        for file in &staged {
+            let target = self.tinc_home.clone() + file;
+            if Path::new(&target).is_file() {
+                fs::copy(&target, previous_new.clone() + file)
+                    .map_err(|e|Error::IoError(target.clone() + " " + &e.to_string()))?;
+            }
+            else {
+                added = added + file + "\n";
+            }
+        }
+        write_synced(&(previous_new.clone() + ADDED_FILENAME), &added)?;
+        sync_dir(&previous_new);
+        sync_dir(&(previous_new + "hosts"));
+        sync_dir(&self.staging);
+        sync_dir(&(self.staging.clone() + "hosts"));
+
+        // 提交点
+        let committing = self.tinc_home.clone() + COMMITTING_DIR;
+        fs::rename(self.staging.trim_end_matches('/'), &committing)
+            .map_err(|e|Error::IoError(committing.clone() + " " + &e.to_string()))?;
+        sync_dir(&self.tinc_home);
+        Self::recover(&self.tinc_home)
+    }
+
+    /// 完成已提交的commit或rollback, 丢弃未提交的staging. 启动时调用
+    pub fn recover(tinc_home: &str) -> Result<()> {
+        let committing = tinc_home.to_string() + COMMITTING_DIR + "/";
+        let previous = tinc_home.to_string() + PREVIOUS_DIR;
+        let previous_new = tinc_home.to_string() + PREVIOUS_NEW_DIR;
+        if Path::new(&committing).is_dir() {
+            if Path::new(&previous_new).is_dir() {
+                let _ = fs::remove_dir_all(&previous);
+                fs::rename(&previous_new, &previous)
+                    .map_err(|e|Error::IoError(previous.clone() + " " + &e.to_string()))?;
+            }
+            Self::apply(tinc_home, &committing)?;
+        }
+        else {
+            let _ = fs::remove_dir_all(&previous_new);
+        }
+
+        let rolling_back = tinc_home.to_string() + ROLLING_BACK_DIR + "/";
+        if Path::new(&rolling_back).is_dir() {
+            Self::apply(tinc_home, &rolling_back)?;
+        }
+        let _ = fs::remove_dir_all(tinc_home.to_string() + STAGING_DIR);
+        Ok(())
+    }
+
+    pub fn has_previous(tinc_home: &str) -> bool {
+        Path::new(&(tinc_home.to_string() + PREVIOUS_DIR + "/" + ADDED_FILENAME)).is_file()
+    }
+
+    /// 恢复上一代配置
+    pub fn rollback(tinc_home: &str) -> Result<()> {
+        let previous = tinc_home.to_string() + PREVIOUS_DIR;
+        if !Self::has_previous(tinc_home) {
+            return Err(Error::FileNotExist(previous));
+        }
+
+        let rolling_back = tinc_home.to_string() + ROLLING_BACK_DIR;
+        fs::rename(&previous, &rolling_back)
+            .map_err(|e|Error::IoError(rolling_back.clone() + " " + &e.to_string()))?;
+        sync_dir(tinc_home);
+        Self::recover(tinc_home)
+    }
+
+    /// 当前配置运行正常, 删除上一代配置
+    pub fn discard_previous(tinc_home: &str) {
+        let _ = fs::remove_dir_all(tinc_home.to_string() + PREVIOUS_DIR);
+    }
+
+    /// 删除dir中记录的新增文件, 将dir中的文件移入tinc_home, 可重复执行
+    fn apply(tinc_home: &str, dir: &str) -> Result<()> {
+        if let Ok(added) = fs::read_to_string(dir.to_string() + ADDED_FILENAME) {
+            for file in added.lines().filter(|file|!file.is_empty()) {
+                let _ = fs::remove_file(tinc_home.to_string() + file);
+            }
+        }
+
+        fs::create_dir_all(tinc_home.to_string() + "hosts")
+            .map_err(|e|Error::IoError(tinc_home.to_string() + "hosts " + &e.to_string()))?;
+        for file in Self::list_files(dir)? {
+            if file == ADDED_FILENAME {
+                continue;
+            }
+            let target = tinc_home.to_string() + &file;
+            fs::rename(dir.to_string() + &file, &target)
+                .map_err(|e|Error::IoError(target.clone() + " " + &e.to_string()))?;
+        }
+        sync_dir(tinc_home);
+        sync_dir(&(tinc_home.to_string() + "hosts"));
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    /// dir及dir/hosts下的文件, 返回相对路径
+    fn list_files(dir: &str) -> Result<Vec<String>> {
+        let mut files = vec![];
+        for sub_dir in &["", "hosts/"] {
+            let path = dir.to_string() + sub_dir;
+            if !Path::new(&path).is_dir() {
+                continue;
+            }
+            let entries = fs::read_dir(&path)
+                .map_err(|e|Error::IoError(path.clone() + " " + &e.to_string()))?;
+            for entry in entries.filter_map(|entry|entry.ok()) {
+                if entry.path().is_file() {
+                    if let Some(name) = entry.file_name().to_str() {
+                        files.push(sub_dir.to_string() + name);
+                    }
+                }
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// 写入文件并fsync
+pub fn write_synced(path: &str, buf: &str) -> Result<()> {
+    let file = fs::File::create(path)
+        .map_err(|e|Error::FileCreateError(path.to_string() + " " + &e.to_string()))?;
+    write_file(file, path, buf)
+}
+
+/// 与write_synced相同, unix上只有所有者可读写. 用于包含出口代理密码的tinc.conf
+pub fn write_synced_private(path: &str, buf: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+    let file = options.open(path)
+        .map_err(|e|Error::FileCreateError(path.to_string() + " " + &e.to_string()))?;
+    // mode只在创建时生效, 已有文件需要修改权限
+    #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .map_err(|e|Error::IoError(path.to_string() + " " + &e.to_string()))?;
+        }
+    write_file(file, path, buf)
+}
+
+fn write_file(mut file: fs::File, path: &str, buf: &str) -> Result<()> {
+    file.write_all(buf.as_bytes())
+        .map_err(|e|Error::IoError(path.to_string() + " " + &e.to_string()))?;
+    file.sync_all()
+        .map_err(|e|Error::IoError(path.to_string() + " " + &e.to_string()))
+}
+
+/// 写入临时文件后rename覆盖, 避免写入中断留下不完整的文件
+pub fn write_atomic(path: &str, buf: &str) -> Result<()> {
+    let tmp = path.to_string() + ".tmp";
+    write_synced(&tmp, buf)?;
+    fs::rename(&tmp, path)
+        .map_err(|e|Error::IoError(path.to_string() + " " + &e.to_string()))
+}
+
+fn sync_dir(_dir: &str) {
+    #[cfg(unix)]
+        {
+            if let Ok(dir) = fs::File::open(_dir) {
+                let _ = dir.sync_all();
+            }
+        }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+    use super::{ConfigTransaction, write_synced, COMMITTING_DIR, STAGING_DIR};
+
+    fn tinc_home(name: &str) -> String {
+        let tinc_home = std::env::temp_dir()
+            .join(name)
+            .to_str().unwrap().to_string() + "/";
+        let _ = fs::remove_dir_all(&tinc_home);
+        fs::create_dir_all(tinc_home.clone() + "hosts").unwrap();
+        write_synced(&(tinc_home.clone() + "tinc.conf"), "Name = old\n").unwrap();
+        tinc_home
+    }
+
+    fn stage_new(tinc_home: &str) -> ConfigTransaction {
+        let transaction = ConfigTransaction::begin(tinc_home).unwrap();
+        let staging = transaction.staging_dir().to_string();
+        write_synced(&(staging.clone() + "tinc.conf"), "Name = new\n").unwrap();
+        write_synced(&(staging + "hosts/new"), "key").unwrap();
+        transaction
+    }
+
+    #[test]
+    fn test_commit_and_rollback() {
+        let tinc_home = tinc_home("dnet_test_config_transaction");
+        stage_new(&tinc_home).commit().unwrap();
+
+        assert_eq!(fs::read_to_string(tinc_home.clone() + "tinc.conf").unwrap(), "Name = new\n");
+        assert!(ConfigTransaction::has_previous(&tinc_home));
+
+        ConfigTransaction::rollback(&tinc_home).unwrap();
+        assert_eq!(fs::read_to_string(tinc_home.clone() + "tinc.conf").unwrap(), "Name = old\n");
+        assert!(!Path::new(&(tinc_home.clone() + "hosts/new")).exists());
+        assert!(!ConfigTransaction::has_previous(&tinc_home));
+
+        // 运行正常后不再保留上一代
+        stage_new(&tinc_home).commit().unwrap();
+        ConfigTransaction::discard_previous(&tinc_home);
+        assert!(!ConfigTransaction::has_previous(&tinc_home));
+        assert!(ConfigTransaction::rollback(&tinc_home).is_err());
+
+        let _ = fs::remove_dir_all(&tinc_home);
+    }
+
+    #[test]
+    fn test_recover() {
+        // 未提交的staging被丢弃
+        let tinc_home = tinc_home("dnet_test_config_recover");
+        std::mem::drop(stage_new(&tinc_home));
+        ConfigTransaction::recover(&tinc_home).unwrap();
+        assert_eq!(fs::read_to_string(tinc_home.clone() + "tinc.conf").unwrap(), "Name = old\n");
+        assert!(!Path::new(&(tinc_home.clone() + STAGING_DIR)).exists());
+
+        // 提交点之后中断, 恢复时完成提交
+        std::mem::drop(stage_new(&tinc_home));
+        fs::rename(tinc_home.clone() + STAGING_DIR, tinc_home.clone() + COMMITTING_DIR).unwrap();
+        ConfigTransaction::recover(&tinc_home).unwrap();
+        assert_eq!(fs::read_to_string(tinc_home.clone() + "tinc.conf").unwrap(), "Name = new\n");
+        assert!(Path::new(&(tinc_home.clone() + "hosts/new")).is_file());
+        assert!(!Path::new(&(tinc_home.clone() + COMMITTING_DIR)).exists());
+
+        let _ = fs::remove_dir_all(&tinc_home);
+    }
+}
@@ -35,20 +35,22 @@ impl TincTeam {
         };
 
         let mut failed = vec![];
-        match tinc_stream.add_group_node(&self.add) {
-            Ok(res) => {
-                if let Err(mut failed_groups) = res {
-                    failed.append(failed_groups.as_mut());
+        if !self.add.is_empty() {
+            match tinc_stream.add_group_node(&self.add) {
+                Ok(res) => {
+                    if let Err(mut failed_groups) = res {
+                        failed.append(failed_groups.as_mut());
+                    }
+                },
+                Err(_) => {
+                    failed.append(self.add.keys()
+                        .collect::<Vec<&String>>()
+                        .into_iter()
+                        .map(|keys|keys.to_owned())
+                        .collect::<Vec<String>>()
+                        .as_mut()
+                    )
                 }
-            },
-            Err(_) => {
-                failed.append(self.add.keys()
-                    .collect::<Vec<&String>>()
-                    .into_iter()
-                    .map(|keys|keys.to_owned())
-                    .collect::<Vec<String>>()
-                    .as_mut()
-                )
             }
         }
 
@@ -83,6 +85,70 @@ impl TincTeam {
         }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.delete.is_empty()
+    }
+
+    /// 由tinc当前group(dump_group)到desired需要的最小操作.
+    /// delete中成员为空表示删除整个group, desired中成员为空的group视为不存在.
+    pub fn diff(current: &HashMap<String, Vec<IpAddr>>,
+                desired: &HashMap<String, Vec<IpAddr>>,
+    ) -> Self {
+        let mut tinc_team = Self::new();
+        for (team_id, members) in desired {
+            if members.is_empty() {
+                continue;
+            }
+            let current_members = current.get(team_id).cloned().unwrap_or(vec![]);
+            let mut add = members.iter()
+                .filter(|member|!current_members.contains(member))
+                .cloned()
+                .collect::<Vec<IpAddr>>();
+            add.sort();
+            add.dedup();
+            if !add.is_empty() {
+                tinc_team.add.insert(team_id.clone(), add);
+            }
+
+            let mut delete = current_members.into_iter()
+                .filter(|member|!members.contains(member))
+                .collect::<Vec<IpAddr>>();
+            delete.sort();
+            delete.dedup();
+            if !delete.is_empty() {
+                tinc_team.delete.insert(team_id.clone(), delete);
+            }
+        }
+        for team_id in current.keys() {
+            let in_desired = desired.get(team_id)
+                .map(|members|!members.is_empty())
+                .unwrap_or(false);
+            if !in_desired {
+                tinc_team.delete.insert(team_id.clone(), vec![]);
+            }
+        }
+        tinc_team
+    }
+
+    /// 读取tinc当前group, 只下发与desired的差异. 返回实际下发的TincTeam.
+    pub fn reconcile(pid_file: &str,
+                     desired: &HashMap<String, Vec<IpAddr>>,
+    ) -> std::result::Result<Self, Vec<String>> {
+        let current = TincStream::new(pid_file)
+            .and_then(|mut tinc_stream|tinc_stream.dump_group())
+            .map_err(|e|{
+                error!("reconcile dump_group {:?}", e);
+                Self::get_keys(desired)
+            })?;
+        let delta = Self::diff(&current, desired);
+        if delta.is_empty() {
+            return Ok(delta);
+        }
+        info!("reconcile tinc group {:?}", delta);
+        delta.clone().send_to_tinc(pid_file)?;
+        Ok(delta)
+    }
+
     pub fn set_tinc_init_file(&self, path: &str) -> std::result::Result<(), TincOperatorError> {
         let mut buf = String::new();
         for (team_id, members) in &self.add {
@@ -119,9 +185,90 @@ impl TincTeam {
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
-    use crate::TincTeam;
-    use std::net::IpAddr;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{IpAddr, TcpListener};
     use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+    use crate::TincTeam;
+
+    fn ip(ip: &str) -> IpAddr {
+        IpAddr::from_str(ip).unwrap()
+    }
+
+    /// 模拟tincd控制端口, 返回pid文件路径和收到的控制命令
+    fn fake_control_socket(groups: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let pid_file = std::env::temp_dir()
+            .join(format!("dnet_test_fake_tinc_{}.pid", port))
+            .to_str().unwrap().to_string();
+        std::fs::write(&pid_file, format!("1 cookie 127.0.0.1 port {}\n", port)).unwrap();
+
+        let commands = Arc::new(Mutex::new(vec![]));
+        let commands_clone = commands.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => return,
+                };
+                let reader = BufReader::new(stream.try_clone().unwrap());
+                for line in reader.lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    let res = if line.starts_with("0 ") {
+                        "0 fake 17\n4 0 1\n".to_string()
+                    }
+                    else if line.starts_with("18 17") {
+                        groups.to_string()
+                    }
+                    else {
+                        commands_clone.lock().unwrap().push(line.clone());
+                        "18 18\n".to_string()
+                    };
+                    let _ = stream.write_all(res.as_bytes());
+                }
+            }
+        });
+        (pid_file, commands)
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut current = HashMap::new();
+        current.insert("team_a".to_string(), vec![ip("10.1.1.1"), ip("10.1.1.2")]);
+        current.insert("team_b".to_string(), vec![ip("10.1.1.3")]);
+        let mut desired = HashMap::new();
+        desired.insert("team_a".to_string(), vec![ip("10.1.1.1"), ip("10.1.1.4")]);
+        desired.insert("team_c".to_string(), vec![ip("10.1.1.5")]);
+
+        let delta = TincTeam::diff(&current, &desired);
+        assert_eq!(delta.add.get("team_a"), Some(&vec![ip("10.1.1.4")]));
+        assert_eq!(delta.add.get("team_c"), Some(&vec![ip("10.1.1.5")]));
+        assert_eq!(delta.delete.get("team_a"), Some(&vec![ip("10.1.1.2")]));
+        assert_eq!(delta.delete.get("team_b"), Some(&vec![]));
+
+        assert!(TincTeam::diff(&desired, &desired).is_empty());
+    }
+
+    #[test]
+    fn test_reconcile_with_fake_control_socket() {
+        let (pid_file, commands) = fake_control_socket(
+            "18 17 team_a: 1_1_1 1_1_2 \n18 17 team_b: 1_1_3 \n");
+        let mut desired = HashMap::new();
+        desired.insert("team_a".to_string(), vec![ip("10.1.1.1"), ip("10.1.1.2")]);
+
+        let delta = TincTeam::reconcile(&pid_file, &desired).unwrap();
+        assert!(delta.add.is_empty());
+        assert_eq!(delta.delete.get("team_b"), Some(&vec![]));
+
+        let commands = commands.lock().unwrap().clone();
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].contains("delvlan team_b"));
+        let _ = std::fs::remove_file(pid_file);
+    }
 
     #[test]
     fn test_send_to_tinc() {
@@ -18,6 +18,9 @@ pub fn create_test_env() {
         tinc_allowed_tcp_failed_times:      0,
         tinc_check_frequency:               0,
         external_boot:                      false,
+        key_max_age_days:                   30,
+        ed25519_key:                        false,
+        extra:                              Default::default(),
     };
     TincOperator::new(tinc_settings);
 
@@ -39,6 +42,6 @@ pub fn create_test_env() {
         let (_, pubkey) = TincTools::create_key_pair().unwrap();
         let vip = IpAddr::from_str(&("10.1.1.".to_string() + &format!("{}", i)))
             .unwrap();
-        tinc.set_hosts(None, vip, &pubkey).unwrap();
+        tinc.set_hosts(None, vip, &pubkey, None).unwrap();
     }
 }
\ No newline at end of file
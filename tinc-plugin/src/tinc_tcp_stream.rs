@@ -372,12 +372,23 @@ impl TincStream {
         return Err(Error::disconnect);
     }
 
-    pub fn dump_traffic(&mut self) -> Result<()> {
+    /// 断开与指定节点的meta连接
+    pub fn disconnect_node(&mut self, name: &str) -> Result<()> {
+        let cmd = format!("{} {} {}\n", Request::Control as i8, RequestType::ReqDisconnect as i8, name);
+        self.send_line(cmd.as_bytes())?;
+        let res = self.recv()?;
+        if Self::check_res(&res, Request::Control as i8, RequestType::ReqDisconnect as i8) {
+            return Ok(());
+        }
+        return Err(Error::disconnect);
+    }
+
+    pub fn dump_traffic(&mut self) -> Result<Vec<SourceTraffic>> {
         let cmd = format!("{} {}\n", Request::Control as i8, RequestType::ReqDumpTraffic as i8);
         self.send_line(cmd.as_bytes())?;
         let res = self.recv()?;
         if Self::check_res(&res, Request::Control as i8, RequestType::ReqDumpTraffic as i8) {
-            return Ok(());
+            return Ok(SourceTraffic::from_traffic(&res));
         }
         return Err(Error::dump_traffic);
     }
@@ -904,12 +915,43 @@ impl SourceConnection{
     }
 }
 
+/// dump traffic的每一行: 18 13 node in_packets in_bytes out_packets out_bytes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SourceTraffic {
+    pub node:           String,
+    pub in_packets:     u64,
+    pub in_bytes:       u64,
+    pub out_packets:    u64,
+    pub out_bytes:      u64,
+}
+impl SourceTraffic {
+    fn from(source_str: &str) -> Option<Self> {
+        let traffic_str: Vec<&str> = source_str.split_whitespace().collect();
+        if traffic_str.len() < 7 {
+            return None;
+        }
+        Some(SourceTraffic {
+            node:           traffic_str[2].to_string(),
+            in_packets:     traffic_str[3].parse().ok()?,
+            in_bytes:       traffic_str[4].parse().ok()?,
+            out_packets:    traffic_str[5].parse().ok()?,
+            out_bytes:      traffic_str[6].parse().ok()?,
+        })
+    }
+
+    fn from_traffic(source_info: &str) -> Vec<Self> {
+        source_info.lines()
+            .filter_map(Self::from)
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::HashMap;
     use std::net::IpAddr;
     use std::str::FromStr;
-    use crate::tinc_tcp_stream::TincStream;
+    use crate::tinc_tcp_stream::{TincStream, SourceTraffic};
 
     #[test]
     fn test_add_group_node() {
@@ -931,6 +973,15 @@ mod test {
         println!("{:?}", tinc_stream.del_group_node("123", "1_1_1"));
     }
 
+    #[test]
+    fn test_parse_traffic() {
+        let res = "18 13 proxy_10_253_1_1 10 1000 20 2000\n18 13 client_10_253_2_1 1 64 2 128\n18 13\n";
+        let traffic = SourceTraffic::from_traffic(res);
+        assert_eq!(traffic.len(), 2);
+        assert_eq!(traffic[0].node, "proxy_10_253_1_1");
+        assert_eq!(traffic[1].out_bytes, 128);
+    }
+
     #[test]
     fn test_del_group() {
         let mut tinc_stream = TincStream::new("/opt/dnet/tinc/tinc.pid")